@@ -0,0 +1,101 @@
+
+use anyhow::{bail, Context};
+use serde::{Serialize, Deserialize};
+use std::collections::HashSet;
+
+/// Initial chain state and parameters shared by every node of a network.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Genesis {
+    pub chain_id: String,
+    pub accounts: Vec<GenesisAccount>,
+    #[serde(default)]
+    pub validators: Vec<GenesisValidator>,
+    #[serde(default)]
+    pub consensus: ConsensusParams,
+    #[serde(default)]
+    pub da: DaParams,
+    #[serde(default)]
+    pub fees: FeeParams,
+    /// Account allowed to mint native coins; nobody can when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub governance: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GenesisAccount { pub addr: String, pub balance: types::Amount }
+
+/// `addr` is the validator's QUIC socket address; `pubkey` is its hex ed25519 key.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GenesisValidator { pub id: u32, pub addr: String, pub pubkey: String }
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConsensusParams { pub pacemaker_ms: u64 }
+impl Default for ConsensusParams { fn default() -> Self { Self { pacemaker_ms: 60 } } }
+
+/// Reed-Solomon layout of each batch: `k` data shards plus `m` parity shards.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DaParams { pub k: u32, pub m: u32 }
+impl Default for DaParams { fn default() -> Self { Self { k: 2, m: 1 } } }
+
+/// Minimum fee per unit of gas, and the account collecting fees (burned when unset).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FeeParams {
+    #[serde(default)]
+    pub gas_price: u64,
+    #[serde(default)]
+    pub treasury: Option<String>,
+}
+
+impl Genesis {
+    /// Single-account local network funding the `alice` dev key, used when no genesis file is given.
+    pub fn dev() -> Self {
+        let (_sk, alice) = crypto::keypair_from_seed(b"alice");
+        Self {
+            chain_id: types::DEFAULT_CHAIN_ID.to_string(),
+            accounts: vec![GenesisAccount { addr: crypto::address(&alice), balance: types::Amount(1_000_000_000_000) }],
+            validators: Vec::new(),
+            consensus: ConsensusParams::default(),
+            da: DaParams::default(),
+            fees: FeeParams::default(),
+            governance: None,
+        }
+    }
+
+    /// blake3 over the canonical JSON encoding, so formatting and YAML vs JSON don't matter.
+    pub fn hash(&self) -> [u8; 32] {
+        *blake3::hash(&serde_json::to_vec(self).expect("genesis serializes")).as_bytes()
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.chain_id.is_empty() { bail!("genesis: empty chain_id"); }
+        let mut addrs = HashSet::new();
+        for a in &self.accounts { if !addrs.insert(&a.addr) { bail!("genesis: duplicate account {}", a.addr); } }
+        let mut ids = HashSet::new();
+        for v in &self.validators {
+            if !ids.insert(v.id) { bail!("genesis: duplicate validator id {}", v.id); }
+            v.addr.parse::<std::net::SocketAddr>().with_context(|| format!("genesis: validator {} addr", v.id))?;
+            v.pubkey()?;
+        }
+        if self.da.k == 0 || self.da.m == 0 || self.da.k as u64 + self.da.m as u64 > 256 { bail!("genesis: invalid DA layout k={} m={}", self.da.k, self.da.m); }
+        if self.fees.treasury.as_deref() == Some("") { bail!("genesis: empty fee treasury"); }
+        if self.governance.as_deref() == Some("") { bail!("genesis: empty governance account"); }
+        if self.consensus.pacemaker_ms == 0 { bail!("genesis: pacemaker_ms must be positive"); }
+        Ok(())
+    }
+}
+
+impl GenesisValidator {
+    pub fn pubkey(&self) -> anyhow::Result<crypto::PubKey> {
+        let bytes: [u8; 32] = hex::decode(&self.pubkey).ok().and_then(|b| b.try_into().ok())
+            .with_context(|| format!("genesis: validator {} pubkey must be 32 hex-encoded bytes", self.id))?;
+        Ok(crypto::PubKey(bytes))
+    }
+}
+
+/// Loads and validates a genesis file; `.yaml`/`.yml` files are read as YAML, anything else as JSON.
+pub async fn load_genesis(path: &str) -> anyhow::Result<Genesis> {
+    let data = tokio::fs::read_to_string(path).await.with_context(|| format!("reading genesis {path}"))?;
+    let g: Genesis = if path.ends_with(".yaml") || path.ends_with(".yml") { serde_yaml::from_str(&data)? } else { serde_json::from_str(&data)? };
+    g.validate()?;
+    Ok(g)
+}
@@ -3,6 +3,9 @@ use serde::{Serialize, Deserialize};
 use tokio::fs;
 use tracing::info;
 
+pub mod genesis;
+pub use genesis::{Genesis, load_genesis};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NodeConfig {
     pub rpc_addr: String,
@@ -13,11 +16,40 @@ pub struct NodeConfig {
     pub validators_keys: String,
     pub node_sk: Option<String>,
     pub db_path: String,
+    #[serde(default)]
+    pub da_peers: Vec<String>,
+    #[serde(default = "default_da_samples")]
+    pub da_samples: u32,
+    /// Must match the genesis chain id when set.
+    #[serde(default)]
+    pub chain_id: Option<String>,
+    #[serde(default)]
+    pub genesis_file: Option<String>,
+    #[serde(default)]
+    pub genesis_hash: Option<String>,
+    #[serde(default)]
+    pub mempool: MempoolSettings,
+}
+
+fn default_da_samples() -> u32 { 8 }
+
+/// Mempool tuning; a field left out keeps the `MEMPOOL_*` environment value or the built-in default.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MempoolSettings {
+    pub flush_ms: Option<u64>,
+    pub max_batch_txs: Option<usize>,
+    pub max_batch_bytes: Option<usize>,
+    pub channel_capacity: Option<usize>,
+    pub dedup_secs: Option<u64>,
+    pub max_txs: Option<usize>,
+    pub max_bytes: Option<usize>,
 }
 
 pub async fn load_yaml(path: &str) -> anyhow::Result<NodeConfig> {
     let data = fs::read_to_string(path).await?;
     let cfg: NodeConfig = serde_yaml::from_str(&data)?;
+    if cfg.mempool.flush_ms == Some(0) { anyhow::bail!("config: mempool flush_ms must be positive"); }
+    if cfg.mempool.channel_capacity == Some(0) { anyhow::bail!("config: mempool channel_capacity must be positive"); }
     Ok(cfg)
 }
 
@@ -0,0 +1,52 @@
+use configd::{Genesis, load_genesis};
+
+const JSON: &str = r#"{
+  "chain_id": "v-test",
+  "accounts": [{ "addr": "aa01", "balance": 500 }, { "addr": "bb02", "balance": 7 }],
+  "validators": [{ "id": 1, "addr": "127.0.0.1:7000", "pubkey": "0101010101010101010101010101010101010101010101010101010101010101" }],
+  "consensus": { "pacemaker_ms": 100 },
+  "da": { "k": 4, "m": 2 },
+  "fees": { "gas_price": 3, "treasury": "cc03" },
+  "governance": "dd04"
+}"#;
+
+const YAML: &str = "
+chain_id: v-test
+accounts:
+  - { addr: aa01, balance: 500 }
+  - { addr: bb02, balance: 7 }
+validators:
+  - id: 1
+    addr: 127.0.0.1:7000
+    pubkey: '0101010101010101010101010101010101010101010101010101010101010101'
+consensus: { pacemaker_ms: 100 }
+da: { k: 4, m: 2 }
+fees: { gas_price: 3, treasury: cc03 }
+governance: dd04
+";
+
+async fn load(name: &str, body: &str) -> anyhow::Result<Genesis> {
+    let path = std::env::temp_dir().join(format!("genesis-{}-{}", std::process::id(), name));
+    tokio::fs::write(&path, body).await?;
+    load_genesis(path.to_str().unwrap()).await
+}
+
+#[tokio::test]
+async fn json_and_yaml_hash_the_same() {
+    let j = load("a.json", JSON).await.unwrap();
+    let y = load("a.yaml", YAML).await.unwrap();
+    assert_eq!(j, y);
+    assert_eq!(j.hash(), y.hash());
+    assert_eq!((j.fees.gas_price, j.fees.treasury.as_deref()), (3, Some("cc03")));
+    assert_eq!(j.governance.as_deref(), Some("dd04"));
+    assert_ne!(j.hash(), Genesis::dev().hash());
+}
+
+#[tokio::test]
+async fn rejects_invalid_genesis() {
+    assert!(load("dup.json", &JSON.replace("bb02", "aa01")).await.is_err());
+    assert!(load("key.json", &JSON.replace("01010101010101010101", "zz")).await.is_err());
+    assert!(load("da.json", &JSON.replace("\"k\": 4", "\"k\": 0")).await.is_err());
+    assert!(load("parity.json", &JSON.replace("\"m\": 2", "\"m\": 0")).await.is_err());
+    assert!(load("wrap.json", &JSON.replace("\"m\": 2", "\"m\": 4294967295")).await.is_err());
+}
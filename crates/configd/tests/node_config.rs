@@ -0,0 +1,25 @@
+use configd::{load_yaml, MempoolSettings, NodeConfig};
+
+const YAML: &str = "
+rpc_addr: 127.0.0.1:8080
+quic_addr: 127.0.0.1:7000
+p2p_listen: /ip4/127.0.0.1/tcp/9000
+node_id: 1
+validators: ''
+validators_keys: ''
+db_path: db
+";
+
+async fn load(name: &str, body: &str) -> anyhow::Result<NodeConfig> {
+    let path = std::env::temp_dir().join(format!("node-config-{}-{}.yaml", std::process::id(), name));
+    tokio::fs::write(&path, body).await?;
+    load_yaml(path.to_str().unwrap()).await
+}
+
+#[tokio::test]
+async fn mempool_settings_are_optional() {
+    assert_eq!(load("none", YAML).await.unwrap().mempool, MempoolSettings::default());
+    let c = load("some", &format!("{YAML}mempool: {{ flush_ms: 10, max_txs: 500 }}\n")).await.unwrap();
+    assert_eq!(c.mempool, MempoolSettings { flush_ms: Some(10), max_txs: Some(500), ..MempoolSettings::default() });
+    assert!(load("zero", &format!("{YAML}mempool: {{ flush_ms: 0 }}\n")).await.is_err());
+}
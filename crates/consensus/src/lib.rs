@@ -17,18 +17,71 @@ pub struct Signed { pub voter: u32, pub sig: crypto::Sig }
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QuorumCert {
     pub view: u64,
+    /// Height the payload commits at; signed along with the root, so a QC can't be replayed at another height.
+    pub height: u64,
     pub root: [u8;32],
+    pub state: Option<StateCommitment>,
     pub voters: Vec<u32>,
     pub sigs: Vec<Signed>,
 }
 
+impl QuorumCert {
+    /// Checks that a quorum of distinct validators signed this view, height, payload root and state commitment.
+    pub fn verify(&self, validators: &Validators) -> bool {
+        let bytes = block_sign_bytes("VOTE", self.view, self.height, &self.root, self.state.as_ref());
+        let mut seen = std::collections::HashSet::new();
+        let valid = self.sigs.iter().filter(|s| seen.insert(s.voter))
+            .filter(|s| validators.get_pub(s.voter).is_some_and(|pk| crypto::verify(pk, &bytes, &s.sig)))
+            .count();
+        valid >= validators.quorum()
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TimeoutCert { pub view: u64, pub sigs: Vec<Signed> }
 
+/// Post-execution state root at `height`; each proposal commits to the proposer's latest one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateCommitment { pub height: u64, pub root: [u8;32] }
+
+/// State roots reported by the local executor, keyed by committed height, plus the newest QC
+/// that certifies one of them and the QC that committed each recent height.
+#[derive(Clone, Default)]
+pub struct StateRoots { roots: Arc<RwLock<BTreeMap<u64, [u8;32]>>>, certified: Arc<RwLock<Option<QuorumCert>>>, commits: Arc<RwLock<BTreeMap<u64, QuorumCert>>> }
+impl StateRoots {
+    const RETAIN: u64 = 1024;
+    pub fn new() -> Self { Self::default() }
+    pub fn record(&self, height: u64, root: [u8;32]) {
+        let mut w = self.roots.write();
+        w.insert(height, root);
+        while w.len() as u64 > Self::RETAIN { w.pop_first(); }
+    }
+    pub fn get(&self, height: u64) -> Option<[u8;32]> { self.roots.read().get(&height).copied() }
+    pub fn latest(&self) -> Option<StateCommitment> { self.roots.read().last_key_value().map(|(&height, &root)| StateCommitment { height, root }) }
+    /// Newest QC carrying a state commitment; its `state` is the latest certified state root.
+    pub fn certified(&self) -> Option<QuorumCert> { self.certified.read().clone() }
+    /// Remembers the QC whose payload was committed at `height`.
+    pub fn record_commit(&self, height: u64, qc: QuorumCert) {
+        let mut w = self.commits.write();
+        w.insert(height, qc);
+        while w.len() as u64 > Self::RETAIN { w.pop_first(); }
+    }
+    pub fn commit_qc(&self, height: u64) -> Option<QuorumCert> { self.commits.read().get(&height).cloned() }
+    fn certify(&self, qc: &QuorumCert) {
+        let Some(c) = qc.state else { return };
+        let mut w = self.certified.write();
+        if w.as_ref().and_then(|q| q.state).is_none_or(|cur| c.height > cur.height) { *w = Some(qc.clone()); }
+    }
+    /// True if we executed `c.height` ourselves and got a different root.
+    fn diverges(&self, c: &StateCommitment) -> bool { self.get(c.height).is_some_and(|r| r != c.root) }
+}
+
 use net_quic::{QuicEvent, NetOut};
 use once_cell::sync::Lazy;
 use prometheus::{IntCounter, Histogram, register_int_counter, register_histogram};
-use bincode;
+use parking_lot::RwLock;
+use std::collections::BTreeMap;
+use std::sync::Arc;
 
 static PROPOSALS_SENT: Lazy<IntCounter> = Lazy::new(|| register_int_counter!("consensus_proposals_sent_total", "Proposals sent").unwrap());
 static VOTES_SENT: Lazy<IntCounter> = Lazy::new(|| register_int_counter!("consensus_votes_sent_total", "Votes sent").unwrap());
@@ -36,6 +89,7 @@ static QCS_FORMED: Lazy<IntCounter> = Lazy::new(|| register_int_counter!("consen
 static COMMITS: Lazy<IntCounter> = Lazy::new(|| register_int_counter!("consensus_commits_total", "Blocks committed").unwrap());
 static NEWVIEWS_SENT: Lazy<IntCounter> = Lazy::new(|| register_int_counter!("consensus_newviews_sent_total", "NewViews sent").unwrap());
 static TIMEOUTS_SENT: Lazy<IntCounter> = Lazy::new(|| register_int_counter!("consensus_timeouts_sent_total", "Timeouts sent").unwrap());
+static STATE_ROOT_MISMATCHES: Lazy<IntCounter> = Lazy::new(|| register_int_counter!("consensus_state_root_mismatches_total", "Proposals whose state root differs from ours").unwrap());
 static PROPOSAL_TO_COMMIT: Lazy<Histogram> = Lazy::new(|| register_histogram!("consensus_proposal_to_commit_seconds", "proposal->commit duration").unwrap());
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -43,10 +97,12 @@ pub enum ConsensusMsg {
     RbcShard { sender: u32, root: [u8;32], shard_index: u32, bytes: Vec<u8>, proof: MerkleProof },
     RbcEcho { sender: u32, root: [u8;32], sig: crypto::Sig },
     RbcReady { sender: u32, root: [u8;32], sig: crypto::Sig },
-    Proposal { view: u64, proposer: u32, root: [u8;32], da_proof: DaProof, high_qc: Option<QuorumCert>, sig: crypto::Sig },
-    Vote { view: u64, voter: u32, root: [u8;32], sig: crypto::Sig },
+    Proposal { view: u64, height: u64, proposer: u32, root: [u8;32], state: Option<StateCommitment>, da_proof: DaProof, high_qc: Option<QuorumCert>, sig: crypto::Sig },
+    Vote { view: u64, height: u64, voter: u32, root: [u8;32], state: Option<StateCommitment>, sig: crypto::Sig },
     NewView { view: u64, voter: u32, high_qc: Option<QuorumCert>, tc: Option<TimeoutCert>, sig: crypto::Sig },
     Timeout { view: u64, voter: u32, sig: crypto::Sig },
+    /// Sent by the leader once `qc` commits its payload at `qc.height`, so every node learns the committed root.
+    Commit { qc: QuorumCert },
 }
 
 #[derive(Debug, Clone)]
@@ -56,10 +112,16 @@ pub struct Validator {
     pub pubkey: crypto::PubKey,
 }
 
+/// Network-wide consensus and DA settings, normally taken from genesis.
+#[derive(Debug, Clone, Copy)]
+pub struct Params { pub pacemaker_ms: u64, pub da_k: u32, pub da_m: u32 }
+impl Default for Params { fn default() -> Self { Self { pacemaker_ms: 60, da_k: 2, da_m: 1 } } }
+
 #[derive(Clone)]
 pub struct Validators { pub self_id: u32, pub nodes: Vec<Validator> }
 impl Validators {
     pub fn len(&self) -> usize { self.nodes.len() }
+    pub fn is_empty(&self) -> bool { self.nodes.is_empty() }
     pub fn f(&self) -> usize { (self.len().saturating_sub(1)) / 3 }
     pub fn quorum(&self) -> usize { (2 * self.f()) + 1 }
     pub fn leader_for(&self, view: u64) -> &Validator { let idx = ((view - 1) as usize) % self.nodes.len(); &self.nodes[idx] }
@@ -86,7 +148,7 @@ impl RbcState {
                 for (idx, bytes) in vec { if (*idx as usize) < max_idx { shards[*idx as usize] = Some(bytes.clone()); } }
                 if let Ok(payload) = reed_solomon_erasure::galois_8::ReedSolomon::new(k as usize, m as usize).and_then(|rs| {
                     let mut shards_clone = shards.clone(); rs.reconstruct(&mut shards_clone)?;
-                    let mut out = Vec::new(); for i in 0..(k as usize) { out.extend_from_slice(shards_clone[i].as_ref().unwrap()); }
+                    let mut out = Vec::new(); for s in shards_clone.iter().take(k as usize) { out.extend_from_slice(s.as_ref().unwrap()); }
                     while out.last().copied() == Some(0) { out.pop(); } Ok::<Vec<u8>, reed_solomon_erasure::Error>(out)
                 }) { self.payloads.insert(root, payload); return true; }
             }
@@ -108,29 +170,41 @@ impl KeySet {
     }
 }
 fn sign_bytes(tag: &str, data: &[u8]) -> Vec<u8> { let mut v = Vec::new(); v.extend_from_slice(tag.as_bytes()); v.extend_from_slice(blake3::hash(data).as_bytes()); v }
+/// Bytes signed by proposals (`tag = "PROPOSAL"`) and votes (`tag = "VOTE"`): view, commit height, payload root and state commitment.
+pub fn block_sign_bytes(tag: &str, view: u64, height: u64, root: &[u8;32], state: Option<&StateCommitment>) -> Vec<u8> {
+    let mut d = [view.to_le_bytes().as_slice(), &height.to_le_bytes(), root].concat();
+    if let Some(c) = state { d.extend_from_slice(&c.height.to_le_bytes()); d.extend_from_slice(&c.root); }
+    sign_bytes(tag, &d)
+}
 
+#[allow(clippy::too_many_arguments)]
 pub async fn run_hotstuff_quic(
     mut from_mempool: mpsc::Receiver<Batch>,
-    mut to_exec: mpsc::Sender<(Batch, u64)>,
-    pacemaker_ms: u64,
+    to_exec: mpsc::Sender<(Batch, u64)>,
+    params: Params,
     net_out: mpsc::Sender<NetOut>,
     mut net_in: mpsc::Receiver<QuicEvent>,
     validators: Validators,
     keys: KeySet,
     qc_store: Option<std::sync::Arc<dyn store::QcTcStore>>,
+    da_store: Option<da::ShardStore>,
+    state_roots: Option<StateRoots>,
 ) {
-    let mut height: u64 = 1;
+    let mut height: u64 = state_roots.as_ref().and_then(|r| r.latest()).map_or(1, |c| c.height + 1);
     let mut view: u64 = 1;
-    let timeout = Duration::from_millis(pacemaker_ms);
+    let timeout = Duration::from_millis(params.pacemaker_ms);
     let mut pending_root: Option<[u8;32]> = None;
+    let mut proposed_state: Option<StateCommitment> = None;
+    let mut proposed_height: u64 = 0;
     let mut votes_ed: std::collections::HashMap<u32, crypto::Sig> = std::collections::HashMap::new();
     let mut rbc = RbcState::default();
     let f = validators.f();
     let quorum = validators.quorum();
     let mut prop_start: std::collections::HashMap<[u8;32], std::time::Instant> = std::collections::HashMap::new();
-    let k: u32 = 2; let m: u32 = 1;
+    let (k, m) = (params.da_k, params.da_m);
 
     let mut high_qc: Option<QuorumCert> = qc_store.as_ref().and_then(|s| s.load_high_qc());
+    if let (Some(qc), Some(r)) = (high_qc.as_ref(), state_roots.as_ref()) { r.certify(qc); }
 
     loop {
         tokio::select! {
@@ -143,6 +217,7 @@ pub async fn run_hotstuff_quic(
                         let msg = ConsensusMsg::RbcShard { sender: validators.self_id, root, shard_index: s.index, bytes: s.bytes.clone(), proof: s.proof.clone() };
                         broadcast(&net_out, &validators, &msg).await;
                     }
+                    if let Some(ds) = da_store.as_ref() { for s in shards { ds.put(s); } }
                     let echo_bytes = sign_bytes("RBC_ECHO", &root);
                     let echo_sig = keys.sign(&echo_bytes);
                     let echo = ConsensusMsg::RbcEcho { sender: validators.self_id, root, sig: echo_sig };
@@ -166,6 +241,7 @@ pub async fn run_hotstuff_quic(
                             match msg {
                                 ConsensusMsg::RbcShard { root, bytes, proof, .. } => {
                                     if proof_verify(&proof, da::digest(&bytes)) {
+                                        if let Some(ds) = da_store.as_ref() { ds.put(da::Shard { index: proof.index, k, m, bytes: bytes.clone(), proof: proof.clone() }); }
                                         rbc.push_shard(root, proof.index, bytes);
                                         let _ = rbc.try_reconstruct(root, k, m);
                                     }
@@ -191,8 +267,10 @@ pub async fn run_hotstuff_quic(
                                             if let Some(pr) = pending_root {
                                                 if pr == root && validators.leader_for(view).id == validators.self_id {
                                                     let da_proof = DaProof { ready_signers: e.iter().copied().collect(), merkle_root: root, k, m };
-                                                    let prop_bytes = sign_bytes("PROPOSAL", &[view.to_le_bytes().as_slice(), &root].concat());
-                                                    let prop = ConsensusMsg::Proposal { view, proposer: validators.self_id, root, da_proof, high_qc: high_qc.clone(), sig: keys.sign(&prop_bytes) };
+                                                    proposed_state = state_roots.as_ref().and_then(|r| r.latest());
+                                                    proposed_height = height;
+                                                    let prop_bytes = block_sign_bytes("PROPOSAL", view, height, &root, proposed_state.as_ref());
+                                                    let prop = ConsensusMsg::Proposal { view, height, proposer: validators.self_id, root, state: proposed_state, da_proof, high_qc: high_qc.clone(), sig: keys.sign(&prop_bytes) };
                                                     broadcast(&net_out, &validators, &prop).await; PROPOSALS_SENT.inc();
                                                     prop_start.insert(root, std::time::Instant::now());
                                                 }
@@ -200,47 +278,74 @@ pub async fn run_hotstuff_quic(
                                         }
                                     }
                                 }
-                                ConsensusMsg::Proposal { view: v, proposer, root, sig, .. } => {
-                                    let prop_bytes = sign_bytes("PROPOSAL", &[v.to_le_bytes().as_slice(), &root].concat());
+                                ConsensusMsg::Proposal { view: v, height: h, proposer, root, state, high_qc: prop_qc, sig, .. } => {
+                                    let prop_bytes = block_sign_bytes("PROPOSAL", v, h, &root, state.as_ref());
                                     if let Some(pk) = validators.get_pub(proposer) {
                                         if crypto::verify(pk, &prop_bytes, &sig) {
                                             view = v;
-                                            if rbc.has_payload(&root) {
-                                                let vote_bytes = sign_bytes("VOTE", &[view.to_le_bytes().as_slice(), &root].concat());
-                                                let vote = ConsensusMsg::Vote { view, voter: validators.self_id, root, sig: keys.sign(&vote_bytes) };
+                                            if let Some(qc) = prop_qc.filter(|qc| high_qc.as_ref().is_none_or(|h| qc.view > h.view) && qc.verify(&validators)) {
+                                                if let Some(store) = qc_store.as_ref() { store.save_high_qc(&qc); }
+                                                if let Some(r) = state_roots.as_ref() { r.certify(&qc); }
+                                                high_qc = Some(qc);
+                                            }
+                                            let diverged = state.as_ref().is_some_and(|c| state_roots.as_ref().is_some_and(|r| r.diverges(c)));
+                                            if diverged {
+                                                STATE_ROOT_MISMATCHES.inc();
+                                                warn!(view, proposer, ?state, "state root mismatch with proposer; not voting");
+                                            } else if h < height {
+                                                warn!(view, proposer, height = h, "proposal for an already committed height; not voting");
+                                            } else if rbc.has_payload(&root) {
+                                                let vote_bytes = block_sign_bytes("VOTE", view, h, &root, state.as_ref());
+                                                let vote = ConsensusMsg::Vote { view, height: h, voter: validators.self_id, root, state, sig: keys.sign(&vote_bytes) };
                                                 send_to(&net_out, validators.leader_for(view).addr, &vote).await; VOTES_SENT.inc();
                                             }
                                         }
                                     }
                                 }
-                                ConsensusMsg::Vote { view: v, voter, root, sig } => {
-                                    let vote_bytes = sign_bytes("VOTE", &[v.to_le_bytes().as_slice(), &root].concat());
-                                    if keys.verify(voter, &vote_bytes, &sig) {
-                                        if validators.leader_for(v).id == validators.self_id && v == view {
-                                            votes_ed.insert(voter, sig);
-                                            if votes_ed.len() >= quorum {
-                                                let qc = QuorumCert { view, root, voters: votes_ed.keys().copied().collect(), sigs: votes_ed.iter().map(|(id,s)| Signed{voter:*id, sig:s.clone()}).collect() };
-                                                if let Some(store) = qc_store.as_ref() { store.save_high_qc(&qc); }
-                                                QCS_FORMED.inc();
-                                                if let Some(payload) = rbc.get_payload(&root) {
-                                                    if let Ok(batch) = bincode::deserialize::<Batch>(payload) {
-                                                        if let Err(e) = to_exec.send((batch, height)).await { warn!("consensus -> exec send error: {e}"); }
-                                                        else { COMMITS.inc(); height += 1;
-                                                            if let Some(start) = prop_start.remove(&root) { PROPOSAL_TO_COMMIT.observe(start.elapsed().as_secs_f64()); }
-                                                        }
+                                ConsensusMsg::Vote { view: v, height: h, voter, root, state, sig } => {
+                                    let vote_bytes = block_sign_bytes("VOTE", v, h, &root, state.as_ref());
+                                    if keys.verify(voter, &vote_bytes, &sig) && validators.leader_for(v).id == validators.self_id && v == view && h == proposed_height && state == proposed_state {
+                                        votes_ed.insert(voter, sig);
+                                        if votes_ed.len() >= quorum {
+                                            let qc = QuorumCert { view, height: h, root, state, voters: votes_ed.keys().copied().collect(), sigs: votes_ed.iter().map(|(id,s)| Signed{voter:*id, sig:s.clone()}).collect() };
+                                            if let Some(store) = qc_store.as_ref() { store.save_high_qc(&qc); }
+                                            if let Some(r) = state_roots.as_ref() { r.certify(&qc); }
+                                            high_qc = Some(qc.clone());
+                                            QCS_FORMED.inc();
+                                            if let Some(payload) = rbc.get_payload(&root).filter(|_| h >= height) {
+                                                if let Ok(batch) = bincode::deserialize::<Batch>(payload) {
+                                                    if let Some(ds) = da_store.as_ref() { ds.record_commit(h, root, k, m); }
+                                                    if let Some(r) = state_roots.as_ref() { r.record_commit(h, qc.clone()); }
+                                                    broadcast(&net_out, &validators, &ConsensusMsg::Commit { qc: qc.clone() }).await;
+                                                    if let Err(e) = to_exec.send((batch, h)).await { warn!("consensus -> exec send error: {e}"); }
+                                                    else { COMMITS.inc(); height = h + 1;
+                                                        if let Some(start) = prop_start.remove(&root) { PROPOSAL_TO_COMMIT.observe(start.elapsed().as_secs_f64()); }
                                                     }
                                                 }
-                                                votes_ed.clear(); pending_root=None; view += 1;
                                             }
+                                            votes_ed.clear(); pending_root=None; view += 1;
                                         }
                                     }
                                 }
                                 ConsensusMsg::NewView { view: v, .. } => { if v > view { view = v; } }
                                 ConsensusMsg::Timeout { .. } => {}
+                                ConsensusMsg::Commit { qc } => {
+                                    let h = qc.height;
+                                    if h >= height && qc.verify(&validators) {
+                                        if let Some(ds) = da_store.as_ref() { ds.record_commit(h, qc.root, k, m); }
+                                        if let Some(r) = state_roots.as_ref() { r.certify(&qc); r.record_commit(h, qc.clone()); }
+                                        if high_qc.as_ref().is_none_or(|hq| qc.view > hq.view) {
+                                            if let Some(store) = qc_store.as_ref() { store.save_high_qc(&qc); }
+                                            high_qc = Some(qc);
+                                        }
+                                        height = h + 1;
+                                    }
+                                }
                             }
                         }
                     }
-                    _ => {}
+                    Some(_) => {}
+                    None => break,
                 }
             }
         }
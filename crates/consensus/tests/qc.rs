@@ -0,0 +1,18 @@
+use consensus::{block_sign_bytes, QuorumCert, Signed, Validator, Validators};
+
+fn qc_at(height: u64) -> (QuorumCert, Validators) {
+    let keys: Vec<_> = (1..=4u32).map(|i| (i, crypto::keypair_from_seed(format!("validator-{i}").as_bytes()))).collect();
+    let nodes = keys.iter().map(|(id, (_, pk))| Validator { id: *id, addr: format!("127.0.0.1:{}", 7000 + id).parse().unwrap(), pubkey: pk.clone() }).collect();
+    let root = [7; 32];
+    let bytes = block_sign_bytes("VOTE", 3, height, &root, None);
+    let sigs = keys.iter().map(|(id, (sk, _))| Signed { voter: *id, sig: crypto::sign(sk, &bytes) }).collect();
+    (QuorumCert { view: 3, height, root, state: None, voters: vec![1, 2, 3, 4], sigs }, Validators { self_id: 1, nodes })
+}
+
+#[test]
+fn qc_does_not_verify_at_another_height() {
+    let (qc, validators) = qc_at(5);
+    assert!(qc.verify(&validators));
+    assert!(!QuorumCert { height: 9, ..qc.clone() }.verify(&validators));
+    assert!(!QuorumCert { height: 4, ..qc }.verify(&validators));
+}
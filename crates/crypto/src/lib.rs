@@ -3,7 +3,7 @@ use ed25519_dalek::{SigningKey, VerifyingKey, Signature, Signer, Verifier, SECRE
 use rand_core::OsRng;
 use serde::{Serialize, Deserialize};
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct PubKey(#[serde(with = "hex::serde")] pub [u8; PUBLIC_KEY_LENGTH]);
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -19,6 +19,13 @@ impl PubKey {
     pub fn hex(&self) -> String { hex::encode(self.0) }
 }
 
+pub const ADDRESS_LENGTH: usize = 20;
+
+/// Account address owned by `pk`: hex of the first 20 bytes of blake3(pk).
+pub fn address(pk: &PubKey) -> String {
+    hex::encode(&blake3::hash(&pk.0).as_bytes()[..ADDRESS_LENGTH])
+}
+
 impl SecretKey {
     pub fn to_signing_key(&self) -> SigningKey {
         SigningKey::from_bytes(&self.0)
@@ -32,6 +39,15 @@ pub fn generate() -> (SecretKey, PubKey) {
     (SecretKey(sk.to_bytes()), PubKey(pk.to_bytes()))
 }
 
+/// Deterministic keypair derived from `seed`; only meant for dev and test accounts.
+pub fn keypair_from_seed(seed: &[u8]) -> (SecretKey, PubKey) {
+    let sk = SecretKey(*blake3::hash(seed).as_bytes());
+    let pk = public_key(&sk);
+    (sk, pk)
+}
+
+pub fn public_key(sk: &SecretKey) -> PubKey { PubKey(sk.to_signing_key().verifying_key().to_bytes()) }
+
 pub fn sign(sk: &SecretKey, msg: &[u8]) -> Sig {
     let sig: Signature = sk.to_signing_key().sign(msg);
     Sig(sig.to_bytes())
@@ -39,5 +55,5 @@ pub fn sign(sk: &SecretKey, msg: &[u8]) -> Sig {
 
 pub fn verify(pk: &PubKey, msg: &[u8], sig: &Sig) -> bool {
     let sig = Signature::from_bytes(&sig.0);
-    pk.to_verkey().verify(msg, &sig).is_ok()
+    VerifyingKey::from_bytes(&pk.0).is_ok_and(|vk| vk.verify(msg, &sig).is_ok())
 }
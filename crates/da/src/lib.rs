@@ -1,6 +1,12 @@
 
 use serde::{Serialize, Deserialize};
 use reed_solomon_erasure::galois_8::ReedSolomon;
+use parking_lot::RwLock;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+pub mod sampling;
 
 pub fn digest(data: &[u8]) -> [u8;32] { *blake3::hash(data).as_bytes() }
 
@@ -50,9 +56,9 @@ fn merkle_proof(leaves: &[[u8;32]], idx: usize) -> Vec<[u8;32]> {
     let mut level = leaves.to_vec();
     let mut i = idx;
     while level.len() > 1 {
-        let sib = if i % 2 == 0 { if i+1 < level.len() { level[i+1] } else { level[i] } } else { level[i-1] };
+        let sib = if i.is_multiple_of(2) { if i+1 < level.len() { level[i+1] } else { level[i] } } else { level[i-1] };
         path.push(sib);
-        i = i / 2;
+        i /= 2;
         let mut next = Vec::new();
         for j in (0..level.len()).step_by(2) {
             let l = level[j];
@@ -66,7 +72,7 @@ fn merkle_proof(leaves: &[[u8;32]], idx: usize) -> Vec<[u8;32]> {
 pub fn proof_verify(p: &MerkleProof, leaf: [u8;32]) -> bool {
     let mut cur = leaf; let mut idx = p.index as usize;
     for sib in &p.path {
-        let (l, r) = if idx % 2 == 0 { (cur, *sib) } else { (*sib, cur) };
+        let (l, r) = if idx.is_multiple_of(2) { (cur, *sib) } else { (*sib, cur) };
         cur = merkle_hash(&l, &r); idx /= 2;
     }
     cur == p.root
@@ -74,11 +80,11 @@ pub fn proof_verify(p: &MerkleProof, leaf: [u8;32]) -> bool {
 
 pub fn encode(payload: &[u8], k: u32, m: u32) -> anyhow::Result<Vec<Shard>> {
     let rs = ReedSolomon::new(k as usize, m as usize)?;
-    let shard_len = ((payload.len() + k as usize - 1) / k as usize).max(1);
+    let shard_len = payload.len().div_ceil(k as usize).max(1);
     let mut shards: Vec<Vec<u8>> = vec![vec![0u8; shard_len]; (k+m) as usize];
-    for i in 0..k as usize {
+    for (i, shard) in shards.iter_mut().take(k as usize).enumerate() {
         let start = i * shard_len; let end = (start + shard_len).min(payload.len());
-        if start < end { shards[i][..(end-start)].copy_from_slice(&payload[start..end]); }
+        if start < end { shard[..(end-start)].copy_from_slice(&payload[start..end]); }
     }
     rs.encode(&mut shards)?;
     let leaves: Vec<[u8;32]> = shards.iter().map(|s| digest(s)).collect();
@@ -90,3 +96,70 @@ pub fn encode(payload: &[u8], k: u32, m: u32) -> anyhow::Result<Vec<Shard>> {
     }
     Ok(out)
 }
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct CommittedRoot {
+    pub height: u64,
+    pub root: [u8;32],
+    pub k: u32,
+    pub m: u32,
+}
+
+#[derive(Default)]
+struct ShardStoreInner {
+    shards: HashMap<[u8;32], HashMap<u32, Shard>>,
+    /// Roots with shards that no commit names yet, oldest first.
+    uncommitted: VecDeque<[u8;32]>,
+    commits: BTreeMap<u64, CommittedRoot>,
+    committed: HashMap<[u8;32], u64>,
+}
+
+/// Verified shards held by this node, plus the roots consensus committed at each height.
+///
+/// Shards reach a node before the proposal that names their root, so shards of uncommitted roots
+/// are kept too, but only for the newest [`ShardStore::UNCOMMITTED_ROOTS`] roots. Commits, and the
+/// shards of their roots, are kept for the last [`ShardStore::RETAIN`] heights.
+#[derive(Clone)]
+pub struct ShardStore { inner: Arc<RwLock<ShardStoreInner>>, commits: broadcast::Sender<CommittedRoot> }
+impl Default for ShardStore {
+    fn default() -> Self { Self { inner: Default::default(), commits: broadcast::channel(1024).0 } }
+}
+impl ShardStore {
+    pub const RETAIN: u64 = 1024;
+    pub const UNCOMMITTED_ROOTS: usize = 256;
+    pub fn new() -> Self { Self::default() }
+    /// Keeps `shard` unless its index is outside its `k + m` layout.
+    pub fn put(&self, shard: Shard) {
+        if shard.index as u64 >= shard.k as u64 + shard.m as u64 { return; }
+        let mut w = self.inner.write();
+        let root = shard.proof.root;
+        if !w.shards.contains_key(&root) && !w.committed.contains_key(&root) {
+            w.uncommitted.push_back(root);
+            while w.uncommitted.len() > Self::UNCOMMITTED_ROOTS {
+                let Some(old) = w.uncommitted.pop_front() else { break };
+                w.shards.remove(&old);
+            }
+        }
+        w.shards.entry(root).or_default().insert(shard.index, shard);
+    }
+    pub fn get(&self, root: &[u8;32], index: u32) -> Option<Shard> { self.inner.read().shards.get(root).and_then(|m| m.get(&index).cloned()) }
+    /// Records the root committed at `height`, once; later records for the height are ignored.
+    pub fn record_commit(&self, height: u64, root: [u8;32], k: u32, m: u32) {
+        let c = CommittedRoot { height, root, k, m };
+        {
+            let mut w = self.inner.write();
+            if let std::collections::btree_map::Entry::Vacant(e) = w.commits.entry(height) { e.insert(c); } else { return; }
+            w.committed.insert(root, height);
+            w.uncommitted.retain(|r| r != &root);
+            while w.commits.len() as u64 > Self::RETAIN {
+                let Some((h, old)) = w.commits.pop_first() else { break };
+                if w.committed.get(&old.root) == Some(&h) { w.committed.remove(&old.root); w.shards.remove(&old.root); }
+            }
+        }
+        let _ = self.commits.send(c);
+    }
+    pub fn commit_at(&self, height: u64) -> Option<CommittedRoot> { self.inner.read().commits.get(&height).copied() }
+    pub fn latest_commit(&self) -> Option<CommittedRoot> { self.inner.read().commits.last_key_value().map(|(_, c)| *c) }
+    /// Every root recorded from now on, in the order recorded.
+    pub fn subscribe(&self) -> broadcast::Receiver<CommittedRoot> { self.commits.subscribe() }
+}
@@ -0,0 +1,100 @@
+
+use super::{CommittedRoot, Shard, digest, proof_verify};
+use parking_lot::RwLock;
+use rand::seq::index::sample as sample_indices;
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+#[async_trait::async_trait]
+pub trait ShardFetcher: Send + Sync {
+    async fn fetch(&self, root: &[u8;32], index: u32) -> Option<Shard>;
+}
+
+/// Fetches shards from peer RPC endpoints (`GET {peer}/da/shard/{root}/{index}`), one random peer per request.
+pub struct HttpShardFetcher { client: reqwest::Client, peers: Vec<String> }
+impl HttpShardFetcher {
+    pub fn new(peers: Vec<String>) -> Self {
+        let peers = peers.into_iter().map(|p| p.trim_end_matches('/').to_string()).collect();
+        Self { client: reqwest::Client::new(), peers }
+    }
+}
+
+#[async_trait::async_trait]
+impl ShardFetcher for HttpShardFetcher {
+    async fn fetch(&self, root: &[u8;32], index: u32) -> Option<Shard> {
+        if self.peers.is_empty() { return None; }
+        let peer = &self.peers[rand::random::<usize>() % self.peers.len()];
+        let url = format!("{}/da/shard/{}/{}", peer, hex::encode(root), index);
+        let resp = self.client.get(url).send().await.ok()?;
+        if !resp.status().is_success() { return None; }
+        resp.json::<Shard>().await.ok()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Availability {
+    pub height: u64,
+    #[serde(with = "hex::serde")]
+    pub root: [u8;32],
+    pub k: u32,
+    pub m: u32,
+    pub sampled: Vec<u32>,
+    pub verified: u32,
+    pub failed: Vec<u32>,
+    pub confidence: f64,
+}
+
+/// Probability that the batch is recoverable given `verified` distinct shards checked out.
+/// An unrecoverable batch exposes at most `k - 1` of the `k + m` shards, so every sample
+/// landing inside that set has probability C(k-1, s) / C(k+m, s).
+pub fn confidence(k: u32, m: u32, verified: u32) -> f64 {
+    let n = (k + m) as f64;
+    let avail = k.saturating_sub(1) as f64;
+    let mut miss = 1.0;
+    for i in 0..verified {
+        let i = i as f64;
+        if avail - i <= 0.0 { return 1.0; }
+        miss *= (avail - i) / (n - i);
+    }
+    1.0 - miss
+}
+
+pub struct SamplingClient {
+    fetcher: Arc<dyn ShardFetcher>,
+    samples: u32,
+    results: RwLock<BTreeMap<u64, Availability>>,
+}
+
+impl SamplingClient {
+    /// Results are kept for this many of the newest heights sampled.
+    pub const RETAIN: usize = 1024;
+
+    pub fn new(fetcher: Arc<dyn ShardFetcher>, samples: u32) -> Self {
+        Self { fetcher, samples, results: RwLock::new(BTreeMap::new()) }
+    }
+
+    pub async fn sample(&self, c: &CommittedRoot) -> Availability {
+        let n = (c.k + c.m) as usize;
+        let s = (self.samples as usize).min(n);
+        let sampled: Vec<u32> = sample_indices(&mut rand::thread_rng(), n, s).into_iter().map(|i| i as u32).collect();
+        let fetches = sampled.iter().map(|&idx| async move { (idx, self.fetcher.fetch(&c.root, idx).await) });
+        let mut verified = 0u32;
+        let mut failed = Vec::new();
+        for (idx, got) in futures_util::future::join_all(fetches).await {
+            let ok = got.is_some_and(|sh| sh.index == idx && sh.proof.index == idx && sh.proof.root == c.root && proof_verify(&sh.proof, digest(&sh.bytes)));
+            if ok { verified += 1; } else { failed.push(idx); }
+        }
+        Availability { height: c.height, root: c.root, k: c.k, m: c.m, sampled, verified, failed, confidence: confidence(c.k, c.m, verified) }
+    }
+
+    pub async fn sample_and_record(&self, c: &CommittedRoot) -> Availability {
+        let a = self.sample(c).await;
+        let mut w = self.results.write();
+        w.insert(c.height, a.clone());
+        while w.len() > Self::RETAIN { w.pop_first(); }
+        a
+    }
+
+    pub fn get(&self, height: u64) -> Option<Availability> { self.results.read().get(&height).cloned() }
+}
@@ -0,0 +1,108 @@
+use da::sampling::{confidence, SamplingClient, ShardFetcher};
+use da::{encode, CommittedRoot, Shard, ShardStore};
+use std::sync::Arc;
+
+/// Serves shards out of a `ShardStore`, optionally flipping a byte so the Merkle proof no longer matches.
+struct StoreFetcher { store: ShardStore, forge: bool }
+
+#[async_trait::async_trait]
+impl ShardFetcher for StoreFetcher {
+    async fn fetch(&self, root: &[u8;32], index: u32) -> Option<Shard> {
+        let mut s = self.store.get(root, index)?;
+        if self.forge { s.bytes[0] ^= 1; }
+        Some(s)
+    }
+}
+
+fn committed(store: &ShardStore, keep: impl Fn(u32) -> bool) -> CommittedRoot {
+    let shards = encode(b"some batch payload long enough to span shards", 4, 4).unwrap();
+    let root = shards[0].proof.root;
+    for s in shards.into_iter().filter(|s| keep(s.index)) { store.put(s); }
+    store.record_commit(7, root, 4, 4);
+    store.commit_at(7).unwrap()
+}
+
+#[test]
+fn confidence_grows_with_verified_shards() {
+    assert_eq!(confidence(2, 1, 0), 0.0);
+    assert!((confidence(2, 1, 1) - 2.0 / 3.0).abs() < 1e-9);
+    assert_eq!(confidence(2, 1, 2), 1.0);
+    assert!((confidence(4, 4, 1) - 5.0 / 8.0).abs() < 1e-9);
+    assert!(confidence(4, 4, 2) > confidence(4, 4, 1));
+    // Once more than k - 1 distinct shards check out, the batch is recoverable for certain.
+    assert_eq!(confidence(4, 4, 4), 1.0);
+}
+
+#[tokio::test]
+async fn sampling_verifies_served_shards() {
+    let store = ShardStore::new();
+    let c = committed(&store, |_| true);
+    let client = SamplingClient::new(Arc::new(StoreFetcher { store, forge: false }), 3);
+    let a = client.sample_and_record(&c).await;
+    assert_eq!((a.height, a.root, a.sampled.len(), a.verified), (7, c.root, 3, 3));
+    assert!(a.failed.is_empty());
+    assert_eq!(a.confidence, confidence(4, 4, 3));
+    assert_eq!(client.get(7).unwrap().verified, 3);
+    assert!(client.get(8).is_none());
+}
+
+#[tokio::test]
+async fn sampling_reports_missing_and_forged_shards() {
+    let store = ShardStore::new();
+    let c = committed(&store, |i| i < 4);
+    let a = SamplingClient::new(Arc::new(StoreFetcher { store: store.clone(), forge: false }), 8).sample(&c).await;
+    assert_eq!((a.sampled.len(), a.verified), (8, 4));
+    let mut failed = a.failed.clone();
+    failed.sort();
+    assert_eq!(failed, vec![4, 5, 6, 7]);
+
+    let a = SamplingClient::new(Arc::new(StoreFetcher { store, forge: true }), 8).sample(&c).await;
+    assert_eq!((a.verified, a.failed.len(), a.confidence), (0, 8, 0.0));
+}
+
+#[test]
+fn shard_store_keeps_shards_and_first_commit_per_height() {
+    let store = ShardStore::new();
+    let mut commits = store.subscribe();
+    assert!(store.latest_commit().is_none());
+    let c = committed(&store, |i| i != 2);
+    assert_eq!(store.get(&c.root, 0).unwrap().index, 0);
+    assert!(store.get(&c.root, 2).is_none());
+    assert!(store.get(&[9; 32], 0).is_none());
+
+    store.record_commit(7, [1; 32], 4, 4);
+    assert_eq!(store.commit_at(7).unwrap().root, c.root);
+    store.record_commit(9, [2; 32], 2, 1);
+    assert_eq!(store.latest_commit().unwrap().height, 9);
+    assert!(store.commit_at(8).is_none());
+
+    assert_eq!(commits.try_recv().unwrap().root, c.root);
+    assert_eq!(commits.try_recv().unwrap().height, 9);
+    assert!(commits.try_recv().is_err());
+}
+
+#[test]
+fn shard_store_keeps_recent_roots_only() {
+    let store = ShardStore::new();
+    let c = committed(&store, |_| true);
+    let mut forged = encode(b"other", 2, 1).unwrap().remove(0);
+    forged.index = 3;
+    store.put(forged.clone());
+    assert!(store.get(&forged.proof.root, 3).is_none(), "index outside k + m");
+
+    // A flood of roots nobody commits pushes out the oldest uncommitted ones, not committed ones.
+    let roots: Vec<[u8; 32]> = (0..=ShardStore::UNCOMMITTED_ROOTS).map(|i| {
+        let s = encode(format!("batch {i}").as_bytes(), 2, 1).unwrap().remove(0);
+        let root = s.proof.root;
+        store.put(s);
+        root
+    }).collect();
+    assert!(store.get(&roots[0], 0).is_none());
+    assert!(store.get(&roots[1], 0).is_some());
+    assert!(store.get(&c.root, 0).is_some());
+
+    for h in 8..8 + ShardStore::RETAIN { store.record_commit(h, [0; 32], 2, 1); }
+    assert!(store.commit_at(7).is_none());
+    assert!(store.get(&c.root, 0).is_none());
+    assert_eq!(store.latest_commit().unwrap().height, 7 + ShardStore::RETAIN);
+}
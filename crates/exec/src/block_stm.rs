@@ -0,0 +1,173 @@
+
+//! Block-STM: optimistic parallel execution over multi-version memory.
+//!
+//! Every transaction runs speculatively on a rayon worker against the writes of lower-indexed
+//! transactions, recording which version of each account it read. After each execution wave the
+//! read sets are validated in parallel; a transaction whose reads are stale is aborted, its
+//! incarnation bumped and its writes marked as estimates so that readers re-execute too. The
+//! lowest aborted index strictly increases from one wave to the next, so the loop terminates and
+//! the committed state equals serial in-order application of the block.
+
+use super::{Account, AccountState, PendingLock, AccountView, Executor, Journal, SimpleExecutor, StateProof, TokenInfo, Outcome, UndoLog, EXEC_LATENCY, apply_tx, pay_fees, receipt};
+use once_cell::sync::Lazy;
+use parking_lot::{RwLockUpgradableReadGuard, RwLockWriteGuard};
+use prometheus::{IntCounter, register_int_counter};
+use rayon::prelude::*;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use storage::WriteBatch;
+use types::{Amount, Batch, Receipt, Rejection, Tx};
+
+static STM_REEXECUTIONS: Lazy<IntCounter> = Lazy::new(|| register_int_counter!("exec_blockstm_reexecutions_total", "Block-STM incarnations aborted by validation").unwrap());
+
+type TxnIdx = usize;
+type Incarnation = u32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ReadOrigin { Storage, Version(TxnIdx, Incarnation) }
+
+#[derive(Debug, Clone)]
+enum MvEntry { Write(Incarnation, Account), Estimate }
+
+/// Account values written by each transaction of the block, keyed by account and then tx index.
+#[derive(Default)]
+struct MvMemory { data: HashMap<String, BTreeMap<TxnIdx, MvEntry>> }
+
+impl MvMemory {
+    /// Latest write below `idx`, or `Err(blocking)` if that write belongs to an aborted incarnation.
+    fn read(&self, addr: &str, idx: TxnIdx) -> Result<Option<(ReadOrigin, Account)>, TxnIdx> {
+        match self.data.get(addr).and_then(|m| m.range(..idx).next_back()) {
+            Some((&j, MvEntry::Write(inc, acc))) => Ok(Some((ReadOrigin::Version(j, *inc), acc.clone()))),
+            Some((&j, MvEntry::Estimate)) => Err(j),
+            None => Ok(None),
+        }
+    }
+    fn origin(&self, addr: &str, idx: TxnIdx) -> Option<ReadOrigin> {
+        match self.data.get(addr).and_then(|m| m.range(..idx).next_back()) {
+            Some((&j, MvEntry::Write(inc, _))) => Some(ReadOrigin::Version(j, *inc)),
+            Some((_, MvEntry::Estimate)) => None,
+            None => Some(ReadOrigin::Storage),
+        }
+    }
+    fn record(&mut self, idx: TxnIdx, inc: Incarnation, prev: &[String], writes: &BTreeMap<String, Account>) {
+        for addr in prev.iter().filter(|a| !writes.contains_key(*a)) {
+            if let Some(m) = self.data.get_mut(addr) { m.remove(&idx); }
+        }
+        for (addr, acc) in writes { self.data.entry(addr.clone()).or_default().insert(idx, MvEntry::Write(inc, acc.clone())); }
+    }
+    fn mark_estimate(&mut self, idx: TxnIdx, keys: &[String]) {
+        for addr in keys { if let Some(e) = self.data.get_mut(addr).and_then(|m| m.get_mut(&idx)) { *e = MvEntry::Estimate; } }
+    }
+    /// Final value of every account written in the block: the write of the highest tx index.
+    fn into_final(self) -> impl Iterator<Item = (String, Account)> {
+        self.data.into_iter().filter_map(|(addr, m)| match m.into_iter().next_back() {
+            Some((_, MvEntry::Write(_, acc))) => Some((addr, acc)),
+            _ => None,
+        })
+    }
+}
+
+/// One incarnation's view of state: its own writes first, then lower txs in `mv`, then storage.
+struct TxView<'a> {
+    idx: TxnIdx,
+    mv: &'a MvMemory,
+    base: &'a HashMap<String, Account>,
+    reads: Vec<(String, ReadOrigin)>,
+    writes: BTreeMap<String, Account>,
+}
+
+impl AccountView for TxView<'_> {
+    type Err = TxnIdx;
+    fn get(&mut self, addr: &str) -> Result<Account, TxnIdx> {
+        if let Some(acc) = self.writes.get(addr) { return Ok(acc.clone()); }
+        let (origin, acc) = match self.mv.read(addr, self.idx)? {
+            Some(hit) => hit,
+            None => (ReadOrigin::Storage, self.base.get(addr).cloned().unwrap_or_default()),
+        };
+        self.reads.push((addr.to_string(), origin));
+        Ok(acc)
+    }
+    fn put(&mut self, addr: &str, acc: Account) { self.writes.insert(addr.to_string(), acc); }
+}
+
+struct TxnState {
+    incarnation: Incarnation,
+    reads: Vec<(String, ReadOrigin)>,
+    write_keys: Vec<String>,
+    outcome: Option<Outcome>,
+}
+
+pub struct BlockStmExecutor { inner: Arc<SimpleExecutor> }
+impl BlockStmExecutor { pub fn new(inner: Arc<SimpleExecutor>) -> Self { Self { inner } } }
+impl Executor for BlockStmExecutor {
+    fn balance(&self, addr: &str) -> Amount { self.inner.balance(addr) }
+    fn asset_balance(&self, addr: &str, asset: &str) -> Amount { self.inner.asset_balance(addr, asset) }
+    fn token(&self, asset: &str) -> Option<TokenInfo> { self.inner.token(asset) }
+    fn account(&self, addr: &str) -> Option<AccountState> { self.inner.account(addr) }
+    fn locks(&self, addr: &str) -> Vec<PendingLock> { self.inner.locks(addr) }
+    fn nonce(&self, addr: &str) -> u64 { self.inner.nonce(addr) }
+    fn apply_batch_blocking(&self, batch: Batch, block_height: u64) -> Vec<Receipt> {
+        let _t = EXEC_LATENCY.start_timer();
+        let (txs, time_ms) = (batch.txs, batch.time_ms);
+        *self.inner.last_height.write() = block_height;
+        let chain_id = &self.inner.chain_id;
+        let auth: Vec<Result<(), Rejection>> = txs.par_iter().map(|tx| tx.signed.verify(chain_id)).collect();
+
+        // Matured locks pay out before the block runs, so its transactions see the released funds.
+        let mut w = self.inner.accounts.write();
+        let mut undo = UndoLog::new();
+        let Ok(()) = self.inner.release_locks(&mut Journal { accounts: &mut w, undo: &mut undo }, block_height, time_ms);
+        let base = RwLockWriteGuard::downgrade_to_upgradable(w);
+        let mut mv = MvMemory::default();
+        let mut state: Vec<TxnState> = (0..txs.len()).map(|_| TxnState { incarnation: 0, reads: Vec::new(), write_keys: Vec::new(), outcome: None }).collect();
+        let mut pending: Vec<TxnIdx> = (0..txs.len()).filter(|&i| auth[i].is_ok()).collect();
+
+        while let Some(&lowest) = pending.first() {
+            let executed: Vec<_> = pending.par_iter().filter_map(|&i| {
+                let mut v = TxView { idx: i, mv: &mv, base: &base, reads: Vec::new(), writes: BTreeMap::new() };
+                apply_tx(&txs[i].signed, &mut v, block_height, time_ms, &self.inner).ok().map(|out| (i, out, v.reads, v.writes))
+            }).collect();
+            pending.retain(|i| executed.binary_search_by_key(i, |e| e.0).is_err());
+            for (i, out, reads, writes) in executed {
+                let st = &mut state[i];
+                mv.record(i, st.incarnation, &st.write_keys, &writes);
+                st.write_keys = writes.into_keys().collect();
+                st.reads = reads;
+                st.outcome = Some(out);
+            }
+
+            let invalid: Vec<TxnIdx> = (lowest + 1..txs.len()).into_par_iter()
+                .filter(|&i| state[i].outcome.is_some() && state[i].reads.iter().any(|(addr, origin)| mv.origin(addr, i) != Some(*origin)))
+                .collect();
+            STM_REEXECUTIONS.inc_by(invalid.len() as u64);
+            for &i in &invalid {
+                let st = &mut state[i];
+                st.incarnation += 1;
+                st.outcome = None;
+                mv.mark_estimate(i, &st.write_keys);
+            }
+            pending.extend(invalid);
+            pending.sort_unstable();
+            pending.dedup();
+        }
+
+        let mut w = RwLockUpgradableReadGuard::upgrade(base);
+        for (addr, acc) in mv.into_final() {
+            let prev = w.insert(addr.clone(), acc);
+            undo.entry(addr).or_insert(prev);
+        }
+        let outcomes: Vec<Outcome> = auth.into_iter().zip(state).map(|(auth, st)| auth.and_then(|()| st.outcome.expect("every authorized tx executed"))).collect();
+        let Ok(()) = pay_fees(&mut Journal { accounts: &mut w, undo: &mut undo }, &self.inner.fees, outcomes.iter().flatten().map(|(fee, _)| *fee), block_height);
+        self.inner.commit_block(&w, block_height, undo, WriteBatch::new(), []);
+        drop(w);
+
+        txs.iter().zip(outcomes).map(|(tx, out)| receipt(tx, out, block_height)).collect()
+    }
+    fn simulate(&self, txs: &[Tx]) -> Vec<Receipt> { self.inner.simulate(txs) }
+    fn last_height(&self) -> u64 { *self.inner.last_height.read() }
+    fn snapshot(&self) -> Vec<AccountState> { self.inner.snapshot() }
+    fn diff_since(&self, since: u64) -> Vec<AccountState> { self.inner.diff_since(since) }
+    fn restore(&self, replace: bool, items: Vec<AccountState>) { self.inner.restore(replace, items) }
+    fn state_root(&self) -> crate::smt::Hash { self.inner.state_root() }
+    fn prove(&self, addr: &str, height: u64) -> Option<StateProof> { self.inner.prove(addr, height) }
+}
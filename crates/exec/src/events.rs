@@ -0,0 +1,78 @@
+//! Receipt events indexed by account and height, so clients can follow an account without
+//! replaying blocks.
+//!
+//! Every event is stored once per account it concerns, under
+//! `ev/ | len(account) | account | height | tx index | event index`, with fixed-width big-endian
+//! numbers so an account's events scan in chain order.
+
+use serde::{Serialize, Deserialize};
+use std::sync::Arc;
+use storage::{Kv, WriteBatch};
+use types::{Event, Receipt, TxId};
+
+const EVENT_PREFIX: &[u8] = b"ev/";
+
+fn account_prefix(account: &str) -> Vec<u8> {
+    [EVENT_PREFIX, &(account.len() as u32).to_be_bytes(), account.as_bytes()].concat()
+}
+
+fn event_key(account: &str, at: &EventCursor) -> Vec<u8> {
+    [&account_prefix(account)[..], &at.height.to_be_bytes(), &at.tx.to_be_bytes(), &at.event.to_be_bytes()].concat()
+}
+
+/// Where an event sits in an account's stream: block height, transaction index in the block,
+/// event index in the receipt. Ordered like the stream.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct EventCursor { pub height: u64, pub tx: u32, pub event: u32 }
+
+/// One page of an account's events; `next` is where the following page starts, if there is one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventPage { pub events: Vec<IndexedEvent>, pub next: Option<EventCursor> }
+
+/// An event and the transaction that emitted it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexedEvent {
+    pub height: u64,
+    #[serde(with = "hex::serde")]
+    pub tx_id: TxId,
+    pub event: Event,
+}
+
+#[derive(Clone)]
+pub struct EventIndex { kv: Arc<dyn Kv> }
+
+impl EventIndex {
+    pub fn new(kv: Arc<dyn Kv>) -> Self { Self { kv } }
+
+    /// Indexes the events of one block's receipts in a single batch. Indexing the same block
+    /// again rewrites the same keys.
+    pub fn record(&self, receipts: &[Receipt]) -> anyhow::Result<()> {
+        let mut b = WriteBatch::new();
+        for (i, r) in receipts.iter().enumerate() {
+            for (j, event) in r.events.iter().enumerate() {
+                let value = bincode::serialize(&IndexedEvent { height: r.block_height, tx_id: r.tx_id, event: event.clone() })?;
+                let at = EventCursor { height: r.block_height, tx: i as u32, event: j as u32 };
+                for account in event.accounts() { b.put(&event_key(account, &at), value.clone()); }
+            }
+        }
+        if b.is_empty() { return Ok(()); }
+        self.kv.write_batch(b)
+    }
+
+    /// Up to `limit` events concerning `account` at `from` or later, oldest first.
+    pub fn query(&self, account: &str, from: EventCursor, limit: usize) -> anyhow::Result<EventPage> {
+        let prefix = account_prefix(account);
+        // One extra entry tells whether there is a next page, and where it starts.
+        let mut found = self.kv.scan_from(&prefix, &event_key(account, &from), limit.saturating_add(1));
+        let next = if found.len() > limit { found.pop().map(|(k, _)| decode_cursor(&k[prefix.len()..])).transpose()? } else { None };
+        let events = found.into_iter().map(|(_, v)| Ok(bincode::deserialize(&v)?)).collect::<anyhow::Result<_>>()?;
+        Ok(EventPage { events, next })
+    }
+}
+
+fn decode_cursor(b: &[u8]) -> anyhow::Result<EventCursor> {
+    let b: &[u8; 16] = b.try_into().map_err(|_| anyhow::anyhow!("malformed event key"))?;
+    let (height, rest) = b.split_at(8);
+    let (tx, event) = rest.split_at(4);
+    Ok(EventCursor { height: u64::from_be_bytes(height.try_into()?), tx: u32::from_be_bytes(tx.try_into()?), event: u32::from_be_bytes(event.try_into()?) })
+}
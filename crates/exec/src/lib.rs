@@ -1,114 +1,698 @@
 
 use parking_lot::RwLock;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
 use std::sync::Arc;
 use serde::{Serialize, Deserialize};
-use types::{Batch, Receipt, Status, now_ms};
+use types::{Amount, AssetId, AuthPolicy, Batch, Event, Payment, Receipt, Rejection, SignedTx, Status, TokenOp, Transfer, Tx, TxKind, TxResult, Unlock, account_address, asset_id, lock_id, now_ms};
 use once_cell::sync::Lazy;
 use prometheus::{Histogram, register_histogram};
+use storage::{Kv, WriteBatch};
+
+mod block_stm;
+mod events;
+pub mod smt;
+#[cfg(feature = "wasm")]
+mod wasm;
+pub use block_stm::BlockStmExecutor;
+pub use events::{EventCursor, EventIndex, EventPage, IndexedEvent};
+#[cfg(feature = "wasm")]
+pub use wasm::{WasmExecutor, WasmLimits, contract_address};
+
+/// Number of recent heights whose state can still be proven against.
+pub const STATE_HISTORY: usize = 64;
+
+const ACCOUNT_PREFIX: &[u8] = b"acct/";
+const LAST_HEIGHT_KEY: &[u8] = b"meta/last_height";
+const GENESIS_KEY: &[u8] = b"meta/genesis";
+
+fn account_key(addr: &str) -> Vec<u8> { [ACCOUNT_PREFIX, addr.as_bytes()].concat() }
 
 static EXEC_LATENCY: Lazy<Histogram> = Lazy::new(|| register_histogram!("exec_apply_batch_seconds", "Batch apply duration").unwrap());
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct AccountState { pub addr: String, pub ver: u64, pub bal: u64, pub last_update_height: u64 }
+/// `bal` is the native coin; `assets` holds nonzero token balances. An account whose address is a
+/// token id carries that token's `token` record, and one whose address is a lock id carries the
+/// pending `lock`. `auth`, once set, decides which keys can sign for the account instead of its
+/// address.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountState {
+    pub addr: String,
+    pub ver: u64,
+    pub bal: Amount,
+    #[serde(default)]
+    pub nonce: u64,
+    pub last_update_height: u64,
+    #[serde(default)]
+    pub assets: BTreeMap<AssetId, Amount>,
+    #[serde(default)]
+    pub token: Option<TokenInfo>,
+    #[serde(default)]
+    pub auth: Option<AuthPolicy>,
+    #[serde(default)]
+    pub lock: Option<Lock>,
+}
+
+/// An issued token and its current supply.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenInfo { pub issuer: String, pub symbol: String, pub decimals: u8, pub max_supply: Amount, pub supply: Amount }
+
+/// Funds held until `unlock`, then paid to `to`. A hash lock pays `to` only if claimed with the
+/// preimage of `hash` first, and refunds `from` at `unlock` otherwise.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lock { pub from: String, pub to: String, pub amount: Amount, pub asset: Option<AssetId>, pub unlock: Unlock, pub hash: Option<[u8; 32]> }
+
+impl Lock {
+    /// Who gets the funds when the lock matures unclaimed.
+    fn payee(&self) -> &str { if self.hash.is_some() { &self.from } else { &self.to } }
+}
+
+/// A lock still holding funds, and its id.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingLock { pub id: String, pub lock: Lock }
+
+/// In-memory account record; `nonce` is the next nonce the account must use.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Account { pub ver: u64, pub bal: Amount, pub nonce: u64, pub last_update_height: u64, pub assets: BTreeMap<AssetId, Amount>, pub token: Option<TokenInfo>, pub auth: Option<AuthPolicy>, pub lock: Option<Box<Lock>> }
+
+impl Account {
+    fn to_state(&self, addr: &str) -> AccountState {
+        AccountState { addr: addr.to_string(), ver: self.ver, bal: self.bal, nonce: self.nonce, last_update_height: self.last_update_height, assets: self.assets.clone(), token: self.token.clone(), auth: self.auth.clone(), lock: self.lock.as_deref().cloned() }
+    }
+    fn asset(&self, id: &str) -> Amount { self.assets.get(id).copied().unwrap_or_default() }
+    /// Sets a token balance, dropping it at zero so equal states have equal roots.
+    fn set_asset(&mut self, id: &str, amount: Amount) {
+        if amount == Amount::ZERO { self.assets.remove(id); } else { self.assets.insert(id.to_string(), amount); }
+    }
+    /// Takes `amount` of token `id` out of this account, `addr`.
+    fn withdraw(&mut self, addr: &str, id: &str, amount: Amount) -> Result<(), Rejection> {
+        let Some(left) = self.asset(id).checked_sub(amount) else { return Err(insufficient(addr, Some(id))) };
+        self.set_asset(id, left);
+        Ok(())
+    }
+}
+
+impl AccountState {
+    /// Key of this account in the state tree.
+    pub fn key(addr: &str) -> smt::Hash { *blake3::hash(addr.as_bytes()).as_bytes() }
+    /// Leaf value committed to by the state root.
+    pub fn value_hash(&self) -> smt::Hash {
+        let mut h = blake3::Hasher::new();
+        let str = |h: &mut blake3::Hasher, s: &str| { h.update(&(s.len() as u32).to_le_bytes()); h.update(s.as_bytes()); };
+        str(&mut h, &self.addr);
+        for x in [self.ver, self.bal.0, self.nonce, self.last_update_height] { h.update(&x.to_le_bytes()); }
+        h.update(&(self.assets.len() as u32).to_le_bytes());
+        for (id, amount) in &self.assets { str(&mut h, id); h.update(&amount.to_le_bytes()); }
+        match &self.token {
+            Some(t) => {
+                h.update(&[1]); str(&mut h, &t.issuer); str(&mut h, &t.symbol); h.update(&[t.decimals]);
+                h.update(&t.max_supply.to_le_bytes()); h.update(&t.supply.to_le_bytes());
+            }
+            None => { h.update(&[0]); }
+        }
+        match &self.auth {
+            Some(p) => {
+                h.update(&[1]); h.update(&p.threshold.to_le_bytes()); h.update(&(p.keys.len() as u32).to_le_bytes());
+                for k in &p.keys { h.update(&k.0); }
+            }
+            None => { h.update(&[0]); }
+        }
+        match &self.lock {
+            Some(l) => {
+                h.update(&[1]); str(&mut h, &l.from); str(&mut h, &l.to); h.update(&l.amount.to_le_bytes());
+                match &l.asset { Some(a) => { h.update(&[1]); str(&mut h, a); } None => { h.update(&[0]); } }
+                let (tag, at) = match l.unlock { Unlock::Height(x) => (0u8, x), Unlock::Time(x) => (1, x) };
+                h.update(&[tag]); h.update(&at.to_le_bytes());
+                match &l.hash { Some(x) => { h.update(&[1]); h.update(x); } None => { h.update(&[0]); } }
+            }
+            None => { h.update(&[0]); }
+        }
+        *h.finalize().as_bytes()
+    }
+}
+
+/// State tree kept in step with the accounts (and contract storage, under a [`WasmExecutor`]),
+/// plus its versions at recently committed heights for [`Executor::prove`].
+#[derive(Default)]
+struct StateTree { height: u64, current: smt::SparseMerkleTree, versions: VecDeque<(u64, smt::SparseMerkleTree)> }
+impl StateTree {
+    fn set(&mut self, key: smt::Hash, value: Option<smt::Hash>) {
+        match value { Some(v) => self.current.insert(key, v), None => self.current.remove(&key) }
+    }
+    fn set_account(&mut self, addr: &str, acc: Option<&Account>) { self.set(AccountState::key(addr), acc.map(|a| a.to_state(addr).value_hash())); }
+    /// Keeps the tree as of the last height and starts `height`.
+    fn advance(&mut self, height: u64) {
+        self.versions.push_back((self.height, self.current.clone()));
+        while self.versions.len() > STATE_HISTORY { self.versions.pop_front(); }
+        self.height = height;
+    }
+    fn at(&self, height: u64) -> Option<&smt::SparseMerkleTree> {
+        if height == self.height { return Some(&self.current); }
+        self.versions.iter().rev().find(|(h, _)| *h == height).map(|(_, t)| t)
+    }
+}
+
+fn check_nonce(t: &Transfer, expected: u64) -> Result<(), Rejection> {
+    if t.nonce == expected { Ok(()) } else { Err(Rejection::BadNonce { account: t.from.clone(), expected, got: t.nonce }) }
+}
+
+/// Account reads and writes as seen by one transaction. `Err` aborts execution without an outcome.
+trait AccountView {
+    type Err;
+    fn get(&mut self, addr: &str) -> Result<Account, Self::Err>;
+    fn put(&mut self, addr: &str, acc: Account);
+}
+
+/// Prior value of every account written at one height; `None` if the account didn't exist.
+type UndoLog = HashMap<String, Option<Account>>;
+
+/// Writes straight into the account map, remembering what each first write replaced.
+struct Journal<'a> { accounts: &'a mut HashMap<String, Account>, undo: &'a mut UndoLog }
+
+impl AccountView for Journal<'_> {
+    type Err = std::convert::Infallible;
+    fn get(&mut self, addr: &str) -> Result<Account, Self::Err> { Ok(self.accounts.get(addr).cloned().unwrap_or_default()) }
+    fn put(&mut self, addr: &str, acc: Account) {
+        let prev = self.accounts.insert(addr.to_string(), acc);
+        self.undo.entry(addr.to_string()).or_insert(prev);
+    }
+}
+
+/// Copy-on-write view over the account map: reads fall through to `base`, writes stay here.
+struct Overlay<'a> { base: &'a HashMap<String, Account>, writes: HashMap<String, Account> }
+
+impl AccountView for Overlay<'_> {
+    type Err = std::convert::Infallible;
+    fn get(&mut self, addr: &str) -> Result<Account, Self::Err> {
+        Ok(self.writes.get(addr).or_else(|| self.base.get(addr)).cloned().unwrap_or_default())
+    }
+    fn put(&mut self, addr: &str, acc: Account) { self.writes.insert(addr.to_string(), acc); }
+}
+
+/// Gas charged for any transaction, per payment of a batch, plus per byte of payload.
+pub const TRANSFER_GAS: u64 = 1_000;
+pub const PAYLOAD_BYTE_GAS: u64 = 16;
+
+/// Most payments one batch transfer may carry.
+pub const MAX_BATCH_PAYMENTS: usize = 10_000;
+
+pub fn gas_used(t: &Transfer) -> u64 {
+    let payments = match &t.kind { TxKind::BatchTransfer(ps) => ps.len().max(1) as u64, _ => 1 };
+    TRANSFER_GAS.saturating_mul(payments).saturating_add(t.payload.as_ref().map_or(0, |p| p.len() as u64).saturating_mul(PAYLOAD_BYTE_GAS))
+}
+
+/// Smallest `fee` a transfer may offer at `gas_price`.
+pub fn min_fee(t: &Transfer, gas_price: u64) -> Amount { Amount(gas_used(t).saturating_mul(gas_price)) }
+
+/// Fee rules of the chain. Fees collected in a block go to `treasury`, or are burned if it's unset.
+#[derive(Debug, Clone, Default)]
+pub struct FeeSchedule { pub gas_price: u64, pub treasury: Option<String> }
+
+fn check_token_meta(m: &types::TokenMeta, initial: Amount) -> Result<(), Rejection> {
+    if m.symbol.is_empty() || m.symbol.len() > 12 || !m.symbol.bytes().all(|b| b.is_ascii_uppercase() || b.is_ascii_digit()) {
+        return Err(Rejection::InvalidTokenOp(format!("invalid token symbol {:?}", m.symbol)));
+    }
+    if m.decimals > 18 { return Err(Rejection::InvalidTokenOp(format!("invalid token decimals {}", m.decimals))); }
+    if initial > m.max_supply { return Err(Rejection::InvalidTokenOp(format!("initial supply {} exceeds cap {}", initial, m.max_supply))); }
+    Ok(())
+}
+
+/// Fee and result of a committed transaction, or why it was rejected.
+type Outcome = Result<(Amount, TxResult), Rejection>;
+
+/// Writes of one transaction, held back from `inner` until the transaction commits.
+struct Staged<'v, V> { inner: &'v mut V, writes: BTreeMap<String, Account> }
+
+impl<V: AccountView> AccountView for Staged<'_, V> {
+    type Err = V::Err;
+    fn get(&mut self, addr: &str) -> Result<Account, Self::Err> {
+        match self.writes.get(addr) { Some(a) => Ok(a.clone()), None => self.inner.get(addr) }
+    }
+    fn put(&mut self, addr: &str, acc: Account) { self.writes.insert(addr.to_string(), acc); }
+}
+
+/// Runs `f` over staged writes and applies them to `v` only if it succeeds, so a rejection at any
+/// point, even after some accounts were updated, writes nothing.
+fn staged<V: AccountView, T>(v: &mut V, f: impl FnOnce(&mut Staged<'_, V>) -> Result<Result<T, Rejection>, V::Err>) -> Result<Result<T, Rejection>, V::Err> {
+    let mut s = Staged { inner: v, writes: BTreeMap::new() };
+    let out = f(&mut s)?;
+    if out.is_ok() { for (addr, acc) in s.writes { s.inner.put(&addr, acc); } }
+    Ok(out)
+}
+
+/// The state transition of a transaction, shared by every executor so all of them agree with
+/// serial in-order application. A rejected transaction writes nothing.
+fn apply_tx<V: AccountView>(tx: &SignedTx, v: &mut V, h: u64, time_ms: u64, chain: &SimpleExecutor) -> Result<Outcome, V::Err> {
+    staged(v, |v| transition(tx, v, h, time_ms, chain))
+}
+
+fn transition<V: AccountView>(tx: &SignedTx, v: &mut V, h: u64, time_ms: u64, chain: &SimpleExecutor) -> Result<Outcome, V::Err> {
+    let t = &tx.transfer;
+    if let Some(expiry) = t.expiry.filter(|e| e.passed(h, time_ms)) { return Ok(Err(Rejection::Expired { expiry })); }
+    let from = v.get(&t.from)?;
+    if let Err(e) = tx.authorize(from.auth.as_ref()) { return Ok(Err(e)); }
+    if let Err(e) = check_nonce(t, from.nonce) { return Ok(Err(e)); }
+    let need = min_fee(t, chain.fees.gas_price);
+    if t.fee < need { return Ok(Err(Rejection::FeeTooLow { need, got: t.fee })); }
+    if t.token_op.is_some() && t.kind != TxKind::Transfer { return Ok(Err(Rejection::InvalidTokenOp("token operations are only valid on plain transfers".to_string()))); }
+    let native = match &t.kind {
+        TxKind::Transfer if t.asset.is_none() && t.token_op.is_none() => Some(t.amount),
+        TxKind::BatchTransfer(ps) if t.asset.is_none() => Amount::checked_sum(ps.iter().map(|p| p.amount)),
+        TxKind::CreateAccount(_) => Some(t.amount),
+        TxKind::TimeLock(_) | TxKind::Htlc { .. } if t.asset.is_none() => Some(t.amount),
+        _ => Some(Amount::ZERO),
+    };
+    let Some(cost) = native.and_then(|n| n.checked_add(t.fee)).filter(|c| from.bal >= *c) else { return Ok(Err(insufficient(&t.from, None))) };
+    let res = match &t.kind {
+        TxKind::Transfer => match apply_transfer(t, from, cost, v, h)? { Ok(r) => r, Err(e) => return Ok(Err(e)) },
+        TxKind::BatchTransfer(ps) => match apply_batch_transfer(t, ps, from, cost, v, h)? { Ok(r) => r, Err(e) => return Ok(Err(e)) },
+        TxKind::CreateAccount(key) => {
+            let addr = account_address(&t.from, t.nonce);
+            // No key signs for a derived address, so funds sent there ahead of time can't claim it.
+            if v.get(&addr)?.auth.is_some() { return Ok(Err(Rejection::AccountExists(addr))); }
+            v.put(&t.from, debit(from, cost, h));
+            if let Err(e) = pay(v, &addr, None, t.amount, h)? { return Ok(Err(e)); }
+            let mut created = v.get(&addr)?;
+            created.auth = Some(AuthPolicy::single(key.clone()));
+            v.put(&addr, created);
+            TxResult::CreateAccount { addr }
+        }
+        TxKind::SetAccountKey(key) => {
+            let mut from = debit(from, cost, h);
+            from.auth = Some(AuthPolicy::single(key.clone()));
+            v.put(&t.from, from);
+            TxResult::SetAccountKey
+        }
+        TxKind::SetAuthPolicy(policy) => {
+            if let Err(e) = policy.validate() { return Ok(Err(e)); }
+            let mut from = debit(from, cost, h);
+            from.auth = Some(policy.clone());
+            v.put(&t.from, from);
+            TxResult::SetAuthPolicy
+        }
+        TxKind::Mint => {
+            if chain.governance.as_deref() != Some(t.from.as_str()) { return Ok(Err(Rejection::Unauthorized("only the governance account can mint".to_string()))); }
+            if t.asset.is_some() { return Ok(Err(Rejection::InvalidTokenOp("governance mints only the native coin".to_string()))); }
+            v.put(&t.from, debit(from, cost, h));
+            if let Err(e) = pay(v, &t.to, None, t.amount, h)? { return Ok(Err(e)); }
+            TxResult::Mint { balance: v.get(&t.to)?.bal }
+        }
+        TxKind::TimeLock(unlock) => {
+            let lock = Lock { from: t.from.clone(), to: t.to.clone(), amount: t.amount, asset: t.asset.clone(), unlock: *unlock, hash: None };
+            match apply_lock(t, from, cost, lock, v, h, time_ms)? { Ok(r) => r, Err(e) => return Ok(Err(e)) }
+        }
+        TxKind::Htlc { hash, expiry } => {
+            let lock = Lock { from: t.from.clone(), to: t.to.clone(), amount: t.amount, asset: t.asset.clone(), unlock: *expiry, hash: Some(*hash) };
+            match apply_lock(t, from, cost, lock, v, h, time_ms)? { Ok(r) => r, Err(e) => return Ok(Err(e)) }
+        }
+        TxKind::Claim { lock: id, preimage } => {
+            let mut acc = v.get(id)?;
+            let Some(lock) = acc.lock.take() else { return Ok(Err(Rejection::InvalidLock(format!("unknown lock {id}")))) };
+            if lock.hash.is_none() { return Ok(Err(Rejection::InvalidLock(format!("lock {id} is not a hash lock")))); }
+            if lock.hash != Some(*blake3::hash(preimage).as_bytes()) { return Ok(Err(Rejection::InvalidLock(format!("wrong preimage for lock {id}")))); }
+            v.put(&t.from, debit(from, cost, h));
+            acc.ver += 1; acc.last_update_height = h;
+            v.put(id, acc);
+            if let Err(e) = pay(v, &lock.to, lock.asset.as_deref(), lock.amount, h)? { return Ok(Err(e)); }
+            TxResult::Claimed { id: id.clone(), to: lock.to, amount: lock.amount, asset: lock.asset }
+        }
+    };
+    Ok(Ok((t.fee, res)))
+}
+
+fn insufficient(account: &str, asset: Option<&str>) -> Rejection {
+    Rejection::InsufficientFunds { account: account.to_string(), asset: asset.map(str::to_string) }
+}
+
+/// Charges `cost`, which the caller has checked the sender can afford, and uses up its nonce.
+fn debit(mut from: Account, cost: Amount, h: u64) -> Account {
+    from.ver += 1; from.nonce += 1; from.last_update_height = h;
+    from.bal = from.bal.checked_sub(cost).expect("cost checked against the balance");
+    from
+}
+
+/// A [`TxKind::Transfer`] once the checks every kind shares have passed; `cost` is already known
+/// to be affordable.
+fn apply_transfer<V: AccountView>(t: &Transfer, from: Account, cost: Amount, v: &mut V, h: u64) -> Result<Result<TxResult, Rejection>, V::Err> {
+    // `credit` is the token `to` receives, if any.
+    let (token, debit_asset, credit) = match (&t.token_op, &t.asset) {
+        (None, None) => (None, None, None),
+        (None, Some(a)) => (None, Some(a.clone()), Some(a.clone())),
+        (Some(TokenOp::Create(meta)), _) => {
+            if let Err(e) = check_token_meta(meta, t.amount) { return Ok(Err(e)); }
+            let id = asset_id(&t.from, t.nonce);
+            if v.get(&id)?.token.is_some() { return Ok(Err(Rejection::InvalidTokenOp(format!("asset {id} already exists")))); }
+            let info = TokenInfo { issuer: t.from.clone(), symbol: meta.symbol.clone(), decimals: meta.decimals, max_supply: meta.max_supply, supply: t.amount };
+            (Some((id.clone(), info)), None, Some(id))
+        }
+        (Some(op @ (TokenOp::Mint | TokenOp::Burn)), Some(a)) => {
+            let Some(mut info) = v.get(a)?.token else { return Ok(Err(Rejection::InvalidTokenOp(format!("unknown asset {a}")))) };
+            if info.issuer != t.from { return Ok(Err(Rejection::Unauthorized(format!("only the issuer of {a} can mint or burn it")))); }
+            let supply = if *op == TokenOp::Mint { info.supply.checked_add(t.amount).filter(|s| *s <= info.max_supply) } else { info.supply.checked_sub(t.amount) };
+            let Some(supply) = supply else {
+                return Ok(Err(if *op == TokenOp::Mint { Rejection::InvalidTokenOp(format!("mint exceeds the supply cap of {a}")) } else { insufficient(&t.from, Some(a)) }));
+            };
+            info.supply = supply;
+            if *op == TokenOp::Mint { (Some((a.clone(), info)), None, Some(a.clone())) } else { (Some((a.clone(), info)), Some(a.clone()), None) }
+        }
+        (Some(_), None) => return Ok(Err(Rejection::InvalidTokenOp("mint and burn need an asset".to_string()))),
+    };
+
+    let mut from = debit(from, cost, h);
+    if let Some(a) = &debit_asset {
+        if let Err(e) = from.withdraw(&t.from, a, t.amount) { return Ok(Err(e)); }
+    }
+    v.put(&t.from, from);
+    if let Some((id, info)) = token {
+        let mut acc = v.get(&id)?;
+        acc.ver += 1; acc.token = Some(info); acc.last_update_height = h;
+        v.put(&id, acc);
+    }
+    if debit_asset.is_none() || credit.is_some() {
+        if let Err(e) = pay(v, &t.to, credit.as_deref(), t.amount, h)? { return Ok(Err(e)); }
+    }
+    Ok(Ok(TxResult::Transfer))
+}
+
+/// A [`TxKind::TimeLock`] or [`TxKind::Htlc`]: moves the funds out of `from` into `lock`.
+fn apply_lock<V: AccountView>(t: &Transfer, from: Account, cost: Amount, lock: Lock, v: &mut V, h: u64, time_ms: u64) -> Result<Result<TxResult, Rejection>, V::Err> {
+    if lock.unlock.reached(h, time_ms) { return Ok(Err(Rejection::InvalidLock(format!("lock must mature after this block, not at {:?}", lock.unlock)))); }
+    let mut from = debit(from, cost, h);
+    if let Some(a) = &t.asset {
+        if let Err(e) = from.withdraw(&t.from, a, t.amount) { return Ok(Err(e)); }
+    }
+    v.put(&t.from, from);
+    let id = lock_id(&t.from, t.nonce);
+    let mut acc = v.get(&id)?;
+    acc.ver += 1; acc.lock = Some(Box::new(lock)); acc.last_update_height = h;
+    v.put(&id, acc);
+    Ok(Ok(TxResult::Locked { id }))
+}
+
+/// A [`TxKind::BatchTransfer`]: pays every recipient in turn, in the native coin or `t.asset`.
+fn apply_batch_transfer<V: AccountView>(t: &Transfer, payments: &[Payment], from: Account, cost: Amount, v: &mut V, h: u64) -> Result<Result<TxResult, Rejection>, V::Err> {
+    if payments.is_empty() { return Ok(Err(Rejection::InvalidBatch("batch transfer has no payments".to_string()))); }
+    if payments.len() > MAX_BATCH_PAYMENTS { return Ok(Err(Rejection::InvalidBatch(format!("batch transfer has {} payments, max {}", payments.len(), MAX_BATCH_PAYMENTS)))); }
+    let Some(total) = Amount::checked_sum(payments.iter().map(|p| p.amount)) else { return Ok(Err(insufficient(&t.from, t.asset.as_deref()))) };
+    let mut from = debit(from, cost, h);
+    if let Some(a) = &t.asset {
+        if let Err(e) = from.withdraw(&t.from, a, total) { return Ok(Err(e)); }
+    }
+    v.put(&t.from, from);
+    for p in payments {
+        if let Err(e) = pay(v, &p.to, t.asset.as_deref(), p.amount, h)? { return Ok(Err(e)); }
+    }
+    Ok(Ok(TxResult::BatchTransfer { recipients: payments.len() as u32, total }))
+}
+
+/// Credits `amount` of `asset`, or of the native coin, to `addr`, unless that overflows its
+/// balance.
+fn pay<V: AccountView>(v: &mut V, addr: &str, asset: Option<&str>, amount: Amount, h: u64) -> Result<Result<(), Rejection>, V::Err> {
+    let mut to = v.get(addr)?;
+    let held = match asset { Some(a) => to.asset(a), None => to.bal };
+    let Some(held) = held.checked_add(amount) else { return Ok(Err(Rejection::Overflow { account: addr.to_string(), asset: asset.map(str::to_string) })) };
+    match asset { Some(a) => to.set_asset(a, held), None => to.bal = held }
+    to.ver += 1; to.last_update_height = h;
+    v.put(addr, to);
+    Ok(Ok(()))
+}
+
+/// Events of a committed transaction.
+fn events(t: &Transfer, result: &TxResult) -> Vec<Event> {
+    let moved = |to: &str, amount: Amount, asset: Option<AssetId>| Event::Transferred { from: t.from.clone(), to: to.to_string(), amount, asset };
+    match &t.kind {
+        TxKind::Transfer => match &t.token_op {
+            None => vec![moved(&t.to, t.amount, t.asset.clone())],
+            Some(TokenOp::Create(_)) => vec![Event::Minted { to: t.to.clone(), amount: t.amount, asset: Some(asset_id(&t.from, t.nonce)) }],
+            Some(TokenOp::Mint) => vec![Event::Minted { to: t.to.clone(), amount: t.amount, asset: t.asset.clone() }],
+            Some(TokenOp::Burn) => vec![Event::Burned { from: t.from.clone(), amount: t.amount, asset: t.asset.clone().unwrap_or_default() }],
+        },
+        TxKind::BatchTransfer(ps) => ps.iter().map(|p| moved(&p.to, p.amount, t.asset.clone())).collect(),
+        TxKind::CreateAccount(_) => vec![moved(&account_address(&t.from, t.nonce), t.amount, None)],
+        TxKind::SetAccountKey(_) | TxKind::SetAuthPolicy(_) => Vec::new(),
+        TxKind::Mint => vec![Event::Minted { to: t.to.clone(), amount: t.amount, asset: None }],
+        TxKind::TimeLock(_) | TxKind::Htlc { .. } => {
+            vec![Event::Locked { id: lock_id(&t.from, t.nonce), from: t.from.clone(), to: t.to.clone(), amount: t.amount, asset: t.asset.clone() }]
+        }
+        TxKind::Claim { .. } => match result {
+            TxResult::Claimed { id, to, amount, asset } => vec![Event::Released { id: id.clone(), to: to.clone(), amount: *amount, asset: asset.clone() }],
+            _ => Vec::new(),
+        },
+    }
+}
+
+/// Receipt of `tx` once `block_height` has decided its outcome.
+fn receipt(tx: &Tx, out: Outcome, block_height: u64) -> Receipt {
+    let t = &tx.signed.transfer;
+    let (status, fee_paid, result, events) = match out {
+        Ok((fee, r)) => { let events = events(t, &r); (Status::Committed, fee, Some(r), events) }
+        Err(e) => {
+            let rejected = Event::Rejected { from: t.from.clone(), reason_code: e.reason().to_string() };
+            (Status::Rejected(e), Amount::ZERO, None, vec![rejected])
+        }
+    };
+    let latency_ms = now_ms().saturating_sub(tx.submitted_unix_ms);
+    Receipt { tx_id: tx.id, status, block_height, latency_ms, fee_paid, output: None, logs: Vec::new(), result, events }
+}
+
+/// Credits a block's collected fees once at the end of the block, so the treasury account
+/// doesn't make every transaction conflict with every other. Fees that would overflow the
+/// treasury are burned.
+fn pay_fees<V: AccountView>(v: &mut V, fees: &FeeSchedule, paid: impl IntoIterator<Item = Amount>, h: u64) -> Result<(), V::Err> {
+    let Some(treasury) = fees.treasury.as_deref() else { return Ok(()) };
+    let Some(total) = Amount::checked_sum(paid).filter(|t| *t > Amount::ZERO) else { return Ok(()) };
+    staged(v, |v| pay(v, treasury, None, total, h))?.ok();
+    Ok(())
+}
 
 pub trait Executor: Send + Sync {
-    fn balance(&self, addr: &str) -> u64;
+    fn balance(&self, addr: &str) -> Amount;
+    fn asset_balance(&self, addr: &str, asset: &str) -> Amount;
+    fn token(&self, asset: &str) -> Option<TokenInfo>;
+    fn account(&self, addr: &str) -> Option<AccountState>;
+    /// Pending locks that `addr` funded or will receive.
+    fn locks(&self, addr: &str) -> Vec<PendingLock>;
+    fn nonce(&self, addr: &str) -> u64;
     fn apply_batch_blocking(&self, batch: Batch, block_height: u64) -> Vec<Receipt>;
+    /// Receipts `txs` would get as the next block, executed against a copy-on-write view of the
+    /// current state that is thrown away afterwards.
+    fn simulate(&self, txs: &[Tx]) -> Vec<Receipt>;
     fn last_height(&self) -> u64;
     fn snapshot(&self) -> Vec<AccountState>;
     fn diff_since(&self, since: u64) -> Vec<AccountState>;
     fn restore(&self, replace: bool, items: Vec<AccountState>);
+    /// Sparse Merkle root over every account's `AccountState`, plus any contract state.
+    fn state_root(&self) -> smt::Hash;
+    /// Proof of `addr` against the state root after `height`, if that height is still retained.
+    fn prove(&self, addr: &str, height: u64) -> Option<StateProof>;
+}
+
+/// An account (or its absence) proven against the state root after `height`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateProof {
+    pub height: u64,
+    pub root: smt::Hash,
+    pub account: Option<AccountState>,
+    pub proof: smt::SmtProof,
+}
+
+impl StateProof {
+    pub fn verify(&self, addr: &str) -> bool {
+        if self.account.as_ref().is_some_and(|a| a.addr != addr) { return false; }
+        self.proof.verify(&self.root, &AccountState::key(addr), self.account.as_ref().map(|a| a.value_hash()).as_ref())
+    }
 }
 
 #[derive(Clone)]
 pub struct SimpleExecutor {
-    pub accounts: Arc<RwLock<HashMap<String, (u64, u64, u64)>>>,
+    pub accounts: Arc<RwLock<HashMap<String, Account>>>,
     pub last_height: Arc<RwLock<u64>>,
+    pub chain_id: String,
+    pub fees: FeeSchedule,
+    /// The only account allowed to send [`TxKind::Mint`]; nobody can mint when unset.
+    pub governance: Option<String>,
+    /// Ids of the accounts holding a pending lock, checked for maturity every block.
+    pending_locks: Arc<RwLock<BTreeSet<String>>>,
+    history: Arc<RwLock<VecDeque<(u64, UndoLog)>>>,
+    tree: Arc<RwLock<StateTree>>,
+    store: Option<Arc<dyn Kv>>,
 }
+impl Default for SimpleExecutor { fn default() -> Self { Self::new(types::DEFAULT_CHAIN_ID) } }
 impl SimpleExecutor {
-    pub fn new() -> Self {
-        let s = Self { accounts: Arc::new(RwLock::new(HashMap::new())), last_height: Arc::new(RwLock::new(0)) };
-        s.credit("alice".into(), 1_000_000_000_000, 0);
+    pub fn new(chain_id: impl Into<String>) -> Self {
+        let s = Self::empty(chain_id.into(), None);
+        s.seed_genesis();
         s
     }
-    fn credit(&self, addr: String, amount: u64, h: u64) {
-        let mut w = self.accounts.write();
-        let e = w.entry(addr).or_insert((0,0,0));
-        e.0 += 1; e.1 = e.1.saturating_add(amount); e.2 = h;
+    /// Executor whose state lives in `kv`: resumes from the stored height, or writes the genesis
+    /// `accounts` to an empty store. Refuses a store initialized from a different genesis. Every
+    /// block's account updates and height are then committed atomically.
+    pub fn open(chain_id: impl Into<String>, kv: Arc<dyn Kv>, accounts: &[(String, Amount)], genesis_hash: [u8; 32]) -> anyhow::Result<Self> {
+        let s = Self::empty(chain_id.into(), Some(kv.clone()));
+        match kv.get(GENESIS_KEY) {
+            Some(stored) if stored != genesis_hash => anyhow::bail!("state store was created from genesis {}, not {}", hex::encode(stored), hex::encode(genesis_hash)),
+            Some(_) => {}
+            None => kv.put(GENESIS_KEY, genesis_hash.to_vec()),
+        }
+        match kv.get(LAST_HEIGHT_KEY) {
+            Some(h) => {
+                let h: [u8; 8] = h.as_slice().try_into().map_err(|_| anyhow::anyhow!("corrupt stored height"))?;
+                let mut w = s.accounts.write();
+                for (k, v) in kv.scan_prefix(ACCOUNT_PREFIX) {
+                    let addr = String::from_utf8(k[ACCOUNT_PREFIX.len()..].to_vec())?;
+                    w.insert(addr, bincode::deserialize(&v)?);
+                }
+                s.index_locks(&w);
+                let mut tree = s.tree.write();
+                for (addr, acc) in w.iter() { tree.set_account(addr, Some(acc)); }
+                tree.height = u64::from_le_bytes(h);
+                drop((w, tree));
+                *s.last_height.write() = u64::from_le_bytes(h);
+            }
+            None => {
+                for (addr, bal) in accounts { s.credit(addr.clone(), *bal)?; }
+                let w = s.accounts.read();
+                s.persist(&w, 0, w.keys(), WriteBatch::new())?;
+            }
+        }
+        Ok(s)
+    }
+    fn empty(chain_id: String, store: Option<Arc<dyn Kv>>) -> Self {
+        Self { accounts: Arc::new(RwLock::new(HashMap::new())), last_height: Arc::new(RwLock::new(0)), chain_id, fees: FeeSchedule::default(), governance: None, pending_locks: Arc::default(), history: Arc::default(), tree: Arc::default(), store }
     }
-    fn debit(&self, addr: String, amount: u64, h: u64) -> Result<(), String> {
+    pub fn with_fees(mut self, fees: FeeSchedule) -> Self { self.fees = fees; self }
+    pub fn with_governance(mut self, governance: Option<String>) -> Self { self.governance = governance; self }
+    fn seed_genesis(&self) {
+        let (_sk, alice) = crypto::keypair_from_seed(b"alice");
+        self.credit(crypto::address(&alice), Amount(1_000_000_000_000)).expect("empty genesis");
+    }
+    /// Adds a genesis balance.
+    fn credit(&self, addr: String, amount: Amount) -> anyhow::Result<()> {
         let mut w = self.accounts.write();
-        let e = w.entry(addr.clone()).or_insert((0,0,0));
-        if e.1 >= amount { e.0 += 1; e.1 -= amount; e.2 = h; Ok(()) } else { Err(format!("insufficient funds: {}", addr)) }
+        let e = w.entry(addr.clone()).or_default();
+        e.bal = e.bal.checked_add(amount).ok_or_else(|| anyhow::anyhow!("genesis balance of {addr} overflows"))?;
+        e.ver += 1;
+        self.tree.write().set_account(&addr, w.get(&addr));
+        Ok(())
+    }
+    fn index_locks(&self, accounts: &HashMap<String, Account>) {
+        *self.pending_locks.write() = accounts.iter().filter(|(_, a)| a.lock.is_some()).map(|(id, _)| id.clone()).collect();
+    }
+    /// Pays out every pending lock that matures at `h` and `time_ms`, before the block's
+    /// transactions run. A lock whose payout would overflow the payee stays pending.
+    fn release_locks<V: AccountView>(&self, v: &mut V, h: u64, time_ms: u64) -> Result<(), V::Err> {
+        let pending: Vec<String> = self.pending_locks.read().iter().cloned().collect();
+        for id in pending {
+            staged(v, |v| {
+                let mut acc = v.get(&id)?;
+                let Some(lock) = acc.lock.take_if(|l| l.unlock.reached(h, time_ms)) else { return Ok(Ok(())) };
+                acc.ver += 1; acc.last_update_height = h;
+                v.put(&id, acc);
+                pay(v, lock.payee(), lock.asset.as_deref(), lock.amount, h)
+            })?.ok();
+        }
+        Ok(())
+    }
+    /// Writes the current value of `touched` accounts and `height` to the store in one batch,
+    /// along with whatever `b` already holds.
+    fn persist<'a>(&self, accounts: &HashMap<String, Account>, height: u64, touched: impl Iterator<Item = &'a String>, mut b: WriteBatch) -> anyhow::Result<()> {
+        let Some(kv) = &self.store else { return Ok(()) };
+        for addr in touched {
+            match accounts.get(addr) { Some(acc) => b.put(&account_key(addr), bincode::serialize(acc)?), None => b.delete(&account_key(addr)) }
+        }
+        b.put(LAST_HEIGHT_KEY, height.to_le_bytes().to_vec());
+        kv.write_batch(b)
+    }
+    /// Finishes a block while the caller still holds the account write lock; `extra` carries
+    /// any other state the block changed, and `leaves` its state tree entries.
+    fn commit_block(&self, accounts: &HashMap<String, Account>, height: u64, undo: UndoLog, extra: WriteBatch, leaves: impl IntoIterator<Item = (smt::Hash, Option<smt::Hash>)>) {
+        // Memory already holds the new state, so a block that can't be made durable must stop the node.
+        self.persist(accounts, height, undo.keys(), extra).expect("persist block state");
+        let mut tree = self.tree.write();
+        tree.advance(height);
+        for addr in undo.keys() { tree.set_account(addr, accounts.get(addr)); }
+        for (key, value) in leaves { tree.set(key, value); }
+        drop(tree);
+        let mut pending = self.pending_locks.write();
+        for addr in undo.keys() {
+            if accounts.get(addr).is_some_and(|a| a.lock.is_some()) { pending.insert(addr.clone()); } else { pending.remove(addr); }
+        }
+        drop(pending);
+        let mut h = self.history.write();
+        h.push_back((height, undo));
+        while h.len() > STATE_HISTORY { h.pop_front(); }
     }
 }
 impl Executor for SimpleExecutor {
-    fn balance(&self, addr: &str) -> u64 { self.accounts.read().get(addr).map(|(_,b,_)| *b).unwrap_or(0) }
+    fn balance(&self, addr: &str) -> Amount { self.accounts.read().get(addr).map(|a| a.bal).unwrap_or_default() }
+    fn asset_balance(&self, addr: &str, asset: &str) -> Amount { self.accounts.read().get(addr).map(|a| a.asset(asset)).unwrap_or_default() }
+    fn token(&self, asset: &str) -> Option<TokenInfo> { self.accounts.read().get(asset).and_then(|a| a.token.clone()) }
+    fn account(&self, addr: &str) -> Option<AccountState> { self.accounts.read().get(addr).map(|a| a.to_state(addr)) }
+    fn locks(&self, addr: &str) -> Vec<PendingLock> {
+        let accounts = self.accounts.read();
+        self.pending_locks.read().iter()
+            .filter_map(|id| accounts.get(id)?.lock.as_deref().map(|lock| PendingLock { id: id.clone(), lock: lock.clone() }))
+            .filter(|p| p.lock.from == addr || p.lock.to == addr)
+            .collect()
+    }
+    fn nonce(&self, addr: &str) -> u64 { self.accounts.read().get(addr).map(|a| a.nonce).unwrap_or(0) }
     fn apply_batch_blocking(&self, batch: Batch, block_height: u64) -> Vec<Receipt> {
         let _t = EXEC_LATENCY.start_timer();
         *self.last_height.write() = block_height;
-        batch.txs.into_iter().map(|tx| {
-            let res = self.debit(tx.transfer.from.clone(), tx.transfer.amount, block_height);
-            let status = match res {
-                Ok(()) => { self.credit(tx.transfer.to.clone(), tx.transfer.amount, block_height); Status::Committed }
-                Err(e) => Status::Rejected(e),
-            };
-            let latency_ms = now_ms().saturating_sub(tx.submitted_unix_ms);
-            Receipt { tx_id: tx.id, status, block_height, latency_ms }
+        let mut w = self.accounts.write();
+        let mut undo = UndoLog::new();
+        let Ok(()) = self.release_locks(&mut Journal { accounts: &mut w, undo: &mut undo }, block_height, batch.time_ms);
+        let receipts: Vec<Receipt> = batch.txs.into_iter().map(|tx| {
+            let res = tx.signed.verify(&self.chain_id).and_then(|()| {
+                let Ok(out) = apply_tx(&tx.signed, &mut Journal { accounts: &mut w, undo: &mut undo }, block_height, batch.time_ms, self);
+                out
+            });
+            receipt(&tx, res, block_height)
+        }).collect();
+        let Ok(()) = pay_fees(&mut Journal { accounts: &mut w, undo: &mut undo }, &self.fees, receipts.iter().map(|r| r.fee_paid), block_height);
+        self.commit_block(&w, block_height, undo, WriteBatch::new(), []);
+        receipts
+    }
+    fn simulate(&self, txs: &[Tx]) -> Vec<Receipt> {
+        let accounts = self.accounts.read();
+        let (h, time_ms) = (self.last_height() + 1, now_ms() as u64);
+        let mut v = Overlay { base: &accounts, writes: HashMap::new() };
+        let Ok(()) = self.release_locks(&mut v, h, time_ms);
+        txs.iter().map(|tx| {
+            let res = tx.signed.verify(&self.chain_id).and_then(|()| { let Ok(out) = apply_tx(&tx.signed, &mut v, h, time_ms, self); out });
+            receipt(tx, res, h)
         }).collect()
     }
     fn last_height(&self) -> u64 { *self.last_height.read() }
     fn snapshot(&self) -> Vec<AccountState> {
-        self.accounts.read().iter().map(|(a,(v,b,h))| AccountState { addr:a.clone(), ver:*v, bal:*b, last_update_height:*h }).collect()
+        self.accounts.read().iter().map(|(a, acc)| acc.to_state(a)).collect()
     }
     fn diff_since(&self, since: u64) -> Vec<AccountState> {
-        self.accounts.read().iter().filter_map(|(a,(v,b,h))| if *h > since { Some(AccountState{addr:a.clone(), ver:*v, bal:*b, last_update_height:*h}) } else { None }).collect()
+        self.accounts.read().iter().filter(|(_, acc)| acc.last_update_height > since).map(|(a, acc)| acc.to_state(a)).collect()
     }
     fn restore(&self, replace: bool, items: Vec<AccountState>) {
-        if replace { self.accounts.write().clear(); }
+        self.history.write().clear();
         let mut w = self.accounts.write();
-        for it in items { w.insert(it.addr, (it.ver, it.bal, it.last_update_height)); }
+        let mut touched: Vec<String> = if replace { w.drain().map(|(a, _)| a).collect() } else { Vec::new() };
+        for it in items {
+            touched.push(it.addr.clone());
+            w.insert(it.addr, Account { ver: it.ver, bal: it.bal, nonce: it.nonce, last_update_height: it.last_update_height, assets: it.assets, token: it.token, auth: it.auth, lock: it.lock.map(Box::new) });
+        }
+        self.persist(&w, self.last_height(), touched.iter(), WriteBatch::new()).expect("persist restored state");
+        self.index_locks(&w);
+        let mut tree = self.tree.write();
+        tree.versions.clear();
+        tree.height = self.last_height();
+        for addr in &touched { tree.set_account(addr, w.get(addr)); }
     }
-}
-
-pub struct BlockStmExecutor { inner: Arc<SimpleExecutor>, max_retries: usize }
-impl BlockStmExecutor { pub fn new(inner: Arc<SimpleExecutor>) -> Self { Self { inner, max_retries: 5 } } }
-impl Executor for BlockStmExecutor {
-    fn balance(&self, addr: &str) -> u64 { self.inner.balance(addr) }
-    fn apply_batch_blocking(&self, batch: Batch, block_height: u64) -> Vec<Receipt> {
-        let _t = EXEC_LATENCY.start_timer();
-        let mut txs = batch.txs;
-        *self.inner.last_height.write() = block_height;
-        let mut receipts: Vec<Option<Receipt>> = vec![None; txs.len()];
-        for _round in 0..self.max_retries {
-            let snapshot = self.inner.accounts.read().clone();
-            for (i, tx) in txs.iter_mut().enumerate() {
-                if receipts[i].is_some() { continue; }
-                let from_v = snapshot.get(&tx.transfer.from).map(|(v,b,_)| (*v,*b)).unwrap_or((0,0));
-                let to_v = snapshot.get(&tx.transfer.to).map(|(v,b,_)| (*v,*b)).unwrap_or((0,0));
-                if from_v.1 < tx.transfer.amount {
-                    receipts[i] = Some(Receipt { tx_id: tx.id, status: Status::Rejected("insufficient funds".into()), block_height, latency_ms: now_ms().saturating_sub(tx.submitted_unix_ms) });
-                    continue;
-                }
-                let mut w = self.inner.accounts.write();
-                let (ver_f, bal_f, _) = *w.get(&tx.transfer.from).unwrap_or(&(from_v.0, from_v.1, block_height));
-                let (ver_t, bal_t, _) = *w.get(&tx.transfer.to).unwrap_or(&(to_v.0, to_v.1, block_height));
-                if ver_f == from_v.0 && ver_t == to_v.0 && bal_f >= tx.transfer.amount {
-                    w.insert(tx.transfer.from.clone(), (ver_f+1, bal_f - tx.transfer.amount, block_height));
-                    w.insert(tx.transfer.to.clone(), (ver_t+1, bal_t + tx.transfer.amount, block_height));
-                    receipts[i] = Some(Receipt { tx_id: tx.id, status: Status::Committed, block_height, latency_ms: now_ms().saturating_sub(tx.submitted_unix_ms) });
-                }
-            }
-            if receipts.iter().all(|r| r.is_some()) { break; }
+    fn state_root(&self) -> smt::Hash { self.tree.read().current.root() }
+    fn prove(&self, addr: &str, height: u64) -> Option<StateProof> {
+        // Batches update the tree and append their undo log while still holding the account lock, so all three agree.
+        let accounts = self.accounts.read();
+        let tree = self.tree.read().at(height)?.clone();
+        let mut account = accounts.get(addr).cloned();
+        for (_, undo) in self.history.read().iter().rev().take_while(|(h, _)| *h > height) {
+            if let Some(prev) = undo.get(addr) { account = prev.clone(); }
         }
-        for i in 0..receipts.len() { if receipts[i].is_none() {
-            let tx = &txs[i];
-            receipts[i] = Some(Receipt { tx_id: tx.id, status: Status::Rejected("conflict".into()), block_height, latency_ms: now_ms().saturating_sub(tx.submitted_unix_ms) }); } }
-        receipts.into_iter().map(|r| r.unwrap()).collect()
+        drop(accounts);
+        Some(StateProof { height, root: tree.root(), account: account.map(|a| a.to_state(addr)), proof: tree.prove(&AccountState::key(addr)) })
     }
-    fn last_height(&self) -> u64 { *self.inner.last_height.read() }
-    fn snapshot(&self) -> Vec<AccountState> { self.inner.snapshot() }
-    fn diff_since(&self, since: u64) -> Vec<AccountState> { self.inner.diff_since(since) }
-    fn restore(&self, replace: bool, items: Vec<AccountState>) { self.inner.restore(replace, items) }
 }
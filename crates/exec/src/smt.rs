@@ -0,0 +1,163 @@
+
+//! Sparse Merkle tree over 256-bit keys.
+//!
+//! Subtrees are compacted: an empty subtree hashes to [`EMPTY`], a subtree holding a single leaf
+//! hashes to that leaf, and only subtrees with two or more leaves become internal nodes. Leaf and
+//! internal hashes are domain-separated, so a proof can't pass one off as the other.
+
+use serde::{Serialize, Deserialize};
+use std::sync::Arc;
+
+pub type Hash = [u8; 32];
+pub const EMPTY: Hash = [0u8; 32];
+
+pub fn leaf_hash(key: &Hash, value: &Hash) -> Hash {
+    let mut h = blake3::Hasher::new();
+    h.update(&[0x00]); h.update(key); h.update(value);
+    *h.finalize().as_bytes()
+}
+
+fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    if left == &EMPTY && right == &EMPTY { return EMPTY; }
+    let mut h = blake3::Hasher::new();
+    h.update(&[0x01]); h.update(left); h.update(right);
+    *h.finalize().as_bytes()
+}
+
+/// Bit `depth` of `key`, most significant bit first.
+fn bit(key: &Hash, depth: usize) -> bool { key[depth / 8] & (0x80 >> (depth % 8)) != 0 }
+
+/// Subtree node; an empty subtree is `None`. Nodes are shared between versions of a tree, so
+/// cloning a tree is cheap and an update copies only the path to the changed leaf.
+#[derive(Debug)]
+enum Node {
+    Leaf { key: Hash, value: Hash },
+    /// Two or more leaves below; `hash` covers both children.
+    Internal { hash: Hash, left: Option<Arc<Node>>, right: Option<Arc<Node>> },
+}
+
+type Subtree = Option<Arc<Node>>;
+
+fn hash_of(n: &Subtree) -> Hash {
+    match n.as_deref() {
+        None => EMPTY,
+        Some(Node::Leaf { key, value }) => leaf_hash(key, value),
+        Some(Node::Internal { hash, .. }) => *hash,
+    }
+}
+
+fn internal(left: Subtree, right: Subtree) -> Subtree {
+    Some(Arc::new(Node::Internal { hash: node_hash(&hash_of(&left), &hash_of(&right)), left, right }))
+}
+
+/// `n`, the subtree at `depth`, with `key` set to `value`; also whether `key` is new.
+fn insert(n: &Subtree, depth: usize, key: Hash, value: Hash) -> (Subtree, bool) {
+    match n.as_deref() {
+        None => (Some(Arc::new(Node::Leaf { key, value })), true),
+        Some(Node::Leaf { key: k, .. }) if *k == key => (Some(Arc::new(Node::Leaf { key, value })), false),
+        Some(Node::Leaf { key: k, .. }) => {
+            // Push the existing leaf one level down; this repeats until the two paths part.
+            let down = if bit(k, depth) { internal(None, n.clone()) } else { internal(n.clone(), None) };
+            insert(&down, depth, key, value)
+        }
+        Some(Node::Internal { left, right, .. }) => {
+            if bit(&key, depth) {
+                let (r, new) = insert(right, depth + 1, key, value);
+                (internal(left.clone(), r), new)
+            } else {
+                let (l, new) = insert(left, depth + 1, key, value);
+                (internal(l, right.clone()), new)
+            }
+        }
+    }
+}
+
+/// `n`, the subtree at `depth`, without `key`; also whether `key` was there.
+fn remove(n: &Subtree, depth: usize, key: &Hash) -> (Subtree, bool) {
+    match n.as_deref() {
+        None => (None, false),
+        Some(Node::Leaf { key: k, .. }) => if k == key { (None, true) } else { (n.clone(), false) },
+        Some(Node::Internal { left, right, .. }) => {
+            let (l, r, removed) = if bit(key, depth) {
+                let (r, removed) = remove(right, depth + 1, key);
+                (left.clone(), r, removed)
+            } else {
+                let (l, removed) = remove(left, depth + 1, key);
+                (l, right.clone(), removed)
+            };
+            if !removed { return (n.clone(), false); }
+            // A subtree left holding a single leaf hashes to that leaf, so it becomes one.
+            match (l.as_deref(), r.as_deref()) {
+                (None, None) => (None, true),
+                (Some(Node::Leaf { .. }), None) => (l, true),
+                (None, Some(Node::Leaf { .. })) => (r, true),
+                _ => (internal(l, r), true),
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SparseMerkleTree { root: Subtree, len: usize }
+
+impl SparseMerkleTree {
+    pub fn new() -> Self { Self::default() }
+    pub fn insert(&mut self, key: Hash, value: Hash) {
+        let (root, new) = insert(&self.root, 0, key, value);
+        self.root = root;
+        if new { self.len += 1; }
+    }
+    pub fn remove(&mut self, key: &Hash) {
+        let (root, removed) = remove(&self.root, 0, key);
+        self.root = root;
+        if removed { self.len -= 1; }
+    }
+    pub fn len(&self) -> usize { self.len }
+    pub fn is_empty(&self) -> bool { self.len == 0 }
+
+    pub fn root(&self) -> Hash { hash_of(&self.root) }
+
+    /// Proof of the value stored under `key`, or of its absence.
+    pub fn prove(&self, key: &Hash) -> SmtProof {
+        let mut siblings = Vec::new();
+        let mut n = &self.root;
+        let mut depth = 0;
+        while let Some(Node::Internal { left, right, .. }) = n.as_deref() {
+            let (path, other) = if bit(key, depth) { (right, left) } else { (left, right) };
+            siblings.push(hash_of(other));
+            n = path;
+            depth += 1;
+        }
+        let leaf = match n.as_deref() { Some(Node::Leaf { key, value }) => Some((*key, *value)), _ => None };
+        SmtProof { siblings, leaf }
+    }
+}
+
+/// Sibling hashes from the root down, ending at the leaf (or empty subtree) on the key's path.
+/// `leaf` is the leaf found there: the key's own leaf, another key sharing the path, or none.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmtProof {
+    pub siblings: Vec<Hash>,
+    pub leaf: Option<(Hash, Hash)>,
+}
+
+impl SmtProof {
+    /// Checks that `key` maps to `value` under `root` (`None` proves the key is absent).
+    pub fn verify(&self, root: &Hash, key: &Hash, value: Option<&Hash>) -> bool {
+        if self.siblings.len() > 256 { return false; }
+        let depth = self.siblings.len();
+        let start = match (&self.leaf, value) {
+            (Some((k, v)), Some(want)) if k == key => { if v != want { return false; } leaf_hash(k, v) }
+            (Some((k, v)), None) if k != key => {
+                if (0..depth).any(|d| bit(k, d) != bit(key, d)) { return false; }
+                leaf_hash(k, v)
+            }
+            (None, None) => EMPTY,
+            _ => return false,
+        };
+        let computed = self.siblings.iter().enumerate().rev().fold(start, |acc, (d, sib)| {
+            if bit(key, d) { node_hash(sib, &acc) } else { node_hash(&acc, sib) }
+        });
+        &computed == root
+    }
+}
@@ -0,0 +1,323 @@
+
+//! WASM contracts on top of [`SimpleExecutor`], behind the `wasm` feature.
+//!
+//! A transfer whose payload is a WASM module (it starts with `\0asm`) deploys that module at
+//! [`contract_address`]`(from, nonce)`, which must be the transfer's `to`. A transfer carrying a
+//! payload to a deployed contract calls the contract's exported `call` function with the payload
+//! as input. Anything else, including payloads sent to ordinary accounts, is a plain transfer.
+//!
+//! Only plain transfers reach contracts, and the transfer itself always runs first, through the
+//! same transition as in the other executors. A call that traps or runs out of fuel rejects the
+//! transaction: the transfer and any storage writes are rolled back, but the sender still pays
+//! for the fuel burned and uses up the nonce. Contract code and storage are committed to the
+//! state tree next to the accounts.
+//!
+//! Contracts export `memory` and import these host functions from `env`:
+//! - `input_len() -> i32` and `input_read(ptr)` copy out the call input
+//! - `storage_read(key_ptr, key_len, out_ptr, out_cap) -> i32` returns the value length, or -1 if unset
+//! - `storage_write(key_ptr, key_len, val_ptr, val_len)`
+//! - `set_output(ptr, len)` sets the receipt output; `log(ptr, len)` appends a receipt log
+
+use super::{AccountState, AccountView, Executor, PendingLock, Journal, Overlay, SimpleExecutor, StateProof, TokenInfo, UndoLog, EXEC_LATENCY, apply_tx, debit, min_fee, pay_fees, receipt, smt, staged};
+use parking_lot::{Mutex, RwLock};
+use std::collections::{BTreeMap, HashMap};
+use std::convert::Infallible;
+use std::sync::Arc;
+use storage::WriteBatch;
+use types::{Amount, Batch, Event, Receipt, Rejection, SignedTx, Transfer, Tx, TxKind, TxResult, now_ms};
+use wasmi::{Caller, Config, Engine, Extern, Linker, Module, Store};
+
+const WASM_MAGIC: &[u8] = b"\0asm";
+const CONTRACT_PREFIX: &[u8] = b"wasm/";
+const CODE_PREFIX: &[u8] = b"wasm/code/";
+const DATA_PREFIX: &[u8] = b"wasm/data/";
+
+/// Fuel charged per byte a contract moves through a host function.
+pub const HOST_BYTE_FUEL: u64 = 10;
+
+/// Address a contract deployed by `deployer` with `nonce` lives at.
+pub fn contract_address(deployer: &str, nonce: u64) -> String {
+    let mut h = blake3::Hasher::new();
+    h.update(b"contract"); h.update(&(deployer.len() as u32).to_le_bytes()); h.update(deployer.as_bytes()); h.update(&nonce.to_le_bytes());
+    hex::encode(&h.finalize().as_bytes()[..20])
+}
+
+fn code_key(addr: &str) -> Vec<u8> { [CODE_PREFIX, addr.as_bytes()].concat() }
+fn data_key(addr: &str, key: &[u8]) -> Vec<u8> { [DATA_PREFIX, addr.as_bytes(), b"/", key].concat() }
+
+/// State-tree leaf of a contract entry. No UTF-8 address starts with 0xff, so these keys never
+/// collide with account keys.
+fn leaf(key: &[u8], value: Option<&[u8]>) -> (smt::Hash, Option<smt::Hash>) {
+    let mut h = blake3::Hasher::new();
+    h.update(&[0xff]); h.update(key);
+    (*h.finalize().as_bytes(), value.map(|v| *blake3::hash(v).as_bytes()))
+}
+
+/// Contract code and storage by store key.
+type ContractState = BTreeMap<Vec<u8>, Vec<u8>>;
+
+/// Contract state as a block or simulation sees it: the committed state, shared rather than
+/// copied, plus what the transactions so far have written on top.
+#[derive(Default)]
+struct Contracts {
+    base: Arc<ContractState>,
+    writes: ContractState,
+    /// Set while committing a block; only then may newly compiled modules be cached.
+    live: bool,
+}
+impl Contracts {
+    fn get(&self, key: &[u8]) -> Option<&Vec<u8>> { self.writes.get(key).or_else(|| self.base.get(key)) }
+}
+
+/// Per-call limits. When gas has a price, a call also gets no more fuel than the fee it paid
+/// above [`min_fee`] buys.
+#[derive(Debug, Clone)]
+pub struct WasmLimits { pub max_fuel: u64, pub max_code_bytes: usize }
+impl Default for WasmLimits { fn default() -> Self { Self { max_fuel: 10_000_000, max_code_bytes: 256 * 1024 } } }
+
+/// What a contract call sees and produces. Holds the block's contract state while it runs.
+#[derive(Default)]
+struct Host { addr: String, input: Vec<u8>, contracts: Contracts, writes: ContractState, output: Option<Vec<u8>>, logs: Vec<String> }
+
+/// Output and logs of a contract deploy or call.
+#[derive(Default)]
+struct Outcome { output: Option<Vec<u8>>, logs: Vec<String> }
+
+pub struct WasmExecutor {
+    inner: Arc<SimpleExecutor>,
+    engine: Engine,
+    linker: Linker<Host>,
+    limits: WasmLimits,
+    state: RwLock<Arc<ContractState>>,
+    modules: Mutex<HashMap<String, Arc<Module>>>,
+}
+
+impl WasmExecutor {
+    /// Wraps `inner`, picking up any contracts already in its store.
+    pub fn new(inner: Arc<SimpleExecutor>) -> Self {
+        let mut config = Config::default();
+        // Floats are the one source of nondeterminism across hosts.
+        config.consume_fuel(true).floats(false);
+        let engine = Engine::new(&config);
+        let linker = host_functions(&engine);
+        let state: ContractState = inner.store.as_ref().map(|kv| kv.scan_prefix(CONTRACT_PREFIX).into_iter().collect()).unwrap_or_default();
+        let mut tree = inner.tree.write();
+        for (k, v) in &state { let (key, value) = leaf(k, Some(v)); tree.set(key, value); }
+        drop(tree);
+        Self { inner, engine, linker, limits: WasmLimits::default(), state: RwLock::new(Arc::new(state)), modules: Mutex::default() }
+    }
+    pub fn with_limits(mut self, limits: WasmLimits) -> Self { self.limits = limits; self }
+    pub fn code(&self, addr: &str) -> Option<Vec<u8>> { self.state.read().get(&code_key(addr)).cloned() }
+    pub fn storage(&self, addr: &str, key: &[u8]) -> Option<Vec<u8>> { self.state.read().get(&data_key(addr, key)).cloned() }
+
+    /// A rejection carries the fee charged anyway: a failed contract call still pays for its fuel.
+    fn apply_tx<V: AccountView<Err = Infallible>>(&self, tx: &SignedTx, v: &mut V, contracts: &mut Contracts, h: u64, time_ms: u64) -> Result<(Amount, TxResult, Outcome), (Rejection, Amount)> {
+        let t = &tx.transfer;
+        let payload = t.payload.as_deref().unwrap_or_default();
+        let deploy = payload.starts_with(WASM_MAGIC);
+        if t.kind != TxKind::Transfer || t.token_op.is_some() || (!deploy && (payload.is_empty() || contracts.get(&code_key(&t.to)).is_none())) {
+            let Ok(out) = apply_tx(tx, v, h, time_ms, &self.inner);
+            return out.map(|(fee, r)| (fee, r, Outcome::default())).map_err(|e| (e, Amount::ZERO));
+        }
+        // The transfer is staged with the call, so a failed call rolls it back.
+        let mut fuel_used = None;
+        let Ok(out) = staged(v, |v| {
+            let Ok(out) = apply_tx(tx, v, h, time_ms, &self.inner);
+            let (fee, result) = match out { Ok(x) => x, Err(e) => return Ok(Err(e)) };
+            let res = if deploy { self.deploy(t, payload, contracts).map_err(|e| (e, 0)) } else { self.call(t, payload, fee, contracts) };
+            Ok(match res {
+                Ok((writes, out)) => Ok((fee, result, writes, out)),
+                Err((e, used)) => { fuel_used = Some(used); Err(Rejection::ContractFailed(e)) }
+            })
+        });
+        match (out, fuel_used) {
+            (Ok((fee, result, writes, out)), _) => {
+                contracts.writes.extend(writes);
+                Ok((fee, result, out))
+            }
+            (Err(e), None) => Err((e, Amount::ZERO)),
+            (Err(e), Some(used)) => {
+                // The sender still pays for the fuel burned and uses up the nonce, so the call can't be replayed.
+                let gas_price = self.inner.fees.gas_price;
+                let charged = Amount(used.saturating_mul(gas_price).saturating_add(min_fee(t, gas_price).0).min(t.fee.0));
+                let Ok(from) = v.get(&t.from);
+                v.put(&t.from, debit(from, charged, h));
+                Err((e, charged))
+            }
+        }
+    }
+
+    /// Executes one transaction of a block and builds its receipt, contract output and logs included.
+    fn execute<V: AccountView<Err = Infallible>>(&self, tx: &Tx, v: &mut V, contracts: &mut Contracts, h: u64, time_ms: u64) -> Receipt {
+        let res = tx.signed.verify(&self.inner.chain_id).map_err(|e| (e, Amount::ZERO)).and_then(|()| self.apply_tx(&tx.signed, v, contracts, h, time_ms));
+        let (res, out, fee_paid) = match res { Ok((fee, r, out)) => (Ok((fee, r)), out, fee), Err((e, charged)) => (Err(e), Outcome::default(), charged) };
+        let mut r = receipt(tx, res, h);
+        r.fee_paid = fee_paid;
+        let contract = &tx.signed.transfer.to;
+        r.events.extend(out.logs.iter().map(|m| Event::Log { contract: contract.clone(), message: m.clone() }));
+        r.output = out.output; r.logs = out.logs;
+        r
+    }
+
+    /// Checks and stores `code`; the receipt output is the new contract's address.
+    fn deploy(&self, t: &Transfer, code: &[u8], contracts: &Contracts) -> Result<(ContractState, Outcome), String> {
+        let addr = contract_address(&t.from, t.nonce);
+        if t.to != addr { return Err(format!("contract deploy must be sent to {addr}")); }
+        if code.len() > self.limits.max_code_bytes { return Err(format!("contract too large: {} bytes, max {}", code.len(), self.limits.max_code_bytes)); }
+        if contracts.get(&code_key(&addr)).is_some() { return Err(format!("contract already deployed at {addr}")); }
+        let module = Module::new(&self.engine, code).map_err(|e| format!("invalid contract: {e}"))?;
+        let mut store = Store::new(&self.engine, Host::default());
+        let instance = self.linker.instantiate(&mut store, &module).and_then(|p| p.ensure_no_start(&mut store).map_err(Into::into))
+            .map_err(|e| format!("invalid contract: {e}"))?;
+        instance.get_typed_func::<(), ()>(&store, "call").map_err(|e| format!("invalid contract: {e}"))?;
+        if contracts.live { self.modules.lock().insert(addr.clone(), Arc::new(module)); }
+        Ok((BTreeMap::from([(code_key(&addr), code.to_vec())]), Outcome { output: Some(addr.into_bytes()), logs: Vec::new() }))
+    }
+
+    /// Runs the contract at `t.to`, returning the storage it wrote along with its output and logs,
+    /// or why it failed and the fuel it burned.
+    fn call(&self, t: &Transfer, input: &[u8], fee: Amount, contracts: &mut Contracts) -> Result<(ContractState, Outcome), (String, u64)> {
+        // Free gas still runs under `max_fuel`.
+        let gas_price = self.inner.fees.gas_price;
+        let fuel = (fee.0 - min_fee(t, gas_price).0).checked_div(gas_price).map_or(self.limits.max_fuel, |f| f.min(self.limits.max_fuel));
+        let cached = self.modules.lock().get(&t.to).cloned();
+        let module = match cached {
+            Some(m) => m,
+            None => {
+                let code = contracts.get(&code_key(&t.to)).expect("calls only reach deployed contracts");
+                let m = Arc::new(Module::new(&self.engine, code).map_err(|e| (format!("invalid contract: {e}"), 0))?);
+                if contracts.live { self.modules.lock().insert(t.to.clone(), m.clone()); }
+                m
+            }
+        };
+
+        let host = Host { addr: t.to.clone(), input: input.to_vec(), contracts: std::mem::take(contracts), ..Host::default() };
+        let mut store = Store::new(&self.engine, host);
+        store.set_fuel(fuel).expect("fuel metering is enabled");
+        let res = self.linker.instantiate(&mut store, &module)
+            .and_then(|p| p.ensure_no_start(&mut store).map_err(Into::into))
+            .and_then(|i| i.get_typed_func::<(), ()>(&store, "call"))
+            .and_then(|f| f.call(&mut store, ()));
+        let used = fuel - store.get_fuel().unwrap_or(0);
+        let host = store.into_data();
+        *contracts = host.contracts;
+        res.map_err(|e| (format!("contract call failed: {e}"), used))?;
+        Ok((host.writes, Outcome { output: host.output, logs: host.logs }))
+    }
+}
+
+fn host_error(e: impl std::fmt::Display) -> wasmi::Error { wasmi::Error::new(e.to_string()) }
+
+/// Burns fuel for `bytes` bytes of host work, failing if the call can't afford it.
+fn charge(caller: &mut Caller<'_, Host>, bytes: usize) -> Result<(), wasmi::Error> {
+    let cost = (bytes as u64).saturating_mul(HOST_BYTE_FUEL);
+    let fuel = caller.get_fuel().map_err(host_error)?;
+    if cost > fuel { return Err(host_error("out of fuel")); }
+    caller.set_fuel(fuel - cost).map_err(host_error)
+}
+
+fn read(caller: &mut Caller<'_, Host>, ptr: i32, len: i32) -> Result<Vec<u8>, wasmi::Error> {
+    let len = len as u32 as usize;
+    // Charged before allocating, so fuel also bounds the buffer size.
+    charge(caller, len)?;
+    let mem = caller.get_export("memory").and_then(Extern::into_memory).ok_or_else(|| host_error("contract exports no memory"))?;
+    let mut buf = vec![0; len];
+    mem.read(&*caller, ptr as u32 as usize, &mut buf).map_err(host_error)?;
+    Ok(buf)
+}
+
+fn write(caller: &mut Caller<'_, Host>, ptr: i32, data: &[u8]) -> Result<(), wasmi::Error> {
+    charge(caller, data.len())?;
+    let mem = caller.get_export("memory").and_then(Extern::into_memory).ok_or_else(|| host_error("contract exports no memory"))?;
+    mem.write(&mut *caller, ptr as u32 as usize, data).map_err(host_error)
+}
+
+fn host_functions(engine: &Engine) -> Linker<Host> {
+    let mut l = Linker::new(engine);
+    l.func_wrap("env", "input_len", |caller: Caller<'_, Host>| caller.data().input.len() as i32).expect("unique host function");
+    l.func_wrap("env", "input_read", |mut caller: Caller<'_, Host>, ptr: i32| {
+        let input = std::mem::take(&mut caller.data_mut().input);
+        let res = write(&mut caller, ptr, &input);
+        caller.data_mut().input = input;
+        res
+    }).expect("unique host function");
+    l.func_wrap("env", "storage_read", |mut caller: Caller<'_, Host>, key_ptr: i32, key_len: i32, out_ptr: i32, out_cap: i32| -> Result<i32, wasmi::Error> {
+        let addr = caller.data().addr.clone();
+        let key = data_key(&addr, &read(&mut caller, key_ptr, key_len)?);
+        let host = caller.data();
+        let Some(value) = host.writes.get(&key).or_else(|| host.contracts.get(&key)).cloned() else { return Ok(-1) };
+        let n = value.len().min(out_cap.max(0) as usize);
+        write(&mut caller, out_ptr, &value[..n])?;
+        Ok(value.len() as i32)
+    }).expect("unique host function");
+    l.func_wrap("env", "storage_write", |mut caller: Caller<'_, Host>, key_ptr: i32, key_len: i32, val_ptr: i32, val_len: i32| -> Result<(), wasmi::Error> {
+        let addr = caller.data().addr.clone();
+        let key = data_key(&addr, &read(&mut caller, key_ptr, key_len)?);
+        let value = read(&mut caller, val_ptr, val_len)?;
+        caller.data_mut().writes.insert(key, value);
+        Ok(())
+    }).expect("unique host function");
+    l.func_wrap("env", "set_output", |mut caller: Caller<'_, Host>, ptr: i32, len: i32| -> Result<(), wasmi::Error> {
+        caller.data_mut().output = Some(read(&mut caller, ptr, len)?);
+        Ok(())
+    }).expect("unique host function");
+    l.func_wrap("env", "log", |mut caller: Caller<'_, Host>, ptr: i32, len: i32| -> Result<(), wasmi::Error> {
+        let msg = String::from_utf8_lossy(&read(&mut caller, ptr, len)?).into_owned();
+        caller.data_mut().logs.push(msg);
+        Ok(())
+    }).expect("unique host function");
+    l
+}
+
+impl Executor for WasmExecutor {
+    fn balance(&self, addr: &str) -> Amount { self.inner.balance(addr) }
+    fn asset_balance(&self, addr: &str, asset: &str) -> Amount { self.inner.asset_balance(addr, asset) }
+    fn token(&self, asset: &str) -> Option<TokenInfo> { self.inner.token(asset) }
+    fn account(&self, addr: &str) -> Option<AccountState> { self.inner.account(addr) }
+    fn locks(&self, addr: &str) -> Vec<PendingLock> { self.inner.locks(addr) }
+    fn nonce(&self, addr: &str) -> u64 { self.inner.nonce(addr) }
+    fn apply_batch_blocking(&self, batch: Batch, block_height: u64) -> Vec<Receipt> {
+        let _t = EXEC_LATENCY.start_timer();
+        *self.inner.last_height.write() = block_height;
+        // Contract state is locked before accounts everywhere, and held until the block is recorded.
+        let mut state = self.state.write();
+        let mut w = self.inner.accounts.write();
+        let mut undo = UndoLog::new();
+        let mut contracts = Contracts { base: state.clone(), writes: ContractState::new(), live: true };
+        let time_ms = batch.time_ms;
+        let Ok(()) = self.inner.release_locks(&mut Journal { accounts: &mut w, undo: &mut undo }, block_height, time_ms);
+        let receipts: Vec<Receipt> = batch.txs.iter().map(|tx| {
+            self.execute(tx, &mut Journal { accounts: &mut w, undo: &mut undo }, &mut contracts, block_height, time_ms)
+        }).collect();
+        let Ok(()) = pay_fees(&mut Journal { accounts: &mut w, undo: &mut undo }, &self.inner.fees, receipts.iter().map(|r| r.fee_paid), block_height);
+        let writes = contracts.writes;
+        drop(contracts.base);
+        let mut extra = WriteBatch::new();
+        for (k, v) in &writes { extra.put(k, v.clone()); }
+        self.inner.commit_block(&w, block_height, undo, extra, writes.iter().map(|(k, v)| leaf(k, Some(v))));
+        // Copies the state only if a simulation still holds the old one.
+        Arc::make_mut(&mut state).extend(writes);
+        receipts
+    }
+    /// Runs `txs` on copy-on-write views of the committed state, which stays untouched.
+    fn simulate(&self, txs: &[Tx]) -> Vec<Receipt> {
+        let state = self.state.read();
+        let accounts = self.inner.accounts.read();
+        let mut contracts = Contracts { base: state.clone(), ..Contracts::default() };
+        drop(state);
+        let (h, time_ms) = (self.inner.last_height() + 1, now_ms() as u64);
+        let mut v = Overlay { base: &accounts, writes: HashMap::new() };
+        let Ok(()) = self.inner.release_locks(&mut v, h, time_ms);
+        txs.iter().map(|tx| self.execute(tx, &mut v, &mut contracts, h, time_ms)).collect()
+    }
+    fn last_height(&self) -> u64 { self.inner.last_height() }
+    fn snapshot(&self) -> Vec<AccountState> { self.inner.snapshot() }
+    fn diff_since(&self, since: u64) -> Vec<AccountState> { self.inner.diff_since(since) }
+    /// Restores accounts only; contract state is left as it is.
+    fn restore(&self, replace: bool, items: Vec<AccountState>) {
+        let _state = self.state.write();
+        self.inner.restore(replace, items);
+    }
+    fn state_root(&self) -> smt::Hash { self.inner.state_root() }
+    fn prove(&self, addr: &str, height: u64) -> Option<StateProof> { self.inner.prove(addr, height) }
+}
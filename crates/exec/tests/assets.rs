@@ -0,0 +1,48 @@
+mod common;
+
+use common::{rejection, user, User};
+use exec::{BlockStmExecutor, Executor, SimpleExecutor, TokenInfo};
+use std::sync::Arc;
+use types::{asset_id, Amount, Receipt, Status, TokenMeta, TokenOp, Transfer, TxKind};
+
+/// Sends `amount` of `asset` (native if `None`), applying `token_op`.
+fn send(u: &mut User, ex: &dyn Executor, to: &str, amount: u64, asset: Option<&str>, token_op: Option<TokenOp>) -> Receipt {
+    let t = Transfer { asset: asset.map(str::to_string), token_op, ..u.transfer(TxKind::Transfer, to, amount, 0) };
+    u.submit(ex, t)
+}
+
+fn token_lifecycle(ex: &dyn Executor) {
+    let mut issuer = user("issuer", ex);
+    let mut holder = user("holder", ex);
+    let meta = TokenMeta { symbol: "USDX".into(), decimals: 6, max_supply: Amount(1_000) };
+    let id = asset_id(&issuer.addr, 0);
+    assert_eq!(send(&mut issuer, ex, &holder.addr, 600, None, Some(TokenOp::Create(meta))).status, Status::Committed);
+    assert_eq!(ex.token(&id), Some(TokenInfo { issuer: issuer.addr.clone(), symbol: "USDX".into(), decimals: 6, max_supply: Amount(1_000), supply: Amount(600) }));
+    assert_eq!((ex.asset_balance(&holder.addr, &id), ex.balance(&holder.addr)), (Amount(600), Amount(1_000)));
+
+    // Token transfers leave the native balance alone.
+    assert_eq!(send(&mut holder, ex, &issuer.addr, 100, Some(&id), None).status, Status::Committed);
+    assert_eq!((ex.asset_balance(&holder.addr, &id), ex.asset_balance(&issuer.addr, &id)), (Amount(500), Amount(100)));
+    assert!(rejection(&send(&mut holder, ex, &issuer.addr, 501, Some(&id), None)).starts_with("insufficient"));
+
+    assert_eq!(rejection(&send(&mut holder, ex, &issuer.addr, 1, Some(&id), Some(TokenOp::Mint))), format!("only the issuer of {id} can mint or burn it"));
+    assert_eq!(rejection(&send(&mut issuer, ex, &holder.addr, 401, Some(&id), Some(TokenOp::Mint))), format!("mint exceeds the supply cap of {id}"));
+    assert_eq!(send(&mut issuer, ex, &holder.addr, 400, Some(&id), Some(TokenOp::Mint)).status, Status::Committed);
+    assert_eq!(send(&mut issuer, ex, "", 100, Some(&id), Some(TokenOp::Burn)).status, Status::Committed);
+    assert_eq!(ex.token(&id).unwrap().supply, 900);
+    assert_eq!(ex.asset_balance(&issuer.addr, &id), 0);
+    assert!(ex.snapshot().iter().find(|a| a.addr == issuer.addr).unwrap().assets.is_empty());
+
+    assert!(rejection(&send(&mut issuer, ex, "", 0, Some("nope"), Some(TokenOp::Mint))).starts_with("unknown asset"));
+    let bad = TokenMeta { symbol: "usd x".into(), decimals: 6, max_supply: Amount(1) };
+    assert!(rejection(&send(&mut issuer, ex, "", 0, None, Some(TokenOp::Create(bad)))).starts_with("invalid token symbol"));
+    let capped = TokenMeta { symbol: "CAP".into(), decimals: 0, max_supply: Amount(1) };
+    assert!(rejection(&send(&mut issuer, ex, &holder.addr, 2, None, Some(TokenOp::Create(capped)))).starts_with("initial supply"));
+    assert_eq!(ex.balance(&issuer.addr), 1_000);
+}
+
+#[test]
+fn tokens_in_the_sequential_executor() { token_lifecycle(&SimpleExecutor::default()); }
+
+#[test]
+fn tokens_in_block_stm() { token_lifecycle(&BlockStmExecutor::new(Arc::new(SimpleExecutor::default()))); }
@@ -0,0 +1,104 @@
+mod common;
+
+use common::{keyed, sign, sorted, transfer, User};
+use exec::{AccountState, BlockStmExecutor, Executor, FeeSchedule, SimpleExecutor, TokenInfo, TRANSFER_GAS};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use std::sync::Arc;
+use types::{lock_id, Amount, AuthPolicy, Batch, Payment, TokenOp, Transfer, TxKind, Unlock};
+
+/// Token issued by the second user at genesis; every funded user starts with some.
+const TOKEN: &str = "token-1";
+
+fn users(n: usize) -> Vec<User> { (0..n).map(|i| keyed(&format!("stm-{i}"))).collect() }
+
+/// Few accounts and many txs so most of the batch conflicts; sprinkles in stale nonces,
+/// overdrafts, underpriced fees, self-transfers, forged signatures, token transfers, mints and
+/// burns that often come from someone other than the issuer, and the other transaction kinds:
+/// batch payments, account creation, governance mints, the odd key rotation or multisig policy,
+/// cosignatures, and time and hash locks that mature within a few heights or get claimed.
+fn random_batch(rng: &mut StdRng, us: &mut [User], len: usize, min_fee: u64) -> Batch {
+    let txs = (0..len).map(|_| {
+        let (f, t) = (rng.gen_range(0..us.len()), rng.gen_range(0..us.len()));
+        let amount = if rng.gen_bool(0.1) { 5_000 } else { rng.gen_range(0..400) };
+        let nonce = if rng.gen_bool(0.1) { us[f].nonce.saturating_sub(1) } else { us[f].nonce };
+        us[f].nonce = nonce + 1;
+        let sk = if rng.gen_bool(0.03) { &us[t].sk } else { &us[f].sk };
+        let to = if rng.gen_bool(0.05) { format!("fresh-{}", rng.gen::<u32>()) } else { us[t].addr.clone() };
+        let fee = if rng.gen_bool(0.1) { min_fee.saturating_sub(1) } else { min_fee + rng.gen_range(0..20) };
+        let (asset, token_op) = match rng.gen_range(0..10) {
+            0..=1 => (Some(TOKEN.to_string()), None),
+            2 => (Some(TOKEN.to_string()), Some(TokenOp::Mint)),
+            3 => (Some(TOKEN.to_string()), Some(TokenOp::Burn)),
+            _ => (None, None),
+        };
+        let kind = match rng.gen_range(0..20) {
+            0 => TxKind::BatchTransfer((0..rng.gen_range(0..4)).map(|_| Payment { to: us[rng.gen_range(0..us.len())].addr.clone(), amount: Amount(rng.gen_range(0..200)) }).collect()),
+            1 => TxKind::CreateAccount(crypto::public_key(&us[t].sk)),
+            2 => TxKind::Mint,
+            3 if rng.gen_bool(0.2) => TxKind::SetAccountKey(crypto::public_key(&us[t].sk)),
+            5 => TxKind::TimeLock(Unlock::Height(rng.gen_range(0..40))),
+            6 => TxKind::Htlc { hash: *blake3::hash(b"stm").as_bytes(), expiry: Unlock::Height(rng.gen_range(0..40)) },
+            7 => {
+                let lock = lock_id(&us[t].addr, us[t].nonce.saturating_sub(rng.gen_range(1..4)));
+                TxKind::Claim { lock, preimage: if rng.gen_bool(0.8) { b"stm".to_vec() } else { b"guess".to_vec() } }
+            }
+            4 if rng.gen_bool(0.2) => TxKind::SetAuthPolicy(AuthPolicy { keys: vec![crypto::public_key(&us[f].sk), crypto::public_key(&us[t].sk)], threshold: rng.gen_range(1..=2) }),
+            _ => TxKind::Transfer,
+        };
+        // Batches pay for every payment.
+        let fee = if let TxKind::BatchTransfer(ps) = &kind { fee + min_fee * ps.len().saturating_sub(1) as u64 } else { fee };
+        let body = Transfer { asset, token_op, ..transfer(&us[f].addr, kind, &to, amount, fee, nonce) };
+        let signers = if rng.gen_bool(0.1) { vec![sk, &us[t].sk] } else { vec![sk] };
+        sign(body, &signers)
+    }).collect();
+    Batch { id: 0, txs, time_ms: 0 }
+}
+
+/// Feeds the same random batches to both executors and compares receipts and full snapshots.
+fn differential(seed: u64, accounts: usize, heights: u64, max_len: usize, gas_price: u64) {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut us = users(accounts);
+    let min_fee = TRANSFER_GAS * gas_price;
+    // The first user is never funded, so it only ever sends rejected or zero-value transfers.
+    let bal = 2_000 + 40 * min_fee;
+    let mut genesis: Vec<AccountState> = us.iter().skip(1).map(|u| {
+        AccountState { addr: u.addr.clone(), ver: 1, bal: Amount(bal), nonce: 0, last_update_height: 0, assets: [(TOKEN.to_string(), Amount(1_000))].into(), ..Default::default() }
+    }).collect();
+    let token = TokenInfo { issuer: us[1].addr.clone(), symbol: "TOK".into(), decimals: 0, max_supply: Amount(1_000_000), supply: Amount(1_000 * (accounts as u64 - 1)) };
+    genesis.push(AccountState { addr: TOKEN.to_string(), ver: 1, token: Some(token), ..Default::default() });
+    // The treasury is one of the senders, so fee payouts conflict with its own transfers.
+    let fees = FeeSchedule { gas_price, treasury: Some(us[accounts - 1].addr.clone()) };
+    let governance = Some(us[1].addr.clone());
+    let serial = SimpleExecutor::default().with_fees(fees.clone()).with_governance(governance.clone());
+    let stm = BlockStmExecutor::new(Arc::new(SimpleExecutor::default().with_fees(fees).with_governance(governance)));
+    serial.restore(false, genesis.clone());
+    stm.restore(false, genesis);
+
+    for h in 1..=heights {
+        let len = rng.gen_range(1..max_len);
+        let batch = random_batch(&mut rng, &mut us, len, min_fee);
+        let want = serial.apply_batch_blocking(batch.clone(), h);
+        let got = stm.apply_batch_blocking(batch, h);
+        assert_eq!(want.len(), got.len());
+        for (w, g) in want.iter().zip(&got) {
+            assert_eq!(w.tx_id, g.tx_id);
+            assert_eq!(w.block_height, g.block_height);
+            assert_eq!(w.status, g.status, "seed {seed} height {h}");
+            assert_eq!(w.fee_paid, g.fee_paid, "seed {seed} height {h}");
+            assert_eq!(w.result, g.result, "seed {seed} height {h}");
+            assert_eq!(w.events, g.events, "seed {seed} height {h}");
+        }
+        assert_eq!(sorted(serial.snapshot()), sorted(stm.snapshot()), "seed {seed} height {h}");
+        assert_eq!(serial.state_root(), stm.state_root(), "seed {seed} height {h}");
+        // Resync the generator with the chain so the next batch keeps mostly-valid nonces.
+        for u in us.iter_mut() { u.nonce = serial.nonce(&u.addr); }
+    }
+}
+
+#[test]
+fn block_stm_matches_sequential_execution() { differential(0x5eed, 6, 40, 120, 0); }
+
+#[test]
+fn differential_fuzz_across_seeds() {
+    for seed in 0..16 { differential(seed, 2 + seed as usize % 5, 8, 60, seed % 3); }
+}
@@ -0,0 +1,73 @@
+//! Fixtures shared by the executor integration tests; each test file uses a subset.
+#![allow(dead_code)]
+
+use exec::{AccountState, Executor};
+use types::{Amount, Batch, Receipt, SignedTx, Status, Transfer, Tx, TxKind, DEFAULT_CHAIN_ID};
+
+pub struct User { pub sk: crypto::SecretKey, pub addr: String, pub nonce: u64 }
+
+/// An account derived from `seed`, funded with 1_000.
+pub fn user(seed: &str, ex: &dyn Executor) -> User { funded(seed, ex, 1_000) }
+
+pub fn funded(seed: &str, ex: &dyn Executor, balance: u64) -> User {
+    let u = keyed(seed);
+    ex.restore(false, vec![AccountState { addr: u.addr.clone(), ver: 1, bal: Amount(balance), ..Default::default() }]);
+    u
+}
+
+/// The key derived from `seed` and its address, without touching any executor.
+pub fn keyed(seed: &str) -> User {
+    let (sk, pk) = crypto::keypair_from_seed(seed.as_bytes());
+    User { sk, addr: crypto::address(&pk), nonce: 0 }
+}
+
+/// Transfer of the native coin with every optional field unset.
+pub fn transfer(from: &str, kind: TxKind, to: &str, amount: u64, fee: u64, nonce: u64) -> Transfer {
+    Transfer { from: from.to_string(), to: to.to_string(), amount: Amount(amount), fee: Amount(fee), nonce, kind, asset: None, token_op: None, payload: None, expiry: None }
+}
+
+/// Signs `t` with the first of `signers` and cosigns it with the rest.
+pub fn sign(t: Transfer, signers: &[&crypto::SecretKey]) -> Tx {
+    let mut signed = SignedTx::sign(t, signers[0], DEFAULT_CHAIN_ID);
+    for sk in &signers[1..] { signed = signed.cosign(sk, DEFAULT_CHAIN_ID); }
+    Tx::new(signed)
+}
+
+/// Snapshots in address order, for comparing executors.
+pub fn sorted(mut s: Vec<AccountState>) -> Vec<AccountState> { s.sort_by(|a, b| a.addr.cmp(&b.addr)); s }
+
+impl User {
+    /// Unsigned transfer at the account's next nonce.
+    pub fn transfer(&self, kind: TxKind, to: &str, amount: u64, fee: u64) -> Transfer { transfer(&self.addr, kind, to, amount, fee, self.nonce) }
+    /// Signs `t` and advances the nonce, for transactions batched into one block.
+    pub fn sign(&mut self, t: Transfer) -> Tx {
+        self.nonce += 1;
+        self.signed(t)
+    }
+    /// Signs `t` as it is, leaving the nonce alone.
+    pub fn signed(&self, t: Transfer) -> Tx { sign(t, &[&self.sk]) }
+    /// Signed transfer at `nonce`, whatever the account's next one is.
+    pub fn at(&self, nonce: u64, kind: TxKind, to: &str, amount: u64, fee: u64) -> Tx { self.signed(transfer(&self.addr, kind, to, amount, fee, nonce)) }
+    pub fn tx(&mut self, kind: TxKind, to: &str, amount: u64) -> Tx { let t = self.transfer(kind, to, amount, 0); self.sign(t) }
+    /// Runs `t` signed by `sk` as the next block; the nonce advances only if the block used it up.
+    pub fn send_signed(&mut self, ex: &dyn Executor, t: Transfer, sk: &crypto::SecretKey) -> Receipt {
+        let h = ex.last_height() + 1;
+        let (from, nonce) = (t.from.clone(), t.nonce);
+        let r = ex.apply_batch_blocking(Batch { id: h, txs: vec![sign(t, &[sk])], time_ms: 0 }, h).remove(0);
+        if ex.nonce(&from) > nonce { self.nonce += 1; }
+        r
+    }
+    pub fn submit(&mut self, ex: &dyn Executor, t: Transfer) -> Receipt { let sk = self.sk.clone(); self.send_signed(ex, t, &sk) }
+    pub fn send(&mut self, ex: &dyn Executor, kind: TxKind, to: &str, amount: u64, fee: u64) -> Receipt {
+        let t = self.transfer(kind, to, amount, fee);
+        self.submit(ex, t)
+    }
+}
+
+/// Runs `txs` as the next block at `time_ms`.
+pub fn block(ex: &dyn Executor, txs: Vec<Tx>, time_ms: u64) -> Vec<Receipt> {
+    let h = ex.last_height() + 1;
+    ex.apply_batch_blocking(Batch { id: h, txs, time_ms }, h)
+}
+
+pub fn rejection(r: &Receipt) -> String { match &r.status { Status::Rejected(e) => e.to_string(), Status::Committed => panic!("committed") } }
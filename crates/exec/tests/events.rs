@@ -0,0 +1,80 @@
+mod common;
+
+use common::{block, user};
+use exec::{BlockStmExecutor, EventCursor, EventIndex, Executor, IndexedEvent, SimpleExecutor};
+use std::sync::Arc;
+use storage::{InMemoryKv, Kv};
+use types::{asset_id, Amount, Event, Payment, TokenMeta, TokenOp, Transfer, TxKind};
+
+fn receipts_carry_events(ex: &dyn Executor) {
+    let mut a = user("events-a", ex);
+    let moved = |to: &str, amount| Event::Transferred { from: a.addr.clone(), to: to.to_string(), amount, asset: None };
+    let (first, second) = (moved("b", Amount(10)), moved("c", Amount(5)));
+    let payments = vec![Payment { to: "b".into(), amount: Amount(10) }, Payment { to: "c".into(), amount: Amount(5) }];
+    let token = asset_id(&a.addr, 1);
+    let txs = vec![
+        a.tx(TxKind::BatchTransfer(payments), "", 0),
+        a.sign(Transfer { token_op: Some(TokenOp::Create(TokenMeta { symbol: "EVT".into(), decimals: 0, max_supply: Amount(7) })), ..a.transfer(TxKind::Transfer, "b", 7, 0) }),
+        a.tx(TxKind::Transfer, "b", 5_000),
+    ];
+    let rs = block(ex, txs, 0);
+    assert_eq!(rs[0].events, vec![first, second]);
+    assert_eq!(rs[1].events, vec![Event::Minted { to: "b".into(), amount: Amount(7), asset: Some(token) }]);
+    assert_eq!(rs[2].events, vec![Event::Rejected { from: a.addr.clone(), reason_code: "insufficient_funds".into() }]);
+}
+
+#[test]
+fn events_in_the_sequential_executor() { receipts_carry_events(&SimpleExecutor::default()); }
+
+#[test]
+fn events_in_block_stm() { receipts_carry_events(&BlockStmExecutor::new(Arc::new(SimpleExecutor::default()))); }
+
+#[test]
+fn index_queries_by_account_and_height() {
+    let ex = SimpleExecutor::default();
+    let index = EventIndex::new(Arc::new(InMemoryKv::new()) as Arc<dyn Kv>);
+    let mut a = user("index-a", &ex);
+    let mut receipts = Vec::new();
+    for amount in 1..=3 {
+        let rs = block(&ex, vec![a.tx(TxKind::Transfer, "b", amount), a.tx(TxKind::Transfer, &a.addr.clone(), amount)], 0);
+        index.record(&rs).unwrap();
+        receipts.extend(rs);
+    }
+
+    let count = |account: &str, height, limit| index.query(account, EventCursor { height, ..EventCursor::default() }, limit).unwrap().events.len();
+    let for_b = index.query("b", EventCursor::default(), 10).unwrap();
+    assert_eq!(for_b.events.iter().map(|e| e.height).collect::<Vec<_>>(), vec![1, 2, 3]);
+    assert_eq!(for_b.events[0], IndexedEvent { height: 1, tx_id: receipts[0].tx_id, event: receipts[0].events[0].clone() });
+    assert_eq!(for_b.next, None);
+    // Self-transfers are indexed once for their only account.
+    assert_eq!(count(&a.addr, 0, 10), 6);
+    assert_eq!(count(&a.addr, 2, 10), 4);
+    assert_eq!(count(&a.addr, 2, 3), 3);
+    assert_eq!(count("b", 4, 10), 0);
+    assert_eq!(count("nobody", 0, 10), 0);
+
+    // Re-indexing a block doesn't duplicate its events.
+    index.record(&receipts[..2]).unwrap();
+    assert_eq!(count("b", 0, 10), 3);
+}
+
+#[test]
+fn pages_split_blocks_and_transactions() {
+    let ex = SimpleExecutor::default();
+    let index = EventIndex::new(Arc::new(InMemoryKv::new()) as Arc<dyn Kv>);
+    let mut a = user("paging-a", &ex);
+    // One tx with five payment events, then a block with two more.
+    let payments = (0..5).map(|i| Payment { to: format!("p{i}"), amount: Amount(1) }).collect();
+    index.record(&block(&ex, vec![a.tx(TxKind::BatchTransfer(payments), "", 0)], 0)).unwrap();
+    index.record(&block(&ex, vec![a.tx(TxKind::Transfer, "b", 1), a.tx(TxKind::Transfer, "c", 1)], 0)).unwrap();
+
+    let (mut from, mut pages) = (EventCursor::default(), Vec::new());
+    loop {
+        let page = index.query(&a.addr, from, 3).unwrap();
+        pages.push(page.events.iter().map(|e| e.height).collect::<Vec<_>>());
+        let Some(next) = page.next else { break };
+        from = next;
+    }
+    assert_eq!(pages, vec![vec![1, 1, 1], vec![1, 1, 2], vec![2]]);
+    assert_eq!(index.query(&a.addr, EventCursor::default(), 3).unwrap().next, Some(EventCursor { height: 1, tx: 0, event: 3 }));
+}
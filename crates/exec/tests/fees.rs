@@ -0,0 +1,51 @@
+mod common;
+
+use common::{block, funded, transfer};
+use exec::{Executor, FeeSchedule, SimpleExecutor, gas_used, min_fee, PAYLOAD_BYTE_GAS, TRANSFER_GAS};
+use types::{Amount, Rejection, Status, TxKind};
+
+#[test]
+fn gas_grows_with_payload() {
+    let mut t = transfer("a", TxKind::Transfer, "b", 1, 0, 0);
+    assert_eq!(gas_used(&t), TRANSFER_GAS);
+    t.payload = Some(vec![0; 10]);
+    assert_eq!(gas_used(&t), TRANSFER_GAS + 10 * PAYLOAD_BYTE_GAS);
+    assert_eq!(min_fee(&t, 3), 3 * gas_used(&t));
+}
+
+#[test]
+fn fees_are_charged_and_paid_to_the_treasury() {
+    let ex = SimpleExecutor::default().with_fees(FeeSchedule { gas_price: 2, treasury: Some("treasury".into()) });
+    let payer = funded("fee-payer", &ex, 10_000);
+    let from = payer.addr.clone();
+
+    let txs = vec![
+        payer.at(0, TxKind::Transfer, "bob", 100, 2_000),
+        payer.at(1, TxKind::Transfer, "bob", 100, 1_999),
+        payer.at(1, TxKind::Transfer, "bob", 100, 2_500),
+        payer.at(2, TxKind::Transfer, "bob", 5_000, 2_000),
+    ];
+    let receipts = block(&ex, txs, 0);
+    assert_eq!(receipts[0].status, Status::Committed);
+    assert_eq!(receipts[0].fee_paid, 2_000);
+    assert_eq!(receipts[1].status, Status::Rejected(Rejection::FeeTooLow { need: Amount(2_000), got: Amount(1_999) }));
+    assert_eq!(receipts[1].fee_paid, 0);
+    assert_eq!(receipts[2].fee_paid, 2_500);
+    // Amount plus fee exceeds the remaining 5_300.
+    assert!(matches!(&receipts[3].status, Status::Rejected(Rejection::InsufficientFunds { asset: None, .. })));
+
+    assert_eq!(ex.balance(&from), 10_000 - 2 * 100 - 4_500);
+    assert_eq!(ex.balance("bob"), 200);
+    assert_eq!(ex.balance("treasury"), 4_500);
+}
+
+#[test]
+fn fees_are_burned_without_a_treasury() {
+    let ex = SimpleExecutor::default().with_fees(FeeSchedule { gas_price: 1, treasury: None });
+    let burner = funded("fee-burner", &ex, 5_000);
+    let from = burner.addr.clone();
+    let receipts = block(&ex, vec![burner.at(0, TxKind::Transfer, "bob", 10, 1_000)], 0);
+    assert_eq!(receipts[0].fee_paid, 1_000);
+    assert_eq!(ex.balance(&from), 3_990);
+    assert_eq!(ex.balance(&from).0 + ex.balance("bob").0, 4_000);
+}
@@ -0,0 +1,99 @@
+mod common;
+
+use common::{block, rejection, user, User};
+use exec::{BlockStmExecutor, Executor, PendingLock, SimpleExecutor};
+use std::sync::Arc;
+use storage::{InMemoryKv, Kv};
+use types::{lock_id, Amount, Event, TxKind, TxResult, Unlock, DEFAULT_CHAIN_ID};
+
+fn executors() -> Vec<Box<dyn Executor>> {
+    vec![Box::new(SimpleExecutor::default()), Box::new(BlockStmExecutor::new(Arc::new(SimpleExecutor::default())))]
+}
+
+#[test]
+fn time_locks_pay_out_when_they_mature() {
+    for ex in executors() {
+        let ex = ex.as_ref();
+        let mut a = user("locks-a", ex);
+        let id = lock_id(&a.addr, 0);
+        let r = block(ex, vec![a.tx(TxKind::TimeLock(Unlock::Height(3)), "b", 300)], 0).remove(0);
+        assert_eq!(r.result, Some(TxResult::Locked { id: id.clone() }));
+        assert_eq!(r.events, vec![Event::Locked { id: id.clone(), from: a.addr.clone(), to: "b".into(), amount: Amount(300), asset: None }]);
+        assert_eq!((ex.balance(&a.addr), ex.balance("b")), (Amount(700), Amount(0)));
+        let pending = ex.locks("b");
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending, ex.locks(&a.addr));
+        assert_eq!(pending[0].id, id);
+
+        block(ex, Vec::new(), 0);
+        assert_eq!(ex.balance("b"), 0);
+        block(ex, Vec::new(), 0);
+        assert_eq!(ex.balance("b"), 300);
+        assert!(ex.locks("b").is_empty());
+
+        let at = 1_700_000_000_000;
+        block(ex, vec![a.tx(TxKind::TimeLock(Unlock::Time(at)), "c", 100)], at - 1);
+        block(ex, Vec::new(), at - 1);
+        assert_eq!(ex.balance("c"), 0);
+        block(ex, Vec::new(), at);
+        assert_eq!(ex.balance("c"), 100);
+
+        let late = block(ex, vec![a.tx(TxKind::TimeLock(Unlock::Height(1)), "c", 1)], 0).remove(0);
+        assert!(rejection(&late).starts_with("lock must mature after this block"));
+        assert_eq!(late.events, vec![Event::Rejected { from: a.addr.clone(), reason_code: "invalid_lock".into() }]);
+        a.nonce = ex.nonce(&a.addr);
+        assert!(rejection(&block(ex, vec![a.tx(TxKind::TimeLock(Unlock::Height(99)), "c", 5_000)], 0)[0]).starts_with("insufficient funds"));
+    }
+}
+
+#[test]
+fn hash_locks_release_on_the_preimage_or_refund() {
+    for ex in executors() {
+        let ex = ex.as_ref();
+        let (mut a, mut b) = (user("htlc-a", ex), user("htlc-b", ex));
+        let hash = *blake3::hash(b"secret").as_bytes();
+        let (swap, refund) = (lock_id(&a.addr, 0), lock_id(&a.addr, 1));
+        block(ex, vec![
+            a.tx(TxKind::Htlc { hash, expiry: Unlock::Height(10) }, &b.addr, 200),
+            a.tx(TxKind::Htlc { hash, expiry: Unlock::Height(3) }, &b.addr, 50),
+        ], 0);
+        assert_eq!(ex.balance(&a.addr), 750);
+        assert_eq!(ex.locks(&b.addr).iter().map(|p| p.id.clone()).collect::<Vec<_>>().len(), 2);
+
+        let claim = |b: &mut User, lock: &str, preimage: &[u8]| b.tx(TxKind::Claim { lock: lock.to_string(), preimage: preimage.to_vec() }, "", 0);
+        assert_eq!(rejection(&block(ex, vec![claim(&mut b, &swap, b"guess")], 0)[0]), format!("wrong preimage for lock {swap}"));
+        b.nonce = ex.nonce(&b.addr);
+        assert_eq!(rejection(&block(ex, vec![claim(&mut b, "nope", b"secret")], 0)[0]), "unknown lock nope");
+        b.nonce = ex.nonce(&b.addr);
+
+        let r = block(ex, vec![claim(&mut b, &swap, b"secret")], 0).remove(0);
+        assert_eq!(r.result, Some(TxResult::Claimed { id: swap.clone(), to: b.addr.clone(), amount: Amount(200), asset: None }));
+        assert_eq!(r.events, vec![Event::Released { id: swap.clone(), to: b.addr.clone(), amount: Amount(200), asset: None }]);
+        assert_eq!(ex.balance(&b.addr), 1_200);
+        assert_eq!(rejection(&block(ex, vec![claim(&mut b, &swap, b"secret")], 0)[0]), format!("unknown lock {swap}"));
+
+        // Unclaimed at height 3, the second lock went back to its funder; claiming it is too late.
+        b.nonce = ex.nonce(&b.addr);
+        assert_eq!(rejection(&block(ex, vec![claim(&mut b, &refund, b"secret")], 0)[0]), format!("unknown lock {refund}"));
+        assert_eq!((ex.balance(&a.addr), ex.balance(&b.addr)), (Amount(800), Amount(1_200)));
+        assert!(ex.locks(&a.addr).is_empty());
+    }
+}
+
+#[test]
+fn pending_locks_survive_a_restart() {
+    let kv: Arc<dyn Kv> = Arc::new(InMemoryKv::new());
+    let (sk, pk) = crypto::keypair_from_seed(b"locks-restart");
+    let alice = crypto::address(&pk);
+    let genesis = vec![(alice.clone(), Amount(1_000))];
+    let mut a = User { sk, addr: alice.clone(), nonce: 0 };
+    let pending: Vec<PendingLock> = {
+        let ex = SimpleExecutor::open(DEFAULT_CHAIN_ID, kv.clone(), &genesis, [1; 32]).unwrap();
+        block(&ex, vec![a.tx(TxKind::TimeLock(Unlock::Height(2)), "bob", 400)], 0);
+        ex.locks(&alice)
+    };
+    let ex = SimpleExecutor::open(DEFAULT_CHAIN_ID, kv, &genesis, [1; 32]).unwrap();
+    assert_eq!(ex.locks("bob"), pending);
+    block(&ex, Vec::new(), 0);
+    assert_eq!((ex.balance(&alice), ex.balance("bob")), (Amount(600), Amount(400)));
+}
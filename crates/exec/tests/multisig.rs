@@ -0,0 +1,57 @@
+mod common;
+
+use common::{block, rejection, sign, transfer};
+use exec::{AccountState, BlockStmExecutor, Executor, SimpleExecutor};
+use std::sync::Arc;
+use types::{Amount, AuthPolicy, Receipt, Status, TxKind, TxResult, DEFAULT_CHAIN_ID};
+
+const TREASURY: &str = "treasury";
+
+fn keys(n: usize) -> Vec<crypto::SecretKey> { (0..n).map(|i| crypto::keypair_from_seed(format!("signer-{i}").as_bytes()).0).collect() }
+
+fn policy(sks: &[crypto::SecretKey], threshold: u32) -> AuthPolicy { AuthPolicy { keys: sks.iter().map(crypto::public_key).collect(), threshold } }
+
+/// Sends from the treasury with `nonce`, signed by every key in `signers`.
+fn send(ex: &dyn Executor, nonce: u64, kind: TxKind, signers: &[&crypto::SecretKey]) -> Receipt {
+    block(ex, vec![sign(transfer(TREASURY, kind, "payee", 10, 0, nonce), signers)], 0).remove(0)
+}
+
+fn threshold_is_enforced(ex: &dyn Executor) {
+    let sks = keys(4);
+    ex.restore(false, vec![AccountState { addr: TREASURY.into(), ver: 1, bal: Amount(1_000), auth: Some(policy(&sks[..3], 2)), ..Default::default() }]);
+
+    assert_eq!(rejection(&send(ex, 0, TxKind::Transfer, &[&sks[0]])), "bad signature: 1 of 2 required signatures for treasury");
+    // Signing twice with one key, or adding a key outside the policy, doesn't count.
+    assert_eq!(rejection(&send(ex, 0, TxKind::Transfer, &[&sks[0], &sks[0], &sks[3]])), "bad signature: 1 of 2 required signatures for treasury");
+    assert_eq!(send(ex, 0, TxKind::Transfer, &[&sks[2], &sks[0]]).status, Status::Committed);
+    assert_eq!(ex.balance("payee"), 10);
+
+    // Key set changes go through the current policy, then the new one applies.
+    let next = policy(&sks[1..], 3);
+    assert!(rejection(&send(ex, 1, TxKind::SetAuthPolicy(next.clone()), &[&sks[3]])).starts_with("bad signature"));
+    assert_eq!(send(ex, 1, TxKind::SetAuthPolicy(next.clone()), &[&sks[0], &sks[1]]).result, Some(TxResult::SetAuthPolicy));
+    assert_eq!(ex.account(TREASURY).unwrap().auth, Some(next));
+    assert!(rejection(&send(ex, 2, TxKind::Transfer, &[&sks[0], &sks[1]])).starts_with("bad signature: 1 of 3"));
+    assert_eq!(send(ex, 2, TxKind::Transfer, &[&sks[1], &sks[2], &sks[3]]).status, Status::Committed);
+
+    let all: Vec<&crypto::SecretKey> = sks[1..].iter().collect();
+    assert_eq!(rejection(&send(ex, 3, TxKind::SetAuthPolicy(policy(&sks, 5)), &all)), "invalid auth policy: threshold 5 of 4 keys");
+    let dup = AuthPolicy { keys: vec![crypto::public_key(&sks[0]); 2], threshold: 1 };
+    assert_eq!(rejection(&send(ex, 3, TxKind::SetAuthPolicy(dup), &all)), "invalid auth policy: duplicate key");
+    assert_eq!(ex.nonce(TREASURY), 3);
+}
+
+#[test]
+fn multisig_in_the_sequential_executor() { threshold_is_enforced(&SimpleExecutor::default()); }
+
+#[test]
+fn multisig_in_block_stm() { threshold_is_enforced(&BlockStmExecutor::new(Arc::new(SimpleExecutor::default()))); }
+
+#[test]
+fn forged_cosignatures_fail_verification() {
+    let sks = keys(2);
+    let mut signed = sign(transfer(TREASURY, TxKind::Transfer, "payee", 1, 0, 0), &[&sks[0], &sks[1]]).signed;
+    assert!(signed.verify(DEFAULT_CHAIN_ID).is_ok());
+    signed.cosigs[0].0 = crypto::public_key(&keys(3)[2]);
+    assert!(signed.verify(DEFAULT_CHAIN_ID).is_err());
+}
@@ -0,0 +1,37 @@
+mod common;
+
+use common::{block, user};
+use exec::{BlockStmExecutor, Executor, SimpleExecutor};
+use std::sync::Arc;
+use types::{Rejection, Status, TxKind};
+
+fn bad_nonce(account: &str, expected: u64, got: u64) -> Status { Status::Rejected(Rejection::BadNonce { account: account.to_string(), expected, got }) }
+
+fn replays_and_gaps_are_rejected(ex: &dyn Executor) {
+    let mut a = user("nonce-a", ex);
+    let first = a.tx(TxKind::Transfer, "b", 10);
+    assert_eq!(block(ex, vec![first.clone()], 0)[0].status, Status::Committed);
+    assert_eq!(ex.nonce(&a.addr), 1);
+
+    // The same signed tx again, alone and in a later block.
+    assert_eq!(block(ex, vec![first.clone()], 0)[0].status, bad_nonce(&a.addr, 1, 0));
+    // Skipping nonce 2 leaves 3 unusable until 2 lands.
+    a.nonce = 3;
+    let gap = a.tx(TxKind::Transfer, "b", 10);
+    assert_eq!(block(ex, vec![gap.clone()], 0)[0].status, bad_nonce(&a.addr, 1, 3));
+    assert_eq!((ex.nonce(&a.addr), ex.balance("b").0), (1, 10));
+
+    // Within one block nonces must run in order; the replay and the gap fail, the rest commit.
+    a.nonce = 1;
+    let (n1, n2) = (a.tx(TxKind::Transfer, "b", 1), a.tx(TxKind::Transfer, "b", 1));
+    let rs = block(ex, vec![n1, first, n2.clone(), n2, gap], 0);
+    let statuses: Vec<_> = rs.into_iter().map(|r| r.status).collect();
+    assert_eq!(statuses, vec![Status::Committed, bad_nonce(&a.addr, 2, 0), Status::Committed, bad_nonce(&a.addr, 3, 2), Status::Committed]);
+    assert_eq!((ex.nonce(&a.addr), ex.balance("b").0), (4, 22));
+}
+
+#[test]
+fn nonces_in_the_sequential_executor() { replays_and_gaps_are_rejected(&SimpleExecutor::default()); }
+
+#[test]
+fn nonces_in_block_stm() { replays_and_gaps_are_rejected(&BlockStmExecutor::new(Arc::new(SimpleExecutor::default()))); }
@@ -0,0 +1,42 @@
+mod common;
+
+use common::{block, keyed, sorted};
+use exec::{Executor, SimpleExecutor};
+use std::sync::Arc;
+use storage::{FileKv, Kv};
+use types::{Amount, TxKind, DEFAULT_CHAIN_ID};
+
+const GENESIS: [u8; 32] = [7; 32];
+
+#[test]
+fn resumes_from_stored_height() {
+    let dir = std::env::temp_dir().join(format!("exec-persist-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let open = || -> Arc<dyn Kv> { Arc::new(FileKv::open(dir.join("state.log")).unwrap()) };
+    let sender = keyed("alice");
+    let alice = sender.addr.clone();
+    let genesis = vec![(alice.clone(), Amount(1_000_000))];
+
+    let (snapshot, root) = {
+        let ex = SimpleExecutor::open(DEFAULT_CHAIN_ID, open(), &genesis, GENESIS).unwrap();
+        for h in 1..=3 { block(&ex, vec![sender.at(h - 1, TxKind::Transfer, &format!("bob-{h}"), 100, 0)], 0); }
+        (sorted(ex.snapshot()), ex.state_root())
+    };
+
+    let ex = SimpleExecutor::open(DEFAULT_CHAIN_ID, open(), &genesis, GENESIS).unwrap();
+    assert_eq!(ex.last_height(), 3);
+    assert_eq!(ex.nonce(&alice), 3);
+    assert_eq!(ex.balance("bob-2"), 100);
+    assert_eq!(sorted(ex.snapshot()), snapshot);
+    assert_eq!(ex.state_root(), root);
+}
+
+#[test]
+fn rejects_store_from_another_genesis() {
+    let kv: Arc<dyn Kv> = Arc::new(storage::InMemoryKv::new());
+    let genesis = vec![("carol".to_string(), Amount(5))];
+    let ex = SimpleExecutor::open(DEFAULT_CHAIN_ID, kv.clone(), &genesis, GENESIS).unwrap();
+    assert_eq!(ex.balance("carol"), 5);
+    assert!(SimpleExecutor::open(DEFAULT_CHAIN_ID, kv.clone(), &genesis, [8; 32]).is_err());
+    assert!(SimpleExecutor::open(DEFAULT_CHAIN_ID, kv, &[], GENESIS).is_ok_and(|ex| ex.balance("carol") == 5));
+}
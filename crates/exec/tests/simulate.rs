@@ -0,0 +1,47 @@
+mod common;
+
+use common::{block, funded, keyed, sorted};
+use exec::{BlockStmExecutor, Executor, FeeSchedule, SimpleExecutor, TRANSFER_GAS};
+use std::sync::Arc;
+use types::{Amount, Rejection, Status, TxKind, Unlock};
+
+fn simulation_leaves_state_alone(ex: &dyn Executor) {
+    let sender = funded("simulate", ex, 10_000);
+    let from = sender.addr.clone();
+    let (before, root) = (sorted(ex.snapshot()), ex.state_root());
+
+    let rs = ex.simulate(&[
+        sender.at(0, TxKind::Transfer, "bob", 1_000, TRANSFER_GAS),
+        sender.at(1, TxKind::Transfer, "bob", 1_000, TRANSFER_GAS),
+        sender.at(1, TxKind::Transfer, "bob", 1_000, TRANSFER_GAS),
+        sender.at(2, TxKind::Transfer, "bob", 1_000, TRANSFER_GAS - 1),
+        sender.at(2, TxKind::Transfer, "bob", 9_000, TRANSFER_GAS),
+    ]);
+    // Later transactions see the writes of earlier ones.
+    assert_eq!((rs[0].status.clone(), rs[1].status.clone()), (Status::Committed, Status::Committed));
+    assert_eq!(rs[2].status, Status::Rejected(Rejection::BadNonce { account: from.clone(), expected: 2, got: 1 }));
+    assert_eq!(rs[3].status, Status::Rejected(Rejection::FeeTooLow { need: Amount(TRANSFER_GAS), got: Amount(TRANSFER_GAS - 1) }));
+    assert!(matches!(&rs[4].status, Status::Rejected(Rejection::InsufficientFunds { .. })));
+    assert!(rs.iter().all(|r| r.block_height == ex.last_height() + 1));
+
+    assert_eq!(sorted(ex.snapshot()), before);
+    assert_eq!(ex.state_root(), root);
+    assert_eq!((ex.nonce(&from), ex.balance("bob")), (0, Amount(0)));
+
+    // A lock maturing in the next block is released before the simulated transactions run.
+    let bob = keyed("bob");
+    let h = ex.last_height() + 1;
+    block(ex, vec![sender.at(0, TxKind::TimeLock(Unlock::Height(h + 1)), &bob.addr, 5_000, TRANSFER_GAS)], 0);
+    let pending = ex.locks(&bob.addr);
+    let spend = ex.simulate(&[bob.at(0, TxKind::Transfer, "carol", 1_000, TRANSFER_GAS)]);
+    assert_eq!(spend[0].status, Status::Committed);
+    assert_eq!((ex.locks(&bob.addr), ex.balance(&bob.addr)), (pending, Amount(0)));
+}
+
+fn executor() -> SimpleExecutor { SimpleExecutor::default().with_fees(FeeSchedule { gas_price: 1, treasury: None }) }
+
+#[test]
+fn simulate_in_the_sequential_executor() { simulation_leaves_state_alone(&executor()); }
+
+#[test]
+fn simulate_in_block_stm() { simulation_leaves_state_alone(&BlockStmExecutor::new(Arc::new(executor()))); }
@@ -0,0 +1,72 @@
+use exec::smt::{Hash, SparseMerkleTree, EMPTY};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+fn random_tree(rng: &mut StdRng, n: usize) -> (SparseMerkleTree, Vec<(Hash, Hash)>) {
+    let mut t = SparseMerkleTree::new();
+    let kvs: Vec<_> = (0..n).map(|_| (rng.gen::<[u8; 32]>(), rng.gen::<[u8; 32]>())).collect();
+    for (k, v) in &kvs { t.insert(*k, *v); }
+    (t, kvs)
+}
+
+#[test]
+fn root_is_independent_of_insertion_order() {
+    let mut rng = StdRng::seed_from_u64(7);
+    let (t, mut kvs) = random_tree(&mut rng, 50);
+    kvs.reverse();
+    let mut u = SparseMerkleTree::new();
+    for (k, v) in &kvs { u.insert(*k, *v); }
+    assert_eq!(t.root(), u.root());
+    assert_eq!(SparseMerkleTree::new().root(), EMPTY);
+}
+
+#[test]
+fn proofs_of_inclusion_and_absence() {
+    let mut rng = StdRng::seed_from_u64(8);
+    for n in [0, 1, 2, 3, 17, 100] {
+        let (t, kvs) = random_tree(&mut rng, n);
+        let root = t.root();
+        for (k, v) in &kvs {
+            let p = t.prove(k);
+            assert!(p.verify(&root, k, Some(v)));
+            assert!(!p.verify(&root, k, Some(&[9; 32])));
+            assert!(!p.verify(&root, k, None));
+        }
+        for _ in 0..20 {
+            let k: [u8; 32] = rng.gen();
+            let p = t.prove(&k);
+            assert!(p.verify(&root, &k, None));
+            assert!(!p.verify(&root, &k, Some(&[9; 32])));
+        }
+    }
+}
+
+#[test]
+fn proofs_do_not_verify_against_other_roots() {
+    let mut rng = StdRng::seed_from_u64(9);
+    let (mut t, kvs) = random_tree(&mut rng, 20);
+    let (k, v) = kvs[3];
+    let p = t.prove(&k);
+    t.insert(k, [1; 32]);
+    assert!(!p.verify(&t.root(), &k, Some(&v)));
+    assert!(t.prove(&k).verify(&t.root(), &k, Some(&[1; 32])));
+}
+
+#[test]
+fn updates_and_removals_match_a_rebuild() {
+    let mut rng = StdRng::seed_from_u64(10);
+    let (mut t, mut kvs) = random_tree(&mut rng, 60);
+    let (before, before_root) = (t.clone(), t.root());
+    for (k, v) in kvs.iter_mut().step_by(3) { *v = rng.gen(); t.insert(*k, *v); }
+    let removed: Vec<Hash> = kvs.iter().skip(1).step_by(2).map(|(k, _)| *k).collect();
+    for k in &removed { t.remove(k); }
+    t.remove(&[7; 32]);
+    kvs.retain(|(k, _)| !removed.contains(k));
+
+    let mut rebuilt = SparseMerkleTree::new();
+    for (k, v) in &kvs { rebuilt.insert(*k, *v); }
+    assert_eq!((t.len(), t.root()), (rebuilt.len(), rebuilt.root()));
+    for (k, v) in &kvs { assert!(t.prove(k).verify(&t.root(), k, Some(v))); }
+    for k in &removed { assert!(t.prove(k).verify(&t.root(), k, None)); }
+    // Earlier versions are untouched by later updates.
+    assert_eq!((before.len(), before.root()), (60, before_root));
+}
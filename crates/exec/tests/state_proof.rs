@@ -0,0 +1,44 @@
+mod common;
+
+use common::{block, funded, keyed};
+use exec::{BlockStmExecutor, Executor, SimpleExecutor, STATE_HISTORY};
+use std::sync::Arc;
+use types::TxKind;
+
+fn check_history(ex: &dyn Executor) {
+    let sender = funded("proof-sender", ex, 1_000_000);
+    let from = sender.addr.clone();
+    let mut roots = vec![ex.state_root()];
+    let heights = STATE_HISTORY as u64 + 5;
+    for h in 1..=heights {
+        block(ex, vec![sender.at(h - 1, TxKind::Transfer, &format!("to-{}", h % 3), 10, 0)], 0);
+        roots.push(ex.state_root());
+    }
+    for h in heights - STATE_HISTORY as u64..=heights {
+        let p = ex.prove(&from, h).expect("retained height");
+        assert_eq!(p.root, roots[h as usize]);
+        assert!(p.verify(&from));
+        assert_eq!(p.account.as_ref().map(|a| a.nonce), Some(h));
+        assert!(!p.verify("someone-else"));
+        let absent = ex.prove("never-seen", h).unwrap();
+        assert!(absent.account.is_none() && absent.verify("never-seen"));
+    }
+    assert!(ex.prove(&from, heights - STATE_HISTORY as u64 - 1).is_none());
+    assert!(ex.prove(&from, heights + 1).is_none());
+}
+
+#[test]
+fn simple_executor_proves_recent_heights() { check_history(&SimpleExecutor::default()); }
+
+#[test]
+fn block_stm_proves_recent_heights() { check_history(&BlockStmExecutor::new(Arc::new(SimpleExecutor::default()))); }
+
+#[test]
+fn tampered_account_fails_verification() {
+    let ex = SimpleExecutor::default();
+    let alice = keyed("alice").addr;
+    let mut p = ex.prove(&alice, 0).unwrap();
+    assert!(p.verify(&alice));
+    p.account.as_mut().unwrap().bal.0 += 1;
+    assert!(!p.verify(&alice));
+}
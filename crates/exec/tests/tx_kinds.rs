@@ -0,0 +1,135 @@
+mod common;
+
+use common::{funded, rejection, User};
+use exec::{AccountState, BlockStmExecutor, Executor, FeeSchedule, SimpleExecutor, TRANSFER_GAS};
+use std::sync::Arc;
+use types::{account_address, Amount, AuthPolicy, Event, Batch, Payment, Rejection, Status, Transfer, TxKind, TxResult, Unlock};
+
+fn executors(governance: &str) -> Vec<Box<dyn Executor>> {
+    let simple = || SimpleExecutor::default().with_fees(FeeSchedule { gas_price: 1, treasury: None }).with_governance(Some(governance.to_string()));
+    vec![Box::new(simple()), Box::new(BlockStmExecutor::new(Arc::new(simple())))]
+}
+
+#[test]
+fn batch_transfer_pays_every_recipient() {
+    for ex in executors("gov") {
+        let ex = ex.as_ref();
+        let mut payer = funded("payroll", ex, 100_000);
+        let payments: Vec<Payment> = ["a", "b", "a"].iter().map(|to| Payment { to: to.to_string(), amount: Amount(100) }).collect();
+        let fee = 3 * TRANSFER_GAS;
+        let low = payer.send(ex, TxKind::BatchTransfer(payments.clone()), "", 0, fee - 1);
+        assert_eq!(rejection(&low), format!("fee too low: need {fee}, got {}", fee - 1));
+
+        let r = payer.send(ex, TxKind::BatchTransfer(payments), "", 0, fee);
+        assert_eq!(r.result, Some(TxResult::BatchTransfer { recipients: 3, total: Amount(300) }));
+        assert_eq!((ex.balance("a"), ex.balance("b")), (Amount(200), Amount(100)));
+        assert_eq!(ex.balance(&payer.addr), 100_000 - 300 - fee);
+
+        assert_eq!(rejection(&payer.send(ex, TxKind::BatchTransfer(Vec::new()), "", 0, TRANSFER_GAS)), "batch transfer has no payments");
+        let overdraft = vec![Payment { to: "a".into(), amount: Amount(100_000) }];
+        assert!(rejection(&payer.send(ex, TxKind::BatchTransfer(overdraft), "", 0, TRANSFER_GAS)).starts_with("insufficient funds"));
+        assert_eq!(ex.balance("a"), 200);
+    }
+}
+
+#[test]
+fn created_accounts_are_controlled_by_their_key() {
+    for ex in executors("gov") {
+        let ex = ex.as_ref();
+        let mut creator = funded("creator", ex, 100_000);
+        let (owner_sk, owner_pk) = crypto::keypair_from_seed(b"owner");
+        let addr = account_address(&creator.addr, 0);
+        let r = creator.send(ex, TxKind::CreateAccount(owner_pk.clone()), "", 5_000, TRANSFER_GAS);
+        assert_eq!(r.result, Some(TxResult::CreateAccount { addr: addr.clone() }));
+        let state = ex.account(&addr).unwrap();
+        assert_eq!((state.bal, state.auth), (Amount(5_000), Some(AuthPolicy::single(owner_pk))));
+
+        let mut created = User { sk: owner_sk, addr: addr.clone(), nonce: 0 };
+        assert_eq!(created.send(ex, TxKind::Transfer, "c", 1_000, TRANSFER_GAS).result, Some(TxResult::Transfer));
+        assert_eq!((ex.balance("c"), ex.balance(&addr)), (Amount(1_000), Amount(4_000 - TRANSFER_GAS)));
+    }
+}
+
+#[test]
+fn dust_sent_ahead_cannot_block_account_creation() {
+    for ex in executors("gov") {
+        let ex = ex.as_ref();
+        let mut creator = funded("creator", ex, 100_000);
+        let mut griefer = funded("griefer", ex, 100_000);
+        let (_, owner_pk) = crypto::keypair_from_seed(b"owner");
+        let addr = account_address(&creator.addr, 0);
+        assert_eq!(griefer.send(ex, TxKind::Transfer, &addr, 1, TRANSFER_GAS).status, Status::Committed);
+
+        let r = creator.send(ex, TxKind::CreateAccount(owner_pk.clone()), "", 5_000, TRANSFER_GAS);
+        assert_eq!(r.result, Some(TxResult::CreateAccount { addr: addr.clone() }));
+        let state = ex.account(&addr).unwrap();
+        assert_eq!((state.bal, state.auth), (Amount(5_001), Some(AuthPolicy::single(owner_pk))));
+    }
+}
+
+#[test]
+fn rotated_keys_replace_the_old_one() {
+    for ex in executors("gov") {
+        let ex = ex.as_ref();
+        let mut u = funded("rotating", ex, 100_000);
+        let old = u.sk.clone();
+        let (new_sk, new_pk) = crypto::keypair_from_seed(b"rotated");
+        assert_eq!(u.send(ex, TxKind::SetAccountKey(new_pk.clone()), "", 0, TRANSFER_GAS).result, Some(TxResult::SetAccountKey));
+        assert_eq!(ex.account(&u.addr).unwrap().auth, Some(AuthPolicy::single(new_pk.clone())));
+
+        let stale = u.send_signed(ex, u.transfer(TxKind::Transfer, "d", 1, TRANSFER_GAS), &old);
+        assert_eq!(rejection(&stale), format!("bad signature: 0 of 1 required signatures for {}", u.addr));
+        assert_eq!(u.send_signed(ex, u.transfer(TxKind::Transfer, "d", 1, TRANSFER_GAS), &new_sk).status, Status::Committed);
+        assert_eq!(ex.balance("d"), 1);
+    }
+}
+
+#[test]
+fn only_governance_mints() {
+    let (_sk, gov_pk) = crypto::keypair_from_seed(b"governance");
+    for ex in executors(&crypto::address(&gov_pk)) {
+        let ex = ex.as_ref();
+        let mut gov = funded("governance", ex, 100_000);
+        let mut other = funded("someone", ex, 100_000);
+        assert_eq!(rejection(&other.send(ex, TxKind::Mint, "e", 1_000, TRANSFER_GAS)), "only the governance account can mint");
+        let r = gov.send(ex, TxKind::Mint, "e", 1_000, TRANSFER_GAS);
+        assert_eq!(r.result, Some(TxResult::Mint { balance: Amount(1_000) }));
+        assert_eq!((ex.balance("e"), ex.balance(&gov.addr)), (Amount(1_000), Amount(100_000 - TRANSFER_GAS)));
+        assert!(rejection(&gov.send(ex, TxKind::Mint, "e", u64::MAX, TRANSFER_GAS)).starts_with("amount overflows the balance of e"));
+    }
+}
+
+#[test]
+fn overflowing_credits_write_nothing() {
+    for ex in executors("gov") {
+        let ex = ex.as_ref();
+        let mut payer = funded("overflow", ex, 100_000);
+        ex.restore(false, vec![AccountState { addr: "whale".into(), ver: 1, bal: Amount::MAX, ..Default::default() }]);
+        let payments = vec![Payment { to: "a".into(), amount: Amount(100) }, Payment { to: "whale".into(), amount: Amount(1) }];
+        let r = payer.send(ex, TxKind::BatchTransfer(payments), "", 0, 2 * TRANSFER_GAS);
+        assert_eq!(rejection(&r), "amount overflows the balance of whale");
+        assert_eq!(r.events, vec![Event::Rejected { from: payer.addr.clone(), reason_code: "overflow".into() }]);
+        // The first payment was staged, not applied.
+        assert_eq!((ex.balance("a"), ex.balance(&payer.addr), ex.balance("whale")), (Amount(0), Amount(100_000), Amount::MAX));
+        assert_eq!(ex.nonce(&payer.addr), 0);
+    }
+}
+
+#[test]
+fn transfers_included_after_their_expiry_are_rejected() {
+    for ex in executors("gov") {
+        let ex = ex.as_ref();
+        let alice = funded("late", ex, 100_000);
+        let send = |nonce, expiry, h, time_ms| {
+            let t = Transfer { nonce, expiry: Some(expiry), ..alice.transfer(TxKind::Transfer, "bob", 10, TRANSFER_GAS) };
+            ex.apply_batch_blocking(Batch { id: h, txs: vec![alice.signed(t)], time_ms }, h).remove(0).status
+        };
+        assert_eq!(send(0, Unlock::Height(1), 2, 0), Status::Rejected(Rejection::Expired { expiry: Unlock::Height(1) }));
+        assert_eq!(send(0, Unlock::Time(500), 3, 501), Status::Rejected(Rejection::Expired { expiry: Unlock::Time(500) }));
+        assert_eq!((ex.balance("bob"), ex.nonce(&alice.addr)), (Amount(0), 0));
+        // The expiry is the last height or time the transfer may still be included at.
+        assert_eq!(send(0, Unlock::Height(4), 4, 0), Status::Committed);
+        assert_eq!(send(1, Unlock::Time(500), 5, 500), Status::Committed);
+        assert_eq!(ex.balance("bob"), 20);
+    }
+}
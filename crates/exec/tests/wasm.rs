@@ -0,0 +1,180 @@
+#![cfg(feature = "wasm")]
+
+mod common;
+
+use common::{funded, rejection, User};
+use exec::{contract_address, Executor, FeeSchedule, SimpleExecutor, WasmExecutor};
+use std::sync::Arc;
+use storage::{InMemoryKv, Kv};
+use types::{Event, Receipt, Rejection, Status, Transfer, TxKind, DEFAULT_CHAIN_ID};
+
+/// Counter contract. The first input byte picks the mode: 0 increments and returns the count,
+/// 1 increments and then traps, 2 spins forever.
+const COUNTER: &str = r#"
+(module
+  (import "env" "storage_read" (func $sread (param i32 i32 i32 i32) (result i32)))
+  (import "env" "storage_write" (func $swrite (param i32 i32 i32 i32)))
+  (import "env" "input_read" (func $iread (param i32)))
+  (import "env" "set_output" (func $out (param i32 i32)))
+  (import "env" "log" (func $log (param i32 i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "count")
+  (data (i32.const 16) "incremented")
+  (func (export "call")
+    (call $iread (i32.const 64))
+    (if (i32.eq (i32.load8_u (i32.const 64)) (i32.const 2)) (then (loop $spin (br $spin))))
+    (drop (call $sread (i32.const 0) (i32.const 5) (i32.const 32) (i32.const 8)))
+    (i64.store (i32.const 32) (i64.add (i64.load (i32.const 32)) (i64.const 1)))
+    (call $swrite (i32.const 0) (i32.const 5) (i32.const 32) (i32.const 8))
+    (if (i32.eq (i32.load8_u (i32.const 64)) (i32.const 1)) (then unreachable))
+    (call $out (i32.const 32) (i32.const 8))
+    (call $log (i32.const 16) (i32.const 11))))
+"#;
+
+/// Sends 5 with `payload`, which deploys a contract or calls the one at `to`.
+fn send(s: &mut User, ex: &dyn Executor, to: &str, fee: u64, payload: Vec<u8>) -> Receipt {
+    let t = Transfer { payload: Some(payload), ..s.transfer(TxKind::Transfer, to, 5, fee) };
+    s.submit(ex, t)
+}
+
+fn deploy(s: &mut User, ex: &dyn Executor) -> String {
+    let addr = contract_address(&s.addr, s.nonce);
+    let r = send(s, ex, &addr, 0, wat::parse_str(COUNTER).unwrap());
+    assert_eq!(r.status, Status::Committed);
+    assert_eq!(r.output, Some(addr.clone().into_bytes()));
+    addr
+}
+
+fn deployer(ex: &SimpleExecutor) -> User { funded("wasm-deployer", ex, 1_000_000_000) }
+
+#[test]
+fn deploys_and_calls_a_contract() {
+    let inner = Arc::new(SimpleExecutor::default());
+    let mut s = deployer(&inner);
+    let ex = WasmExecutor::new(inner);
+    let contract = deploy(&mut s, &ex);
+    assert!(ex.code(&contract).is_some());
+
+    for n in 1..=2u64 {
+        let r = send(&mut s, &ex, &contract, 0, vec![0]);
+        assert_eq!(r.status, Status::Committed);
+        assert_eq!(r.output, Some(n.to_le_bytes().to_vec()));
+        assert_eq!(r.logs, vec!["incremented".to_string()]);
+        assert_eq!(r.events.last(), Some(&Event::Log { contract: contract.clone(), message: "incremented".into() }));
+    }
+    assert_eq!(ex.storage(&contract, b"count"), Some(2u64.to_le_bytes().to_vec()));
+    assert_eq!(ex.balance(&contract), 15);
+
+    // A payload to an ordinary account is still just a transfer.
+    let r = send(&mut s, &ex, "plain", 0, b"memo".to_vec());
+    assert_eq!((r.status, r.output), (Status::Committed, None));
+    assert_eq!(ex.balance("plain"), 5);
+}
+
+#[test]
+fn simulated_calls_write_nothing() {
+    let inner = Arc::new(SimpleExecutor::default());
+    let mut s = deployer(&inner);
+    let ex = WasmExecutor::new(inner);
+    let contract = deploy(&mut s, &ex);
+    let (root, nonce, bal) = (ex.state_root(), ex.nonce(&s.addr), ex.balance(&contract));
+    let call = |nonce| {
+        s.signed(Transfer { nonce, payload: Some(vec![0]), ..s.transfer(TxKind::Transfer, &contract, 5, 0) })
+    };
+    let rs = ex.simulate(&[call(nonce), call(nonce + 1)]);
+    assert_eq!(rs.iter().map(|r| r.output.clone()).collect::<Vec<_>>(), vec![Some(1u64.to_le_bytes().to_vec()), Some(2u64.to_le_bytes().to_vec())]);
+    assert_eq!(rs[1].logs, vec!["incremented".to_string()]);
+    assert_eq!(ex.storage(&contract, b"count"), None);
+    assert_eq!((ex.state_root(), ex.nonce(&s.addr), ex.balance(&contract)), (root, nonce, bal));
+}
+
+#[test]
+fn simulated_deploys_are_callable_but_not_kept() {
+    let inner = Arc::new(SimpleExecutor::default());
+    let mut s = deployer(&inner);
+    let ex = WasmExecutor::new(inner);
+    let addr = contract_address(&s.addr, s.nonce);
+    let code = Transfer { payload: Some(wat::parse_str(COUNTER).unwrap()), ..s.transfer(TxKind::Transfer, &addr, 5, 0) };
+    let call = Transfer { nonce: s.nonce + 1, payload: Some(vec![0]), ..s.transfer(TxKind::Transfer, &addr, 5, 0) };
+    let rs = ex.simulate(&[code, call].map(|t| s.signed(t)));
+    assert_eq!(rs.iter().map(|r| r.status.clone()).collect::<Vec<_>>(), vec![Status::Committed, Status::Committed]);
+    assert_eq!(rs[1].output, Some(1u64.to_le_bytes().to_vec()));
+    assert_eq!((ex.code(&addr), ex.balance(&addr).0), (None, 0));
+
+    // The real deploy starts from scratch.
+    assert_eq!(deploy(&mut s, &ex), addr);
+    assert_eq!(send(&mut s, &ex, &addr, 0, vec![0]).output, Some(1u64.to_le_bytes().to_vec()));
+}
+
+#[test]
+fn failed_calls_write_nothing() {
+    let inner = Arc::new(SimpleExecutor::default().with_fees(FeeSchedule { gas_price: 1, treasury: Some("treasury".into()) }));
+    let mut s = deployer(&inner);
+    let ex = WasmExecutor::new(inner);
+    let to = contract_address(&s.addr, 0);
+    let contract = send(&mut s, &ex, &to, 1_000_000, wat::parse_str(COUNTER).unwrap()).output.map(|a| String::from_utf8(a).unwrap()).unwrap();
+    s.nonce = 1;
+    let (bal, treasury, held) = (ex.balance(&s.addr), ex.balance("treasury"), ex.balance(&contract));
+
+    // A failed call pays for the fuel it burned, at least the minimum fee, and can't be replayed.
+    let trapped = send(&mut s, &ex, &contract, 2_000, vec![1]);
+    assert!(rejection(&trapped).starts_with("contract call failed"), "{trapped:?}");
+    assert!(trapped.fee_paid.0 >= 1_016 && trapped.fee_paid.0 < 2_000, "{trapped:?}");
+    assert_eq!((s.nonce, ex.nonce(&s.addr)), (2, 2));
+    s.nonce = 1;
+    assert!(matches!(send(&mut s, &ex, &contract, 2_000, vec![1]).status, Status::Rejected(Rejection::BadNonce { .. })));
+    s.nonce = 2;
+    // The fee above the minimum buys 1_000 fuel, far too little to spin forever, and all of it burns.
+    let spun = send(&mut s, &ex, &contract, 2_016, vec![2]);
+    assert!(rejection(&spun).starts_with("contract call failed"), "{spun:?}");
+    assert_eq!(spun.fee_paid.0, 2_016);
+
+    let paid = trapped.fee_paid.0 + spun.fee_paid.0;
+    assert_eq!((ex.balance(&s.addr).0, ex.nonce(&s.addr)), (bal.0 - paid, 3));
+    assert_eq!(ex.balance("treasury").0, treasury.0 + paid);
+    assert_eq!((ex.balance(&contract), ex.storage(&contract, b"count")), (held, None));
+}
+
+#[test]
+fn rejects_bad_deploys() {
+    let inner = Arc::new(SimpleExecutor::default());
+    let mut s = deployer(&inner);
+    let ex = WasmExecutor::new(inner);
+    let code = wat::parse_str(COUNTER).unwrap();
+    // Each failed deploy still uses up its nonce, so each retry targets a new address.
+    assert!(rejection(&send(&mut s, &ex, "elsewhere", 0, code)).starts_with("contract deploy must be sent to"));
+    let to = contract_address(&s.addr, s.nonce);
+    assert!(rejection(&send(&mut s, &ex, &to, 0, b"\0asm garbage".to_vec())).starts_with("invalid contract"));
+    let no_entry = wat::parse_str(r#"(module (memory (export "memory") 1))"#).unwrap();
+    let to = contract_address(&s.addr, s.nonce);
+    assert!(rejection(&send(&mut s, &ex, &to, 0, no_entry)).starts_with("invalid contract"));
+    let floats = wat::parse_str(r#"(module (func (export "call") (drop (f32.add (f32.const 1) (f32.const 2)))))"#).unwrap();
+    let to = contract_address(&s.addr, s.nonce);
+    assert!(rejection(&send(&mut s, &ex, &to, 0, floats)).starts_with("invalid contract"));
+    assert_eq!((ex.nonce(&s.addr), ex.code(&to)), (4, None));
+}
+
+#[test]
+fn contract_state_is_committed_and_persisted() {
+    let kv: Arc<dyn Kv> = Arc::new(InMemoryKv::new());
+    let open = || Arc::new(SimpleExecutor::open(DEFAULT_CHAIN_ID, kv.clone(), &[], [1; 32]).unwrap());
+    let inner = open();
+    let mut s = deployer(&inner);
+    let ex = WasmExecutor::new(inner);
+    let contract = deploy(&mut s, &ex);
+    let before = ex.state_root();
+    send(&mut s, &ex, &contract, 0, vec![0]);
+    let after = ex.state_root();
+    assert_ne!(before, after);
+
+    // Account proofs cover contract state too, at the latest and earlier heights.
+    for (h, root) in [(1, before), (2, after)] {
+        let p = ex.prove(&s.addr, h).unwrap();
+        assert_eq!(p.root, root);
+        assert!(p.verify(&s.addr));
+    }
+
+    let reopened = WasmExecutor::new(open());
+    assert_eq!(reopened.storage(&contract, b"count"), Some(1u64.to_le_bytes().to_vec()));
+    assert_eq!(reopened.state_root(), after);
+}
@@ -0,0 +1,50 @@
+//! Checks a transaction passes before it's queued, against committed state only: a tx that
+//! passes can still be rejected in its block.
+
+use std::sync::Arc;
+use types::{Rejection, Tx, TxKind};
+
+use crate::StateView;
+
+/// How far past its sender's committed nonce a tx may be queued.
+pub const MAX_NONCE_AHEAD: u64 = 64;
+
+/// Largest payload admitted, in bytes.
+pub const MAX_PAYLOAD_BYTES: usize = 128 << 10;
+
+#[derive(Clone)]
+pub struct Admission {
+    chain_id: Arc<str>,
+    state: Arc<dyn StateView>,
+    pub max_nonce_ahead: u64,
+    pub max_payload_bytes: usize,
+}
+
+impl Admission {
+    pub fn new(chain_id: &str, state: Arc<dyn StateView>) -> Self {
+        Self { chain_id: chain_id.into(), state, max_nonce_ahead: MAX_NONCE_AHEAD, max_payload_bytes: MAX_PAYLOAD_BYTES }
+    }
+
+    pub fn check(&self, tx: &Tx) -> Result<(), Rejection> {
+        let t = &tx.signed.transfer;
+        let bytes = t.payload.as_ref().map_or(0, Vec::len);
+        if bytes > self.max_payload_bytes { return Err(Rejection::TooLarge { bytes, max: self.max_payload_bytes }); }
+        tx.signed.verify(&self.chain_id)?;
+        // Checked here, not only at execution, so a copy stripped of cosignatures can't take the
+        // real transaction's id in the pool.
+        tx.signed.authorize(self.state.auth(&t.from).as_ref())?;
+        if let Some(expiry) = t.expiry.filter(|e| e.passed(self.state.last_height() + 1, types::now_ms() as u64)) {
+            return Err(Rejection::Expired { expiry });
+        }
+        let expected = self.state.nonce(&t.from);
+        if t.nonce < expected || t.nonce - expected > self.max_nonce_ahead {
+            return Err(Rejection::BadNonce { account: t.from.clone(), expected, got: t.nonce });
+        }
+        // Only a plain native transfer is known to spend `amount` of the native coin up front.
+        let spends = if t.kind == TxKind::Transfer && t.asset.is_none() && t.token_op.is_none() { t.fee.checked_add(t.amount) } else { Some(t.fee) };
+        if spends.is_none_or(|s| s > self.state.balance(&t.from)) {
+            return Err(Rejection::InsufficientFunds { account: t.from.clone(), asset: None });
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,75 @@
+//! Admitted transactions kept on disk until they're included in a block or the pool lets go of
+//! them, so a restart re-admits what it would otherwise lose.
+//!
+//! Each one is stored under `mempool/ | tx id`. Every write is fsynced, so writes run one after
+//! another on a thread of the journal's own instead of on the async workers.
+
+use std::sync::{mpsc, Arc};
+use storage::{Kv, WriteBatch};
+use tokio::sync::oneshot;
+use types::{Tx, TxId};
+
+const JOURNAL_PREFIX: &[u8] = b"mempool/";
+
+fn key(id: &TxId) -> Vec<u8> { [JOURNAL_PREFIX, id].concat() }
+
+enum Op { Record(Box<Tx>), Forget(Vec<TxId>) }
+
+/// An op for the writer, and where to report how it went; unreported failures are logged.
+type Job = (Op, Option<oneshot::Sender<anyhow::Result<()>>>);
+
+#[derive(Clone)]
+pub struct Journal { kv: Arc<dyn Kv>, writer: mpsc::Sender<Job> }
+
+impl Journal {
+    /// Starts the writer thread, which stops once every clone of the journal is dropped.
+    pub fn new(kv: Arc<dyn Kv>) -> Self {
+        let (writer, jobs) = mpsc::channel::<Job>();
+        let store = kv.clone();
+        std::thread::spawn(move || {
+            for (op, done) in jobs {
+                let res = apply(&*store, op);
+                match done {
+                    Some(d) => { let _ = d.send(res); }
+                    None => if let Err(e) = res { tracing::warn!(error = %e, "mempool journal: write failed"); },
+                }
+            }
+        });
+        Self { kv, writer }
+    }
+
+    /// Journals `tx`, returning once it's on disk.
+    pub async fn record(&self, tx: &Tx) -> anyhow::Result<()> { self.wait(Op::Record(Box::new(tx.clone()))).await }
+
+    /// Queues dropping the journaled ones among `ids` in a single batch, after every write queued
+    /// before; ids never journaled, like gossiped txs, cost nothing.
+    pub fn forget<'a>(&self, ids: impl IntoIterator<Item = &'a TxId>) {
+        let ids: Vec<TxId> = ids.into_iter().copied().collect();
+        if ids.is_empty() { return; }
+        let _ = self.writer.send((Op::Forget(ids), None));
+    }
+
+    /// Waits until every write queued so far is on disk.
+    pub async fn flush(&self) { let _ = self.wait(Op::Forget(Vec::new())).await; }
+
+    /// Every journaled transaction, skipping any that no longer decode.
+    pub fn load(&self) -> Vec<Tx> {
+        self.kv.scan_prefix(JOURNAL_PREFIX).into_iter().filter_map(|(_, v)| bincode::deserialize(&v).ok()).collect()
+    }
+
+    async fn wait(&self, op: Op) -> anyhow::Result<()> {
+        let (done, rx) = oneshot::channel();
+        self.writer.send((op, Some(done))).map_err(|_| anyhow::anyhow!("journal writer stopped"))?;
+        rx.await.map_err(|_| anyhow::anyhow!("journal writer stopped"))?
+    }
+}
+
+fn apply(kv: &dyn Kv, op: Op) -> anyhow::Result<()> {
+    let mut b = WriteBatch::new();
+    match op {
+        Op::Record(tx) => b.put(&key(&tx.id), bincode::serialize(&*tx)?),
+        Op::Forget(ids) => for k in ids.iter().map(key).filter(|k| kv.get(k).is_some()) { b.delete(&k); },
+    }
+    if b.is_empty() { return Ok(()); }
+    kv.write_batch(b)
+}
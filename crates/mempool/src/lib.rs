@@ -1,67 +1,297 @@
+pub use admission::{Admission, MAX_NONCE_AHEAD, MAX_PAYLOAD_BYTES};
+pub use journal::Journal;
+pub use pool::{Admitted, Dropped, Pool, PoolLimits, PoolObserver, RBF_BUMP_PERCENT};
+mod admission;
+mod journal;
+mod pool;
 
-use tokio::sync::mpsc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::{mpsc, oneshot, watch};
 use tokio::time::{self, Duration};
-use types::{Tx, Batch};
+use types::{Amount, AuthPolicy, Batch, Rejection, Tx, TxId};
 use once_cell::sync::Lazy;
-use prometheus::{IntGauge, Histogram, register_int_gauge, register_histogram};
+use prometheus::{IntGauge, IntCounter, Histogram, register_int_gauge, register_int_counter, register_histogram};
 
 static MEMPOOL_SIZE: Lazy<IntGauge> = Lazy::new(|| register_int_gauge!("mempool_queue_len", "current tx queue length").unwrap());
 static MEMPOOL_FLUSH_LAT: Lazy<Histogram> = Lazy::new(|| register_histogram!("mempool_flush_seconds", "time between flushes").unwrap());
+static MEMPOOL_REJECTED: Lazy<IntCounter> = Lazy::new(|| register_int_counter!("mempool_rejected_total", "txs refused admission").unwrap());
+static MEMPOOL_DROPPED: Lazy<IntCounter> = Lazy::new(|| register_int_counter!("mempool_dropped_total", "duplicate or stale-nonce txs dropped").unwrap());
+static MEMPOOL_BYTES: Lazy<IntGauge> = Lazy::new(|| register_int_gauge!("mempool_queue_bytes", "encoded size of queued txs").unwrap());
+static MEMPOOL_EVICTED: Lazy<IntCounter> = Lazy::new(|| register_int_counter!("mempool_evicted_total", "txs evicted by higher-fee ones when full").unwrap());
+static MEMPOOL_EXPIRED: Lazy<IntCounter> = Lazy::new(|| register_int_counter!("mempool_expired_total", "txs dropped past their expiry").unwrap());
+static MEMPOOL_REPLACED: Lazy<IntCounter> = Lazy::new(|| register_int_counter!("mempool_replaced_total", "txs replaced by fee").unwrap());
+
+/// Committed account state the mempool orders and admits transactions against.
+pub trait StateView: Send + Sync {
+    /// Next unused nonce of `addr`.
+    fn nonce(&self, addr: &str) -> u64;
+    /// Native balance of `addr`.
+    fn balance(&self, addr: &str) -> Amount;
+    /// Height of the last executed block.
+    fn last_height(&self) -> u64;
+    /// Signing policy of `addr`, if it set one; otherwise its address key signs alone.
+    fn auth(&self, addr: &str) -> Option<AuthPolicy>;
+}
+
+/// Why a transaction wasn't queued.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MempoolError {
+    /// The queue or the pool is at capacity; retry later.
+    Full,
+    /// Failed admission; resubmitting it unchanged won't help.
+    Invalid(Rejection),
+    /// Admitted, but the pool turned it away; never [`Dropped::Full`], which is [`MempoolError::Full`].
+    Dropped(Dropped),
+    /// The mempool task has stopped.
+    Closed,
+}
+
+impl std::fmt::Display for MempoolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MempoolError::Full => f.write_str("mempool is full"),
+            MempoolError::Invalid(r) => r.fmt(f),
+            MempoolError::Dropped(Dropped::Duplicate) => f.write_str("transaction is already pending"),
+            MempoolError::Dropped(Dropped::StaleNonce) => f.write_str("nonce is already used"),
+            MempoolError::Dropped(Dropped::Underpriced) => f.write_str("fee too low to replace the pending transaction with this nonce"),
+            MempoolError::Dropped(Dropped::Full) => f.write_str("mempool is full"),
+            MempoolError::Closed => f.write_str("mempool is closed"),
+        }
+    }
+}
+
+impl std::error::Error for MempoolError {}
+
+/// A transaction on its way to the pool, with where to send the pool's verdict on it.
+type Submission = (Tx, Option<oneshot::Sender<Result<(), Dropped>>>);
 
 #[derive(Clone)]
-pub struct MempoolHandle { tx: mpsc::Sender<Tx> }
+pub struct MempoolHandle {
+    tx: mpsc::Sender<Submission>,
+    admission: Admission,
+    journal: Option<Journal>,
+    counters: Arc<Counters>,
+    shutdown: Arc<watch::Sender<bool>>,
+}
 
 impl MempoolHandle {
-    pub async fn enqueue(&self, txi: Tx) -> anyhow::Result<()> {
-        self.tx.send(txi).await.map_err(|e| anyhow::anyhow!(e.to_string()))
+    /// Journals every transaction it admits before queueing it; see [`MempoolHandle::restore`].
+    pub fn with_journal(mut self, journal: Journal) -> Self { self.journal = Some(journal); self }
+
+    /// Pools `txi`, waiting for room if the queue is full, and returns the pool's verdict.
+    pub async fn enqueue(&self, txi: Tx) -> Result<(), MempoolError> {
+        self.check(&txi)?;
+        let permit = self.tx.reserve().await.map_err(|_| MempoolError::Closed)?;
+        self.journal(&txi).await;
+        let (verdict, rx) = oneshot::channel();
+        permit.send((txi, Some(verdict)));
+        verdict_of(rx).await
+    }
+
+    /// Pools `txi` and returns the pool's verdict, or fails with [`MempoolError::Full`] at once if
+    /// the queue has no room.
+    pub async fn try_enqueue(&self, txi: Tx) -> Result<(), MempoolError> {
+        self.check(&txi)?;
+        // Room is taken before journaling, so a tx turned away for it costs no disk write.
+        let permit = self.tx.try_reserve().map_err(|e| match e {
+            mpsc::error::TrySendError::Full(()) => { MEMPOOL_REJECTED.inc(); MempoolError::Full }
+            mpsc::error::TrySendError::Closed(()) => MempoolError::Closed,
+        })?;
+        self.journal(&txi).await;
+        let (verdict, rx) = oneshot::channel();
+        permit.send((txi, Some(verdict)));
+        verdict_of(rx).await
+    }
+
+    /// Queues the journaled transactions that still pass admission, e.g. after a restart, and
+    /// forgets the rest. Returns the ids of the queued ones.
+    pub async fn restore(&self) -> Vec<TxId> {
+        let Some(journal) = &self.journal else { return Vec::new() };
+        let (mut queued, mut stale) = (Vec::new(), Vec::new());
+        for txi in journal.load() {
+            if self.admission.check(&txi).is_err() { stale.push(txi.id); continue; }
+            let id = txi.id;
+            if self.tx.send((txi, None)).await.is_err() { break; }
+            queued.push(id);
+        }
+        journal.forget(&stale);
+        queued
+    }
+
+    pub fn stats(&self) -> MempoolStats {
+        MempoolStats {
+            queued: self.tx.max_capacity() - self.tx.capacity(),
+            pooled: self.counters.pooled.load(Ordering::Relaxed),
+            pooled_bytes: self.counters.pooled_bytes.load(Ordering::Relaxed),
+            batches: self.counters.batches.load(Ordering::Relaxed),
+            batched_txs: self.counters.batched_txs.load(Ordering::Relaxed),
+        }
     }
+
+    /// Transactions admitted but not yet batched.
+    pub fn pending(&self) -> usize { let s = self.stats(); s.queued + s.pooled }
+
+    /// Stops the mempool task; queued transactions stay journaled. Enqueueing fails with
+    /// [`MempoolError::Closed`] once it has stopped.
+    pub fn shutdown(&self) { let _ = self.shutdown.send(true); }
+
+    /// Waits until the mempool task has stopped.
+    pub async fn stopped(&self) { self.tx.closed().await }
+
+    fn check(&self, txi: &Tx) -> Result<(), MempoolError> {
+        self.admission.check(txi).map_err(|r| { MEMPOOL_REJECTED.inc(); MempoolError::Invalid(r) })
+    }
+
+    /// A failure is only logged: a tx that isn't journaled is lost on restart, not refused now.
+    async fn journal(&self, txi: &Tx) {
+        let Some(journal) = &self.journal else { return };
+        if let Err(e) = journal.record(txi).await { tracing::warn!(error = %e, "mempool journal: write failed"); }
+    }
+}
+
+/// The pool's verdict on a submitted tx. A dropped tx is already out of the journal; one the task
+/// stopped before deciding on stays journaled for the next start.
+async fn verdict_of(rx: oneshot::Receiver<Result<(), Dropped>>) -> Result<(), MempoolError> {
+    match rx.await {
+        Ok(Ok(())) => Ok(()),
+        Ok(Err(Dropped::Full)) => Err(MempoolError::Full),
+        Ok(Err(d)) => Err(MempoolError::Dropped(d)),
+        Err(_) => Err(MempoolError::Closed),
+    }
+}
+
+/// How long a tx id is remembered, so the same tx from RPC and gossip (or our own gossip echoed
+/// back) is batched once.
+pub const DEDUP_WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone)]
+pub struct MempoolConfig {
+    /// How often a batch is cut from whatever is ready, even if it isn't full.
+    pub flush_interval: Duration,
+    /// A batch is cut as soon as this many txs, or this many encoded bytes, are queued.
+    pub max_batch_txs: usize,
+    pub max_batch_bytes: usize,
+    /// Txs admitted but not yet taken into the pool; [`MempoolHandle::try_enqueue`] fails past it.
+    pub channel_capacity: usize,
+    pub dedup_window: Duration,
+    pub limits: PoolLimits,
+}
+
+impl Default for MempoolConfig {
+    fn default() -> Self {
+        Self {
+            flush_interval: Duration::from_millis(25), max_batch_txs: 128, max_batch_bytes: 1 << 20,
+            channel_capacity: 64_000, dedup_window: DEDUP_WINDOW, limits: PoolLimits::default(),
+        }
+    }
+}
+
+impl MempoolConfig {
+    /// An empty pool with this config's dedup window and limits.
+    pub fn pool(&self, state: Arc<dyn StateView>) -> Pool { Pool::new(state, self.dedup_window).with_limits(self.limits) }
 }
 
-pub fn spawn_mempool(_flush_ms: u64, _max_batch_len: usize, _to_consensus: mpsc::Sender<Batch>) -> (MempoolHandle, mpsc::Receiver<Tx>) {
-    let (tx, rx) = mpsc::channel::<Tx>(64_000);
-    (MempoolHandle { tx }, rx)
+/// What a running mempool holds and has done; see [`MempoolHandle::stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MempoolStats {
+    /// Admitted, waiting to be taken into the pool.
+    pub queued: usize,
+    pub pooled: usize,
+    pub pooled_bytes: usize,
+    pub batches: u64,
+    pub batched_txs: u64,
 }
 
-pub async fn run_mempool(
-    mut from_clients: mpsc::Receiver<Tx>,
+#[derive(Default)]
+struct Counters { pooled: AtomicUsize, pooled_bytes: AtomicUsize, batches: AtomicU64, batched_txs: AtomicU64 }
+
+/// Starts the task that pools what `handle`s and `from_p2p` admit and sends batches of it to
+/// `to_consensus` as `config` says. It runs until [`MempoolHandle::shutdown`], or until consensus
+/// stops taking batches.
+pub fn spawn_mempool(
+    config: MempoolConfig,
+    admission: Admission,
+    pool: Pool,
+    to_consensus: mpsc::Sender<Batch>,
+    from_p2p: Option<mpsc::Receiver<Tx>>,
+) -> MempoolHandle {
+    let (tx, from_clients) = mpsc::channel::<Submission>(config.channel_capacity);
+    let (shutdown, stop) = watch::channel(false);
+    let counters = Arc::new(Counters::default());
+    let task = Task { config, admission: admission.clone(), pool, from_clients, from_p2p, to_consensus, counters: counters.clone(), stop };
+    tokio::spawn(task.run());
+    MempoolHandle { tx, admission, journal: None, counters, shutdown: Arc::new(shutdown) }
+}
+
+struct Task {
+    config: MempoolConfig,
+    admission: Admission,
+    pool: Pool,
+    from_clients: mpsc::Receiver<Submission>,
+    from_p2p: Option<mpsc::Receiver<Tx>>,
     to_consensus: mpsc::Sender<Batch>,
-    flush_ms: u64,
-    max_batch_len: usize,
-    mut from_p2p: Option<mpsc::Receiver<Tx>>,
-) {
-    let mut ticker = time::interval(Duration::from_millis(flush_ms));
-    let mut cur: Vec<Tx> = Vec::with_capacity(max_batch_len);
-    let mut batch_id: u64 = 1;
-    let mut last_flush = std::time::Instant::now();
-
-    loop {
-        MEMPOOL_SIZE.set(cur.len() as i64);
-        tokio::select! {
-            maybe_tx = from_clients.recv() => {
-                match maybe_tx {
-                    Some(tx) => {
-                        cur.push(tx);
-                        if cur.len() >= max_batch_len {
-                            let out = Batch { id: batch_id, txs: std::mem::take(&mut cur) };
-                            batch_id += 1;
-                            if let Err(e) = to_consensus.send(out).await { eprintln!("mempool -> consensus send error: {e}"); break; }
-                            MEMPOOL_FLUSH_LAT.observe(last_flush.elapsed().as_secs_f64()); last_flush = std::time::Instant::now();
+    counters: Arc<Counters>,
+    stop: watch::Receiver<bool>,
+}
+
+impl Task {
+    async fn run(mut self) {
+        // The first flush is one interval in; there's nothing to cut at start.
+        let mut ticker = time::interval_at(time::Instant::now() + self.config.flush_interval, self.config.flush_interval);
+        let mut batch_id: u64 = 1;
+        let mut last_flush = std::time::Instant::now();
+
+        loop {
+            self.counters.pooled.store(self.pool.len(), Ordering::Relaxed);
+            self.counters.pooled_bytes.store(self.pool.bytes(), Ordering::Relaxed);
+            MEMPOOL_SIZE.set(self.pool.len() as i64);
+            MEMPOOL_BYTES.set(self.pool.bytes() as i64);
+            let full = tokio::select! {
+                _ = self.stop.changed() => break,
+                maybe_tx = self.from_clients.recv() => {
+                    match maybe_tx {
+                        Some((tx, verdict)) => {
+                            let res = admit(&mut self.pool, tx);
+                            if let Some(v) = verdict { let _ = v.send(res); }
+                            self.full()
                         }
-                    },
-                    None => break,
+                        None => break,
+                    }
+                }
+                from_gossip = async { match &mut self.from_p2p { Some(rx) => rx.recv().await, None => std::future::pending().await } } => {
+                    match from_gossip {
+                        Some(tx) if self.admission.check(&tx).is_ok() => { let _ = admit(&mut self.pool, tx); self.full() }
+                        Some(_) => { MEMPOOL_REJECTED.inc(); false }
+                        None => { self.from_p2p = None; false }
+                    }
                 }
-            }
-            from_gossip = async { if let Some(rx) = &mut from_p2p { rx.recv().await } else { None } } => {
-                if let Some(tx) = from_gossip { cur.push(tx); }
-            }
-            _ = ticker.tick() => {
-                if !cur.is_empty() {
-                    let out = Batch { id: batch_id, txs: std::mem::take(&mut cur) };
-                    batch_id += 1;
-                    if let Err(e) = to_consensus.send(out).await { eprintln!("mempool -> consensus send error: {e}"); break; }
-                    MEMPOOL_FLUSH_LAT.observe(last_flush.elapsed().as_secs_f64()); last_flush = std::time::Instant::now();
+                _ = ticker.tick() => {
+                    let expired = self.pool.expire(types::now_ms() as u64);
+                    MEMPOOL_EXPIRED.inc_by(expired.len() as u64);
+                    true
                 }
-            }
+            };
+            if !full { continue; }
+            let txs = self.pool.take_ready(self.config.max_batch_txs, self.config.max_batch_bytes);
+            if txs.is_empty() { continue; }
+            let len = txs.len() as u64;
+            let out = Batch { id: batch_id, txs, time_ms: types::now_ms() as u64 };
+            batch_id += 1;
+            if let Err(e) = self.to_consensus.send(out).await { eprintln!("mempool -> consensus send error: {e}"); break; }
+            self.counters.batches.fetch_add(1, Ordering::Relaxed);
+            self.counters.batched_txs.fetch_add(len, Ordering::Relaxed);
+            MEMPOOL_FLUSH_LAT.observe(last_flush.elapsed().as_secs_f64()); last_flush = std::time::Instant::now();
         }
     }
+
+    fn full(&self) -> bool { self.pool.len() >= self.config.max_batch_txs || self.pool.bytes() >= self.config.max_batch_bytes }
+}
+
+fn admit(pool: &mut Pool, tx: Tx) -> Result<(), Dropped> {
+    match pool.insert(tx) {
+        Ok(a) => { MEMPOOL_REPLACED.inc_by(a.replaced.is_some() as u64); MEMPOOL_EVICTED.inc_by(a.evicted.len() as u64); Ok(()) }
+        Err(d @ (Dropped::Duplicate | Dropped::StaleNonce)) => { MEMPOOL_DROPPED.inc(); Err(d) }
+        Err(d @ (Dropped::Underpriced | Dropped::Full)) => { MEMPOOL_REJECTED.inc(); Err(d) }
+    }
 }
@@ -0,0 +1,246 @@
+//! Pending transactions, deduplicated by id and queued per sender in nonce order, so a batch never
+//! carries a nonce its sender can't use yet. Batches and eviction both go by fee per byte.
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use types::{Tx, TxId, Unlock};
+
+use crate::{Journal, StateView};
+
+/// Told when the pool lets go of a transaction other than into a batch.
+pub trait PoolObserver: Send + Sync {
+    /// Pushed out by a better-paying transaction, or replaced by fee.
+    fn evicted(&self, tx: &Tx);
+    /// Past its expiry before it could be batched.
+    fn expired(&self, tx: &Tx);
+}
+
+/// How much a replacement must raise the fee of the pending tx with its sender and nonce, in percent.
+pub const RBF_BUMP_PERCENT: u64 = 10;
+
+/// Why [`Pool::insert`] dropped a transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dropped {
+    /// Seen within the dedup window, e.g. from both RPC and gossip.
+    Duplicate,
+    /// The nonce is already committed.
+    StaleNonce,
+    /// Another pending transaction of the sender has this nonce, and a fee at most
+    /// [`RBF_BUMP_PERCENT`] higher doesn't replace it.
+    Underpriced,
+    /// The pool is full of transactions paying at least as much per byte.
+    Full,
+}
+
+/// Bounds on what the pool holds; see [`Pool::with_limits`].
+#[derive(Debug, Clone, Copy)]
+pub struct PoolLimits { pub max_txs: usize, pub max_bytes: usize }
+
+impl Default for PoolLimits {
+    fn default() -> Self { Self { max_txs: 50_000, max_bytes: 64 << 20 } }
+}
+
+/// What an admitted transaction pushed out of the pool.
+#[derive(Debug, Default)]
+pub struct Admitted { pub replaced: Option<Tx>, pub evicted: Vec<Tx> }
+
+/// Fee per byte, then arrival order: greater is better.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Priority { fee: u64, bytes: u64, seq: u64 }
+
+impl Ord for Priority {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.fee as u128 * other.bytes as u128).cmp(&(other.fee as u128 * self.bytes as u128)).then(other.seq.cmp(&self.seq))
+    }
+}
+
+impl PartialOrd for Priority {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> { Some(self.cmp(other)) }
+}
+
+struct Entry { tx: Tx, prio: Priority }
+
+struct Queue {
+    /// Nonce of the next transaction to release; ahead of committed state while released ones are in flight.
+    next: u64,
+    txs: BTreeMap<u64, Entry>,
+}
+
+pub struct Pool {
+    state: Arc<dyn StateView>,
+    dedup_window: Duration,
+    limits: PoolLimits,
+    seen: HashMap<TxId, Instant>,
+    seen_order: VecDeque<(Instant, TxId)>,
+    queues: BTreeMap<String, Queue>,
+    /// Every queued transaction, lowest priority first.
+    by_priority: BTreeSet<(Priority, String, u64)>,
+    /// Queued transactions with an expiry, soonest first.
+    by_expiry_height: BTreeSet<(u64, String, u64)>,
+    by_expiry_time: BTreeSet<(u64, String, u64)>,
+    observer: Option<Arc<dyn PoolObserver>>,
+    journal: Option<Journal>,
+    len: usize,
+    bytes: usize,
+    seq: u64,
+}
+
+impl Pool {
+    pub fn new(state: Arc<dyn StateView>, dedup_window: Duration) -> Self {
+        Self {
+            state, dedup_window, limits: PoolLimits::default(), seen: HashMap::new(), seen_order: VecDeque::new(),
+            queues: BTreeMap::new(), by_priority: BTreeSet::new(), by_expiry_height: BTreeSet::new(), by_expiry_time: BTreeSet::new(),
+            observer: None, journal: None, len: 0, bytes: 0, seq: 0,
+        }
+    }
+
+    pub fn with_limits(mut self, limits: PoolLimits) -> Self { self.limits = limits; self }
+
+    pub fn with_observer(mut self, observer: Arc<dyn PoolObserver>) -> Self { self.observer = Some(observer); self }
+
+    /// Forgets journaled transactions as the pool lets go of them other than into a batch.
+    pub fn with_journal(mut self, journal: Journal) -> Self { self.journal = Some(journal); self }
+
+    /// Number of queued transactions.
+    pub fn len(&self) -> usize { self.len }
+
+    pub fn is_empty(&self) -> bool { self.len == 0 }
+
+    /// Encoded size of the queued transactions.
+    pub fn bytes(&self) -> usize { self.bytes }
+
+    pub fn insert(&mut self, tx: Tx) -> Result<Admitted, Dropped> {
+        let id = tx.id;
+        let res = self.try_insert(tx);
+        match &res {
+            Ok(a) => self.forget(a.replaced.iter().chain(&a.evicted).map(|t| &t.id)),
+            // The first copy is still queued or batched.
+            Err(Dropped::Duplicate) => {}
+            Err(_) => self.forget([&id]),
+        }
+        res
+    }
+
+    fn try_insert(&mut self, tx: Tx) -> Result<Admitted, Dropped> {
+        let now = Instant::now();
+        while let Some(&(at, id)) = self.seen_order.front() {
+            if now.duration_since(at) < self.dedup_window { break; }
+            self.seen_order.pop_front();
+            if self.seen.get(&id) == Some(&at) { self.seen.remove(&id); }
+        }
+        // Only pooled txs are remembered, so one dropped for room or price can come back later.
+        if self.seen.contains_key(&tx.id) { return Err(Dropped::Duplicate); }
+
+        let (from, nonce, fee) = (tx.signed.transfer.from.clone(), tx.signed.transfer.nonce, tx.signed.transfer.fee.0);
+        let committed = self.state.nonce(&from);
+        if nonce < committed { return Err(Dropped::StaleNonce); }
+        let prio = Priority { fee, bytes: tx.signed.encoded_len() as u64, seq: self.seq };
+        self.seq += 1;
+        if prio.bytes as usize > self.limits.max_bytes { return Err(Dropped::Full); }
+
+        let mut replaced = None;
+        if let Some(old) = self.queues.get(&from).and_then(|q| q.txs.get(&nonce)) {
+            let old_fee = old.prio.fee;
+            if fee <= old_fee || fee < old_fee.saturating_add(old_fee.saturating_mul(RBF_BUMP_PERCENT) / 100) { return Err(Dropped::Underpriced); }
+            replaced = self.remove(&from, nonce);
+        }
+        let mut evicted = Vec::new();
+        while self.len + 1 > self.limits.max_txs || self.bytes + prio.bytes as usize > self.limits.max_bytes {
+            let Some((lowest, sender, n)) = self.by_priority.first().cloned() else { break };
+            if lowest >= prio {
+                // Nothing is let go unless the tx gets in.
+                for (n, e) in evicted.into_iter().chain(replaced.map(|e| (nonce, e))) { self.put(n, e); }
+                return Err(Dropped::Full);
+            }
+            // Later nonces of the sender can't run without this one, so they go too.
+            let later: Vec<u64> = self.queues[&sender].txs.range(n..).map(|(n, _)| *n).collect();
+            evicted.extend(later.into_iter().rev().filter_map(|n| Some((n, self.remove(&sender, n)?))));
+        }
+        let evicted: Vec<Tx> = evicted.into_iter().map(|(_, e)| e.tx).collect();
+        self.seen.insert(tx.id, now);
+        self.seen_order.push_back((now, tx.id));
+        self.queues.entry(from).or_insert_with(|| Queue { next: committed, txs: BTreeMap::new() });
+        self.put(nonce, Entry { tx, prio });
+        let replaced = replaced.map(|e| e.tx);
+        if let Some(o) = &self.observer { replaced.iter().chain(&evicted).for_each(|t| o.evicted(t)); }
+        Ok(Admitted { replaced, evicted })
+    }
+
+    /// Takes up to `max_txs` transactions, of at most `max_bytes` together, whose nonces continue
+    /// their sender's without a gap: the best paying one of every sender first, then every sender's
+    /// second, and so on. The first one is taken whatever its size.
+    pub fn take_ready(&mut self, max_txs: usize, max_bytes: usize) -> Vec<Tx> {
+        let mut heads = BinaryHeap::new();
+        let senders: Vec<(String, u64)> = self.queues.keys().map(|s| (s.clone(), self.state.nonce(s))).collect();
+        for (sender, committed) in &senders {
+            let committed = *committed;
+            let stale: Vec<u64> = self.queues[sender].txs.range(..committed).map(|(n, _)| *n).collect();
+            let stale: Vec<Tx> = stale.into_iter().filter_map(|n| self.remove(sender, n)).map(|e| e.tx).collect();
+            self.forget(stale.iter().map(|t| &t.id));
+            let q = self.queues.get_mut(sender).expect("listed above");
+            q.next = q.next.max(committed);
+            // A nonce below `next` is a resubmission of one released but not committed, e.g. rejected.
+            if let Some(&first) = q.txs.keys().next() { q.next = q.next.min(first); }
+            if let Some(e) = q.txs.get(&q.next) { heads.push((Reverse(0usize), e.prio, sender.clone())); }
+        }
+
+        let (mut out, mut bytes) = (Vec::new(), 0);
+        while out.len() < max_txs {
+            let Some((Reverse(round), prio, sender)) = heads.pop() else { break };
+            // The sender's later nonces can't go without this one, so the sender is done.
+            if !out.is_empty() && bytes + prio.bytes as usize > max_bytes { continue; }
+            bytes += prio.bytes as usize;
+            let next = self.queues[&sender].next;
+            out.extend(self.remove(&sender, next).map(|e| e.tx));
+            let q = self.queues.get_mut(&sender).expect("has a head");
+            q.next += 1;
+            if let Some(e) = q.txs.get(&q.next) { heads.push((Reverse(round + 1), e.prio, sender)); }
+        }
+        for (sender, committed) in senders {
+            if self.queues.get(&sender).is_some_and(|q| q.txs.is_empty() && q.next <= committed) { self.queues.remove(&sender); }
+        }
+        out
+    }
+
+    /// Drops the transactions that can't be included in the next block if it's made at `time_ms`.
+    pub fn expire(&mut self, time_ms: u64) -> Vec<Tx> {
+        let height = self.state.last_height() + 1;
+        let mut due: Vec<(String, u64)> = Vec::new();
+        due.extend(self.by_expiry_height.iter().take_while(|(h, ..)| *h < height).map(|(_, s, n)| (s.clone(), *n)));
+        due.extend(self.by_expiry_time.iter().take_while(|(t, ..)| *t < time_ms).map(|(_, s, n)| (s.clone(), *n)));
+        let expired: Vec<Tx> = due.into_iter().filter_map(|(s, n)| self.remove(&s, n)).map(|e| e.tx).collect();
+        if let Some(o) = &self.observer { expired.iter().for_each(|t| o.expired(t)); }
+        self.forget(expired.iter().map(|t| &t.id));
+        expired
+    }
+
+    fn forget<'a>(&self, ids: impl IntoIterator<Item = &'a TxId>) {
+        if let Some(j) = &self.journal { j.forget(ids); }
+    }
+
+    fn expiry_index(&mut self, expiry: Unlock) -> &mut BTreeSet<(u64, String, u64)> {
+        match expiry { Unlock::Height(_) => &mut self.by_expiry_height, Unlock::Time(_) => &mut self.by_expiry_time }
+    }
+
+    fn put(&mut self, nonce: u64, e: Entry) {
+        let from = e.tx.signed.transfer.from.clone();
+        if let Some(x) = e.tx.signed.transfer.expiry { self.expiry_index(x).insert((at(x), from.clone(), nonce)); }
+        self.by_priority.insert((e.prio, from.clone(), nonce));
+        self.len += 1;
+        self.bytes += e.prio.bytes as usize;
+        self.queues.get_mut(&from).expect("queue exists").txs.insert(nonce, e);
+    }
+
+    fn remove(&mut self, sender: &str, nonce: u64) -> Option<Entry> {
+        let e = self.queues.get_mut(sender)?.txs.remove(&nonce)?;
+        if let Some(x) = e.tx.signed.transfer.expiry { self.expiry_index(x).remove(&(at(x), sender.to_string(), nonce)); }
+        self.by_priority.remove(&(e.prio, sender.to_string(), nonce));
+        self.len -= 1;
+        self.bytes -= e.prio.bytes as usize;
+        Some(e)
+    }
+}
+
+fn at(u: Unlock) -> u64 { match u { Unlock::Height(h) | Unlock::Time(h) => h } }
@@ -0,0 +1,122 @@
+use mempool::{spawn_mempool, Admission, Dropped, MempoolConfig, MempoolError, MempoolHandle, MempoolStats, PoolLimits, StateView, MAX_NONCE_AHEAD};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use types::{Amount, AuthPolicy, Batch, Rejection, SignedTx, Transfer, Tx, TxKind, Unlock, DEFAULT_CHAIN_ID};
+
+/// Every account has committed nonce 3 and a balance of 1_000, at height 10. The treasury needs
+/// two of the three signer keys.
+struct Fixed;
+
+const TREASURY: &str = "treasury";
+
+fn signer(i: usize) -> crypto::SecretKey { crypto::keypair_from_seed(format!("signer-{i}").as_bytes()).0 }
+
+impl StateView for Fixed {
+    fn nonce(&self, _: &str) -> u64 { 3 }
+    fn balance(&self, _: &str) -> Amount { Amount(1_000) }
+    fn last_height(&self) -> u64 { 10 }
+    fn auth(&self, addr: &str) -> Option<AuthPolicy> {
+        (addr == TREASURY).then(|| AuthPolicy { keys: (0..3).map(|i| crypto::public_key(&signer(i))).collect(), threshold: 2 })
+    }
+}
+
+fn transfer(nonce: u64, amount: u64, fee: u64) -> Transfer {
+    let (_, pk) = crypto::keypair_from_seed(b"alice");
+    Transfer { from: crypto::address(&pk), to: "bob".into(), amount: Amount(amount), fee: Amount(fee), nonce, kind: TxKind::Transfer, asset: None, token_op: None, payload: None, expiry: None }
+}
+
+fn sign(t: Transfer) -> Tx { Tx::new(SignedTx::sign(t, &crypto::keypair_from_seed(b"alice").0, DEFAULT_CHAIN_ID)) }
+
+#[test]
+fn admission_checks_against_committed_state() {
+    let a = Admission::new(DEFAULT_CHAIN_ID, Arc::new(Fixed));
+    let from = transfer(0, 0, 0).from;
+    assert_eq!(a.check(&sign(transfer(3, 900, 100))), Ok(()));
+    assert_eq!(a.check(&sign(transfer(3 + MAX_NONCE_AHEAD, 1, 0))), Ok(()));
+    assert_eq!(a.check(&sign(transfer(2, 1, 0))), Err(Rejection::BadNonce { account: from.clone(), expected: 3, got: 2 }));
+    assert!(matches!(a.check(&sign(transfer(4 + MAX_NONCE_AHEAD, 1, 0))), Err(Rejection::BadNonce { .. })));
+    assert_eq!(a.check(&sign(transfer(3, 901, 100))), Err(Rejection::InsufficientFunds { account: from.clone(), asset: None }));
+    assert!(matches!(a.check(&sign(transfer(3, u64::MAX, 1))), Err(Rejection::InsufficientFunds { .. })));
+
+    let mut forged = sign(transfer(3, 1, 0));
+    forged.signed.transfer.amount = Amount(2);
+    assert!(matches!(a.check(&forged), Err(Rejection::BadSignature(_))));
+
+    let mut big = transfer(3, 0, 0);
+    big.payload = Some(vec![0; a.max_payload_bytes + 1]);
+    assert_eq!(a.check(&sign(big)), Err(Rejection::TooLarge { bytes: a.max_payload_bytes + 1, max: a.max_payload_bytes }));
+}
+
+fn spawn(config: MempoolConfig, to_consensus: mpsc::Sender<Batch>) -> MempoolHandle {
+    let state = Arc::new(Fixed);
+    spawn_mempool(config.clone(), Admission::new(DEFAULT_CHAIN_ID, state.clone()), config.pool(state), to_consensus, None)
+}
+
+#[tokio::test]
+async fn try_enqueue_reports_the_pools_verdict() {
+    let config = MempoolConfig { flush_interval: Duration::from_secs(3600), limits: PoolLimits { max_txs: 1, ..PoolLimits::default() }, ..MempoolConfig::default() };
+    let handle = spawn(config, mpsc::channel(1).0);
+    assert_eq!(handle.try_enqueue(sign(transfer(3, 1, 0))).await, Ok(()));
+    assert_eq!(handle.try_enqueue(sign(transfer(3, 1, 0))).await, Err(MempoolError::Dropped(Dropped::Duplicate)));
+    assert_eq!(handle.try_enqueue(sign(transfer(3, 2, 0))).await, Err(MempoolError::Dropped(Dropped::Underpriced)));
+    // Pays no more per byte than what's pooled, so it can't take its place.
+    assert_eq!(handle.try_enqueue(sign(transfer(4, 1, 0))).await, Err(MempoolError::Full));
+    assert!(matches!(handle.try_enqueue(sign(transfer(0, 1, 0))).await, Err(MempoolError::Invalid(Rejection::BadNonce { .. }))));
+    assert_eq!(handle.pending(), 1);
+    handle.shutdown();
+    handle.stopped().await;
+    assert_eq!(handle.try_enqueue(sign(transfer(4, 1, 0))).await, Err(MempoolError::Closed));
+}
+
+#[tokio::test]
+async fn batches_are_cut_at_the_configured_size() {
+    let (to_consensus, mut batches) = mpsc::channel(1);
+    let handle = spawn(MempoolConfig { max_batch_txs: 2, flush_interval: Duration::from_secs(3600), ..MempoolConfig::default() }, to_consensus);
+    for n in [3, 4] { handle.enqueue(sign(transfer(n, 1, 0))).await.unwrap(); }
+    assert_eq!(batches.recv().await.map(|b| b.txs.len()), Some(2));
+    handle.shutdown();
+    handle.stopped().await;
+    assert_eq!(handle.stats(), MempoolStats { batches: 1, batched_txs: 2, ..MempoolStats::default() });
+}
+
+#[test]
+fn expired_txs_are_not_admitted() {
+    let a = Admission::new(DEFAULT_CHAIN_ID, Arc::new(Fixed));
+    let expiring = |expiry| sign(Transfer { expiry: Some(expiry), ..transfer(3, 1, 0) });
+    // The next block is 11, so a tx valid up to 11 still gets in.
+    assert_eq!(a.check(&expiring(Unlock::Height(11))), Ok(()));
+    assert_eq!(a.check(&expiring(Unlock::Height(10))), Err(Rejection::Expired { expiry: Unlock::Height(10) }));
+    assert_eq!(a.check(&expiring(Unlock::Time(1))), Err(Rejection::Expired { expiry: Unlock::Time(1) }));
+    assert_eq!(a.check(&expiring(Unlock::Time(u64::MAX))), Ok(()));
+}
+
+#[tokio::test]
+async fn multisig_txs_missing_cosigs_are_not_admitted() {
+    let handle = spawn(MempoolConfig::default(), mpsc::channel(1).0);
+    let t = Transfer { from: TREASURY.into(), ..transfer(3, 1, 0) };
+    let full = Tx::new(SignedTx::sign(t, &signer(0), DEFAULT_CHAIN_ID).cosign(&signer(1), DEFAULT_CHAIN_ID));
+    let stripped = Tx::new(SignedTx { cosigs: Vec::new(), ..full.signed.clone() });
+    // Still validly signed by one owner key, and with the same id as the real tx.
+    assert_eq!((stripped.signed.verify(DEFAULT_CHAIN_ID), stripped.id), (Ok(()), full.id));
+    assert_eq!(handle.try_enqueue(stripped).await, Err(MempoolError::Invalid(Rejection::BadSignature(format!("1 of 2 required signatures for {TREASURY}")))));
+    assert_eq!(handle.try_enqueue(full).await, Ok(()));
+    assert_eq!(handle.pending(), 1);
+}
+
+#[test]
+fn badly_signed_txs_are_not_admitted() {
+    let a = Admission::new(DEFAULT_CHAIN_ID, Arc::new(Fixed));
+    let from = transfer(0, 0, 0).from;
+
+    let mut flipped = sign(transfer(3, 1, 0));
+    flipped.signed.sig.0[0] ^= 1;
+    assert_eq!(a.check(&flipped), Err(Rejection::BadSignature(from.clone())));
+
+    let other_chain = Tx::new(SignedTx::sign(transfer(3, 1, 0), &crypto::keypair_from_seed(b"alice").0, "other-chain"));
+    assert_eq!(a.check(&other_chain), Err(Rejection::BadSignature(from.clone())));
+
+    let (mallory, mallory_pk) = crypto::keypair_from_seed(b"mallory");
+    let stolen = Tx::new(SignedTx::sign(transfer(3, 1, 0), &mallory, DEFAULT_CHAIN_ID));
+    assert_eq!(a.check(&stolen), Err(Rejection::BadSignature(format!("key {} does not own {from}", mallory_pk.hex()))));
+}
@@ -0,0 +1,80 @@
+use mempool::{spawn_mempool, Admission, Dropped, Journal, MempoolConfig, MempoolHandle, Pool, StateView};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use storage::InMemoryKv;
+use tokio::sync::mpsc;
+use types::{Amount, AuthPolicy, Batch, SignedTx, Transfer, Tx, TxKind, DEFAULT_CHAIN_ID};
+
+/// Every account shares one committed nonce the test moves by hand.
+#[derive(Default)]
+struct Committed(AtomicU64);
+
+impl StateView for Committed {
+    fn nonce(&self, _: &str) -> u64 { self.0.load(Ordering::SeqCst) }
+    fn balance(&self, _: &str) -> Amount { Amount::MAX }
+    fn last_height(&self) -> u64 { 0 }
+    fn auth(&self, _: &str) -> Option<AuthPolicy> { None }
+}
+
+fn tx(nonce: u64, fee: u64) -> Tx {
+    let (sk, pk) = crypto::keypair_from_seed(b"alice");
+    let t = Transfer { from: crypto::address(&pk), to: "bob".into(), amount: Amount(1), fee: Amount(fee), nonce, kind: TxKind::Transfer, asset: None, token_op: None, payload: None, expiry: None };
+    Tx::new(SignedTx::sign(t, &sk, DEFAULT_CHAIN_ID))
+}
+
+/// A mempool whose batches come out of the returned receiver.
+fn start(state: &Arc<Committed>, journal: &Journal) -> (MempoolHandle, mpsc::Receiver<Batch>) {
+    let (to_consensus, batches) = mpsc::channel(16);
+    let config = MempoolConfig { max_batch_txs: 1, ..MempoolConfig::default() };
+    let pool = config.pool(state.clone()).with_journal(journal.clone());
+    let handle = spawn_mempool(config, Admission::new(DEFAULT_CHAIN_ID, state.clone()), pool, to_consensus, None);
+    (handle.with_journal(journal.clone()), batches)
+}
+
+#[tokio::test]
+async fn queued_txs_survive_a_restart_until_included() {
+    let state = Arc::new(Committed::default());
+    let journal = Journal::new(Arc::new(InMemoryKv::new()));
+    let (handle, rx) = start(&state, &journal);
+    handle.try_enqueue(tx(0, 1)).await.unwrap();
+    handle.enqueue(tx(1, 1)).await.unwrap();
+    handle.shutdown();
+    handle.stopped().await;
+    drop(rx);
+
+    // Nonce 0 committed before the restart, so only nonce 1 is queued again.
+    state.0.store(1, Ordering::SeqCst);
+    let (handle, mut rx) = start(&state, &journal);
+    assert_eq!(handle.restore().await, vec![tx(1, 1).id]);
+    assert_eq!(rx.recv().await.map(|b| b.txs[0].id), Some(tx(1, 1).id));
+    journal.flush().await;
+    assert_eq!(journal.load().len(), 1);
+
+    journal.forget([&tx(1, 1).id]);
+    journal.flush().await;
+    assert!(handle.restore().await.is_empty());
+}
+
+#[tokio::test]
+async fn the_pool_forgets_what_it_lets_go_of() {
+    let state = Arc::new(Committed::default());
+    let journal = Journal::new(Arc::new(InMemoryKv::new()));
+    let mut pool = Pool::new(state.clone(), Duration::from_secs(60)).with_journal(journal.clone());
+    let (first, bumped) = (tx(0, 10), tx(0, 20));
+    for t in [&first, &bumped] { journal.record(t).await.unwrap(); }
+
+    pool.insert(first.clone()).unwrap();
+    assert_eq!(pool.insert(first.clone()).unwrap_err(), Dropped::Duplicate);
+    journal.flush().await;
+    assert_eq!(journal.load().len(), 2, "a duplicate leaves the queued copy journaled");
+    pool.insert(bumped.clone()).unwrap();
+    journal.flush().await;
+    assert_eq!(journal.load().iter().map(|t| t.id).collect::<Vec<_>>(), vec![bumped.id]);
+
+    // Committed by another node's tx with the same nonce.
+    state.0.store(1, Ordering::SeqCst);
+    assert!(pool.take_ready(10, usize::MAX).is_empty());
+    journal.flush().await;
+    assert!(journal.load().is_empty());
+}
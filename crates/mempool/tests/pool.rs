@@ -0,0 +1,192 @@
+use mempool::{Dropped, Pool, PoolLimits, PoolObserver, StateView};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use types::{Amount, AuthPolicy, SignedTx, Transfer, Tx, TxId, TxKind, Unlock, DEFAULT_CHAIN_ID};
+
+fn tx(seed: &str, nonce: u64, fee: u64) -> Tx { expiring(seed, nonce, fee, None) }
+
+fn expiring(seed: &str, nonce: u64, fee: u64, expiry: Option<Unlock>) -> Tx {
+    let (sk, pk) = crypto::keypair_from_seed(seed.as_bytes());
+    let t = Transfer { from: crypto::address(&pk), to: "bob".into(), amount: Amount(1), fee: Amount(fee), nonce, kind: TxKind::Transfer, asset: None, token_op: None, payload: None, expiry };
+    Tx::new(SignedTx::sign(t, &sk, DEFAULT_CHAIN_ID))
+}
+
+/// A nonce-0 transfer carrying `len` payload bytes.
+fn padded(seed: &str, fee: u64, len: usize) -> Tx {
+    let (sk, pk) = crypto::keypair_from_seed(seed.as_bytes());
+    let t = Transfer { from: crypto::address(&pk), to: "bob".into(), amount: Amount(1), fee: Amount(fee), nonce: 0, kind: TxKind::Transfer, asset: None, token_op: None, payload: Some(vec![0; len]), expiry: None };
+    Tx::new(SignedTx::sign(t, &sk, DEFAULT_CHAIN_ID))
+}
+
+/// Committed nonces the test sets by hand.
+#[derive(Default)]
+struct Nonces(Mutex<HashMap<String, u64>>);
+
+impl StateView for Nonces {
+    fn nonce(&self, addr: &str) -> u64 { self.0.lock().unwrap().get(addr).copied().unwrap_or(0) }
+    fn balance(&self, _: &str) -> Amount { Amount::MAX }
+    fn last_height(&self) -> u64 { 0 }
+    fn auth(&self, _: &str) -> Option<AuthPolicy> { None }
+}
+
+fn pool() -> (Pool, Arc<Nonces>) {
+    let committed = Arc::new(Nonces::default());
+    (Pool::new(committed.clone(), Duration::from_secs(60)), committed)
+}
+
+fn nonces(txs: &[Tx]) -> Vec<u64> { txs.iter().map(|t| t.signed.transfer.nonce).collect() }
+
+fn fees(txs: &[Tx]) -> Vec<u64> { txs.iter().map(|t| t.signed.transfer.fee.0).collect() }
+
+#[test]
+fn duplicates_are_dropped() {
+    let (mut pool, _) = pool();
+    let t = tx("alice", 0, 1);
+    assert!(pool.insert(t.clone()).is_ok());
+    assert_eq!(pool.insert(t).unwrap_err(), Dropped::Duplicate);
+    assert_eq!(pool.insert(tx("alice", 0, 1)).unwrap_err(), Dropped::Duplicate);
+    assert_eq!(pool.len(), 1);
+    assert_eq!(pool.take_ready(10, usize::MAX).len(), 1);
+}
+
+#[test]
+fn only_contiguous_nonces_are_released() {
+    let (mut pool, committed) = pool();
+    let alice = tx("alice", 0, 1).signed.transfer.from;
+    for n in [2, 0, 4] { pool.insert(tx("alice", n, 1)).unwrap(); }
+    assert_eq!(nonces(&pool.take_ready(10, usize::MAX)), vec![0]);
+    pool.insert(tx("alice", 1, 1)).unwrap();
+    // 1 follows the released 0 even before it commits; 4 waits for 3.
+    assert_eq!(nonces(&pool.take_ready(10, usize::MAX)), vec![1, 2]);
+    assert!(pool.take_ready(10, usize::MAX).is_empty());
+    assert_eq!(pool.len(), 1);
+
+    committed.0.lock().unwrap().insert(alice, 3);
+    assert_eq!(pool.insert(tx("alice", 2, 9)).unwrap_err(), Dropped::StaleNonce);
+    pool.insert(tx("alice", 3, 1)).unwrap();
+    assert_eq!(nonces(&pool.take_ready(1, usize::MAX)), vec![3]);
+    assert_eq!(nonces(&pool.take_ready(1, usize::MAX)), vec![4]);
+    assert!(pool.is_empty());
+}
+
+#[test]
+fn a_rejected_nonce_can_be_resubmitted() {
+    let (mut pool, _) = pool();
+    pool.insert(tx("alice", 0, 1)).unwrap();
+    assert_eq!(nonces(&pool.take_ready(10, usize::MAX)), vec![0]);
+    // Nonce 0 was rejected in execution, so committed state is still at 0.
+    pool.insert(tx("alice", 0, 2)).unwrap();
+    assert_eq!(nonces(&pool.take_ready(10, usize::MAX)), vec![0]);
+}
+
+#[test]
+fn batches_go_by_fee_one_tx_per_sender_per_round() {
+    let (mut pool, _) = pool();
+    for n in 0..3 { pool.insert(tx("whale", n, 100)).unwrap(); }
+    pool.insert(tx("alice", 0, 5)).unwrap();
+    pool.insert(tx("bob", 0, 10)).unwrap();
+    assert_eq!(fees(&pool.take_ready(4, usize::MAX)), vec![100, 10, 5, 100]);
+    assert_eq!(fees(&pool.take_ready(4, usize::MAX)), vec![100]);
+}
+
+#[test]
+fn batches_stop_at_the_byte_limit() {
+    let (mut pool, _) = pool();
+    let one = tx("alice", 0, 1).signed.encoded_len();
+    for n in 0..3 { pool.insert(tx("alice", n, 1)).unwrap(); }
+    assert_eq!(nonces(&pool.take_ready(10, 2 * one)), vec![0, 1]);
+    // A tx over the limit on its own still goes, alone.
+    assert_eq!(nonces(&pool.take_ready(10, 1)), vec![2]);
+}
+
+#[test]
+fn a_higher_fee_replaces_a_pending_nonce() {
+    let (mut pool, _) = pool();
+    let first = tx("alice", 0, 100);
+    pool.insert(first.clone()).unwrap();
+    assert_eq!(pool.insert(tx("alice", 0, 100)).unwrap_err(), Dropped::Duplicate);
+    assert_eq!(pool.insert(tx("alice", 0, 109)).unwrap_err(), Dropped::Underpriced);
+    let a = pool.insert(tx("alice", 0, 110)).unwrap();
+    assert_eq!(a.replaced.map(|t| t.id), Some(first.id));
+    assert_eq!(pool.len(), 1);
+    assert_eq!(fees(&pool.take_ready(10, usize::MAX)), vec![110]);
+}
+
+#[test]
+fn a_full_pool_evicts_the_lowest_fee_per_byte() {
+    let (pool, _) = pool();
+    let mut pool = pool.with_limits(PoolLimits { max_txs: 3, max_bytes: 1 << 20 });
+    pool.insert(tx("alice", 0, 1)).unwrap();
+    pool.insert(tx("alice", 1, 50)).unwrap();
+    pool.insert(tx("bob", 0, 20)).unwrap();
+    assert_eq!(pool.insert(tx("carol", 0, 1)).unwrap_err(), Dropped::Full);
+
+    // Evicting alice's nonce 0 takes nonce 1 with it, which could never run without it.
+    let a = pool.insert(tx("carol", 0, 10)).unwrap();
+    assert_eq!(nonces(&a.evicted), vec![1, 0]);
+    assert_eq!(pool.len(), 2);
+    assert_eq!(fees(&pool.take_ready(10, usize::MAX)), vec![20, 10]);
+
+    let (pool, _) = self::pool();
+    let one = tx("alice", 0, 1).signed.encoded_len();
+    let mut pool = pool.with_limits(PoolLimits { max_txs: 100, max_bytes: 2 * one });
+    pool.insert(tx("alice", 0, 1)).unwrap();
+    pool.insert(tx("bob", 0, 2)).unwrap();
+    assert_eq!(fees(&pool.insert(tx("carol", 0, 3)).unwrap().evicted), vec![1]);
+    assert!(pool.bytes() <= 2 * one);
+}
+
+#[test]
+fn a_tx_too_big_to_fit_evicts_nothing() {
+    let (pool, _) = pool();
+    let one = tx("alice", 0, 1).signed.encoded_len();
+    let mut pool = pool.with_limits(PoolLimits { max_txs: 100, max_bytes: 3 * one });
+    pool.insert(tx("alice", 0, 1)).unwrap();
+    pool.insert(tx("bob", 0, 100)).unwrap();
+    // Pays more per byte than alice but less than bob, and only fits with both gone.
+    assert_eq!(pool.insert(padded("carol", 20, one)).unwrap_err(), Dropped::Full);
+    assert_eq!(pool.len(), 2);
+    assert_eq!(fees(&pool.take_ready(10, usize::MAX)), vec![100, 1]);
+}
+
+#[test]
+fn txs_dropped_for_room_are_not_remembered() {
+    let (pool, _) = pool();
+    let mut pool = pool.with_limits(PoolLimits { max_txs: 1, max_bytes: 1 << 20 });
+    pool.insert(tx("alice", 0, 5)).unwrap();
+    let late = tx("bob", 0, 1);
+    assert_eq!(pool.insert(late.clone()).unwrap_err(), Dropped::Full);
+    assert_eq!(pool.take_ready(10, usize::MAX).len(), 1);
+    // Not a duplicate: the first copy never made it in.
+    pool.insert(late.clone()).unwrap();
+    assert_eq!(pool.insert(late).unwrap_err(), Dropped::Duplicate);
+}
+
+/// Ids of the txs the pool reported expired.
+#[derive(Default)]
+struct Expired(Mutex<Vec<TxId>>);
+
+impl PoolObserver for Expired {
+    fn evicted(&self, _: &Tx) {}
+    fn expired(&self, tx: &Tx) { self.0.lock().unwrap().push(tx.id); }
+}
+
+#[test]
+fn expired_txs_are_dropped_and_reported() {
+    let (pool, _) = pool();
+    let seen = Arc::new(Expired::default());
+    let mut pool = pool.with_observer(seen.clone());
+    let by_height = expiring("alice", 0, 1, Some(Unlock::Height(0)));
+    let by_time = expiring("bob", 0, 1, Some(Unlock::Time(1_000)));
+    pool.insert(by_height.clone()).unwrap();
+    pool.insert(by_time.clone()).unwrap();
+    pool.insert(expiring("carol", 0, 1, Some(Unlock::Height(1)))).unwrap();
+    pool.insert(tx("dave", 0, 1)).unwrap();
+
+    // The next block is 1, past alice's expiry but not carol's.
+    assert_eq!(pool.expire(1_000).len(), 1);
+    assert_eq!(pool.expire(1_001).len(), 1);
+    assert_eq!(*seen.0.lock().unwrap(), vec![by_height.id, by_time.id]);
+    assert_eq!(pool.len(), 2);
+}
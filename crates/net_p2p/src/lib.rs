@@ -36,7 +36,7 @@ pub async fn build_swarm() -> anyhow::Result<libp2p::Swarm<NodeBehaviour>> {
                 .validation_mode(gossipsub::ValidationMode::Strict)
                 .build()
                 .map_err(|e| -> Box<dyn StdError + Send + Sync> {
-                    Box::new(std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))
+                    Box::new(std::io::Error::other(e.to_string()))
                 })?;
 
             let gs = gossipsub::Behaviour::new(
@@ -44,7 +44,7 @@ pub async fn build_swarm() -> anyhow::Result<libp2p::Swarm<NodeBehaviour>> {
                 gossip_cfg,
             )
             .map_err(|e| -> Box<dyn StdError + Send + Sync> {
-                Box::new(std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))
+                Box::new(std::io::Error::other(e.to_string()))
             })?;
 
             let mdns_beh = mdns::tokio::Behaviour::new(mdns::Config::default(), pid)
@@ -79,7 +79,7 @@ pub async fn spawn_p2p(bind_addr: &str, topic: &str, bootstrap: Vec<Multiaddr>)
     let mut swarm = build_swarm().await?;
 
     // Bind TCP listen (QUIC not wired here)
-    let listen_addr: Multiaddr = format!("{bind_addr}").parse()?;
+    let listen_addr: Multiaddr = bind_addr.parse()?;
     if let Err(e) = swarm.listen_on(listen_addr.clone()) {
         warn!("listen_on {listen_addr} failed: {e}");
     }
@@ -114,13 +114,8 @@ pub async fn spawn_p2p(bind_addr: &str, topic: &str, bootstrap: Vec<Multiaddr>)
                     }
                 }
                 event = swarm.select_next_some() => {
-                    if let SwarmEvent::Behaviour(ev) = event {
-                        match ev {
-                            NodeBehaviourEvent::Gossipsub(gossipsub::Event::Message { message, .. }) => {
-                                let _ = tx_inbound.send(message.data).await;
-                            }
-                            _ => { /* ignore */ }
-                        }
+                    if let SwarmEvent::Behaviour(NodeBehaviourEvent::Gossipsub(gossipsub::Event::Message { message, .. })) = event {
+                        let _ = tx_inbound.send(message.data).await;
                     }
                 }
             }
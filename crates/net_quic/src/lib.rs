@@ -43,34 +43,31 @@ pub async fn spawn_quic_server(bind: &str) -> anyhow::Result<(QuicHandle, mpsc::
         let ep = endpoint.clone();
         let tx_events_accept = tx_events.clone();
         tokio::spawn(async move {
-            loop {
-                match ep.accept().await {
-                    Some(connecting) => match connecting.await {
-                        Ok(conn) => {
-                            let remote = conn.remote_address();
-                            let _ = tx_events_accept.send(QuicEvent::Connected { remote }).await;
-                            let tx_stream = tx_events_accept.clone();
-                            tokio::spawn(async move {
-                                loop {
-                                    match conn.accept_bi().await {
-                                        Ok((mut send, mut recv)) => {
-                                            while let Ok(Some(chunk)) = recv.read_chunk(usize::MAX, true).await {
-                                                let data = chunk.bytes.to_vec();
-                                                let _ = tx_stream.send(QuicEvent::Received { remote, data }).await;
-                                            }
-                                            let _ = send.finish();
-                                        }
-                                        Err(_) => {
-                                            let _ = tx_stream.send(QuicEvent::Closed { remote }).await;
-                                            break;
+            while let Some(connecting) = ep.accept().await {
+                match connecting.await {
+                    Ok(conn) => {
+                        let remote = conn.remote_address();
+                        let _ = tx_events_accept.send(QuicEvent::Connected { remote }).await;
+                        let tx_stream = tx_events_accept.clone();
+                        tokio::spawn(async move {
+                            loop {
+                                match conn.accept_bi().await {
+                                    Ok((mut send, mut recv)) => {
+                                        while let Ok(Some(chunk)) = recv.read_chunk(usize::MAX, true).await {
+                                            let data = chunk.bytes.to_vec();
+                                            let _ = tx_stream.send(QuicEvent::Received { remote, data }).await;
                                         }
+                                        let _ = send.finish();
+                                    }
+                                    Err(_) => {
+                                        let _ = tx_stream.send(QuicEvent::Closed { remote }).await;
+                                        break;
                                     }
                                 }
-                            });
-                        }
-                        Err(e) => warn!("QUIC accept (handshake) error: {e}"),
-                    },
-                    None => break,
+                            }
+                        });
+                    }
+                    Err(e) => warn!("QUIC accept (handshake) error: {e}"),
                 }
             }
         });
@@ -11,8 +11,8 @@ fn main() {
         // Update legacy :param to {param} for axum >= 0.8
         if s.contains(r#""/balance/:addr""#) {
             s = s.replace(r#""/balance/:addr""#, r#""/balance/{addr}""#);
-            // Print a cargo warning (avoid formatting braces by using a single placeholder)
-            println!("cargo:warning={}", "rpc hotfix: changed \"/balance/:addr\" to \"/balance/{addr}\"");
+            // Print a cargo warning (braces escaped for println!)
+            println!("cargo:warning=rpc hotfix: changed \"/balance/:addr\" to \"/balance/{{addr}}\"");
         }
 
         if s != before {
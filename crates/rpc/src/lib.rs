@@ -1,31 +1,89 @@
 use anyhow::Result;
 use axum::{
     routing::{get, post},
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
     Json, Router,
 };
-use serde::{Deserialize, Serialize};
+use serde::{Serialize, Deserialize};
 use tracing::info;
 
-use types::Receipt;
+use types::{Receipt, SignedTx, TxId, TxKind, TxState};
+use da::{Shard, sampling::Availability};
+use mempool::{Dropped, MempoolError};
 
-/// Minimal transfer request the RPC accepts.
+/// API trait the node must implement.
+#[async_trait::async_trait]
+pub trait NodeApi: Send + Sync + 'static + Clone {
+    async fn submit_transfer(&self, t: SignedTx) -> Result<Receipt>;
+    async fn simulate(&self, txs: Vec<SignedTx>) -> Result<Vec<Receipt>>;
+    async fn get_balance(&self, addr: String) -> Result<types::Amount>;
+    async fn get_asset_balance(&self, addr: String, asset: String) -> Result<types::Amount>;
+    async fn get_token(&self, asset: String) -> Result<Option<exec::TokenInfo>>;
+    async fn get_account(&self, addr: String) -> Result<Option<exec::AccountState>>;
+    async fn get_nonce(&self, addr: String) -> Result<u64>;
+    async fn get_da_shard(&self, root: [u8; 32], index: u32) -> Result<Option<Shard>>;
+    async fn get_da_availability(&self, height: u64) -> Result<Option<Availability>>;
+    async fn get_da_commit(&self, height: u64) -> Result<Option<DaCommit>>;
+    async fn get_state_root(&self) -> Result<StateRoot>;
+    async fn get_account_proof(&self, addr: String) -> Result<Option<AccountProof>>;
+    async fn get_events(&self, account: String, from: exec::EventCursor, limit: usize) -> Result<exec::EventPage>;
+    async fn get_locks(&self, addr: String) -> Result<Vec<exec::PendingLock>>;
+    async fn get_tx(&self, id: TxId) -> Result<Option<TxState>>;
+}
+
+/// Most transactions one `POST /simulate` call may execute.
+pub const MAX_SIMULATE_TXS: usize = 1_000;
+
+/// Most events `GET /events` returns at once; page by passing back the `next` cursor as
+/// `from_height`, `from_tx` and `from_event`.
+pub const MAX_EVENTS: usize = 1_000;
+
+#[derive(Debug, Deserialize)]
+struct EventsQuery {
+    account: String,
+    #[serde(default)]
+    from_height: u64,
+    #[serde(default)]
+    from_tx: u32,
+    #[serde(default)]
+    from_event: u32,
+    limit: Option<usize>,
+}
+
+/// Executor state root after the last executed height.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct TransferReq {
-    pub from: String,
-    pub to: String,
-    #[serde(default = "one")]
-    pub amount: u64,
+pub struct StateRoot {
+    pub height: u64,
+    #[serde(with = "hex::serde")]
+    pub root: [u8; 32],
 }
 
-fn one() -> u64 { 1 }
+/// Payload root committed at `commit.height`, with the QC that committed it; `qc.root == commit.root`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DaCommit {
+    pub commit: da::CommittedRoot,
+    pub qc: consensus::QuorumCert,
+}
 
-/// API trait the node must implement.
-#[async_trait::async_trait]
-pub trait NodeApi: Send + Sync + 'static + Clone {
-    async fn submit_transfer(&self, t: TransferReq) -> Result<Receipt>;
-    async fn get_balance(&self, addr: String) -> Result<u64>;
+/// `state` proves the account against the root certified by `qc.state`. Clients check
+/// `state.verify(addr)`, `qc.verify(validators)` and that `qc.state` matches `state.height`/`state.root`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountProof {
+    pub state: exec::StateProof,
+    pub qc: consensus::QuorumCert,
+}
+
+/// 429 when the mempool is full or the fee too low to get in, so clients back off or bump it;
+/// 400 when the tx failed admission or was already pending or used.
+fn submit_error(e: anyhow::Error) -> (StatusCode, String) {
+    let status = match e.downcast_ref::<MempoolError>() {
+        Some(MempoolError::Full | MempoolError::Dropped(Dropped::Full | Dropped::Underpriced)) => StatusCode::TOO_MANY_REQUESTS,
+        Some(MempoolError::Invalid(_) | MempoolError::Dropped(Dropped::Duplicate | Dropped::StaleNonce)) => StatusCode::BAD_REQUEST,
+        Some(MempoolError::Closed) => StatusCode::SERVICE_UNAVAILABLE,
+        None => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (status, e.to_string())
 }
 
 #[derive(Clone)]
@@ -33,19 +91,53 @@ struct RpcState<A: NodeApi> {
     api: A,
 }
 
-pub async fn serve<A, E>(addr: &str, api: A, _executor: E) -> Result<()>
+/// Serves [`router`] on `addr` until `shutdown` resolves, then lets in-flight requests finish.
+pub async fn serve<A, E, F>(addr: &str, api: A, _executor: E, shutdown: F) -> Result<()>
 where
     A: NodeApi,
     E: Send + Sync + 'static,
+    F: std::future::Future<Output = ()> + Send + 'static,
 {
+    let app = router(api);
+    info!("rpc: listening on {}", addr);
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    axum::serve(listener, app).with_graceful_shutdown(shutdown).await?;
+    Ok(())
+}
+
+/// Every RPC route, served by `api`.
+pub fn router<A: NodeApi>(api: A) -> Router {
     let state = RpcState { api };
 
     // Use closures so axum can infer Handler bounds on 0.8 cleanly.
-    let app = Router::new()
+    Router::new()
         .route("/healthz", get(|| async { "ok" }))
-        .route("/transfer", post(|State(state): State<RpcState<A>>, Json(req): Json<TransferReq>| async move {
+        .route("/transfer", post(|State(state): State<RpcState<A>>, Json(req): Json<SignedTx>| async move {
+            if req.transfer.kind != TxKind::Transfer { return Err((StatusCode::BAD_REQUEST, "not a transfer; submit other kinds to /tx".to_string())); }
             match state.api.submit_transfer(req).await {
                 Ok(r) => Ok::<_, (StatusCode, String)>(Json(r)),
+                Err(e) => Err(submit_error(e)),
+            }
+        }))
+        .route("/tx", post(|State(state): State<RpcState<A>>, Json(req): Json<SignedTx>| async move {
+            match state.api.submit_transfer(req).await {
+                Ok(r) => Ok::<_, (StatusCode, String)>(Json(r)),
+                Err(e) => Err(submit_error(e)),
+            }
+        }))
+        .route("/tx/{id}", get(|State(state): State<RpcState<A>>, Path(id): Path<String>| async move {
+            let id: TxId = hex::decode(&id).ok().and_then(|b| b.try_into().ok())
+                .ok_or((StatusCode::BAD_REQUEST, "tx id must be 32 hex-encoded bytes".to_string()))?;
+            match state.api.get_tx(id).await {
+                Ok(Some(s)) => Ok::<_, (StatusCode, String)>(Json(s)),
+                Ok(None) => Err((StatusCode::NOT_FOUND, "unknown tx".to_string())),
+                Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+            }
+        }))
+        .route("/simulate", post(|State(state): State<RpcState<A>>, Json(txs): Json<Vec<SignedTx>>| async move {
+            if txs.len() > MAX_SIMULATE_TXS { return Err((StatusCode::BAD_REQUEST, format!("at most {MAX_SIMULATE_TXS} transactions per simulation"))); }
+            match state.api.simulate(txs).await {
+                Ok(rs) => Ok::<_, (StatusCode, String)>(Json(rs)),
                 Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
             }
         }))
@@ -55,10 +147,82 @@ where
                 Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
             }
         }))
-        .with_state(state);
-
-    info!("rpc: listening on {}", addr);
-    let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
-    Ok(())
+        .route("/balance/{addr}/{asset}", get(|State(state): State<RpcState<A>>, Path((addr, asset)): Path<(String, String)>| async move {
+            let res = if asset == types::NATIVE_ASSET { state.api.get_balance(addr).await } else { state.api.get_asset_balance(addr, asset).await };
+            match res {
+                Ok(b) => Ok::<_, (StatusCode, String)>(Json(b)),
+                Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+            }
+        }))
+        .route("/asset/{id}", get(|State(state): State<RpcState<A>>, Path(id): Path<String>| async move {
+            match state.api.get_token(id).await {
+                Ok(Some(t)) => Ok::<_, (StatusCode, String)>(Json(t)),
+                Ok(None) => Err((StatusCode::NOT_FOUND, "unknown asset".to_string())),
+                Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+            }
+        }))
+        .route("/nonce/{addr}", get(|State(state): State<RpcState<A>>, Path(addr): Path<String>| async move {
+            match state.api.get_nonce(addr).await {
+                Ok(n) => Ok::<_, (StatusCode, String)>(Json(n)),
+                Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+            }
+        }))
+        .route("/da/shard/{root}/{index}", get(|State(state): State<RpcState<A>>, Path((root, index)): Path<(String, u32)>| async move {
+            let root: [u8; 32] = hex::decode(&root).ok().and_then(|b| b.try_into().ok())
+                .ok_or((StatusCode::BAD_REQUEST, "root must be 32 hex-encoded bytes".to_string()))?;
+            match state.api.get_da_shard(root, index).await {
+                Ok(Some(s)) => Ok::<_, (StatusCode, String)>(Json(s)),
+                Ok(None) => Err((StatusCode::NOT_FOUND, "shard not held".to_string())),
+                Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+            }
+        }))
+        .route("/da/availability/{height}", get(|State(state): State<RpcState<A>>, Path(height): Path<u64>| async move {
+            match state.api.get_da_availability(height).await {
+                Ok(Some(a)) => Ok::<_, (StatusCode, String)>(Json(a)),
+                Ok(None) => Err((StatusCode::NOT_FOUND, "no availability sample for height".to_string())),
+                Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+            }
+        }))
+        .route("/da/commit/{height}", get(|State(state): State<RpcState<A>>, Path(height): Path<u64>| async move {
+            match state.api.get_da_commit(height).await {
+                Ok(Some(c)) => Ok::<_, (StatusCode, String)>(Json(c)),
+                Ok(None) => Err((StatusCode::NOT_FOUND, "no commit retained for height".to_string())),
+                Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+            }
+        }))
+        .route("/state/root", get(|State(state): State<RpcState<A>>| async move {
+            match state.api.get_state_root().await {
+                Ok(r) => Ok::<_, (StatusCode, String)>(Json(r)),
+                Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+            }
+        }))
+        .route("/account/{addr}", get(|State(state): State<RpcState<A>>, Path(addr): Path<String>| async move {
+            match state.api.get_account(addr).await {
+                Ok(Some(a)) => Ok::<_, (StatusCode, String)>(Json(a)),
+                Ok(None) => Err((StatusCode::NOT_FOUND, "unknown account".to_string())),
+                Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+            }
+        }))
+        .route("/account/{addr}/proof", get(|State(state): State<RpcState<A>>, Path(addr): Path<String>| async move {
+            match state.api.get_account_proof(addr).await {
+                Ok(Some(p)) => Ok::<_, (StatusCode, String)>(Json(p)),
+                Ok(None) => Err((StatusCode::NOT_FOUND, "no certified state root retained".to_string())),
+                Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+            }
+        }))
+        .route("/events", get(|State(state): State<RpcState<A>>, Query(q): Query<EventsQuery>| async move {
+            let limit = q.limit.unwrap_or(MAX_EVENTS).min(MAX_EVENTS);
+            let from = exec::EventCursor { height: q.from_height, tx: q.from_tx, event: q.from_event };
+            match state.api.get_events(q.account, from, limit).await {
+                Ok(evs) => Ok::<_, (StatusCode, String)>(Json(evs)),
+                Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+            }
+        }))
+        .route("/locks/{addr}", get(|State(state): State<RpcState<A>>, Path(addr): Path<String>| async move {
+            match state.api.get_locks(addr).await {
+                Ok(locks) => Ok::<_, (StatusCode, String)>(Json(locks)),
+                Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+            }
+        }))
+        .with_state(state)
 }
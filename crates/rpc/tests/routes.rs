@@ -0,0 +1,93 @@
+use anyhow::Result;
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use da::{sampling::Availability, Shard};
+use mempool::{spawn_mempool, Admission, MempoolConfig, MempoolHandle, PoolLimits, StateView};
+use rpc::{AccountProof, DaCommit, NodeApi, StateRoot};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tower::ServiceExt;
+use types::{Amount, AuthPolicy, Receipt, SignedTx, Status, Transfer, Tx, TxId, TxKind, TxState, DEFAULT_CHAIN_ID};
+
+/// Serves fixed account nonces and pools submissions in `mempool`, answering as if they
+/// committed at once; everything else is empty.
+#[derive(Clone, Default)]
+struct Fake { nonces: Arc<HashMap<String, u64>>, mempool: Option<MempoolHandle> }
+
+#[async_trait::async_trait]
+impl NodeApi for Fake {
+    async fn submit_transfer(&self, t: SignedTx) -> Result<Receipt> {
+        let Some(mempool) = &self.mempool else { anyhow::bail!("not accepting") };
+        let tx = Tx::new(t);
+        let tx_id = tx.id;
+        mempool.try_enqueue(tx).await?;
+        Ok(Receipt { tx_id, status: Status::Committed, block_height: 1, latency_ms: 0, fee_paid: Amount(0), output: None, logs: Vec::new(), result: None, events: Vec::new() })
+    }
+    async fn simulate(&self, _: Vec<SignedTx>) -> Result<Vec<Receipt>> { Ok(Vec::new()) }
+    async fn get_balance(&self, _: String) -> Result<Amount> { Ok(Amount(0)) }
+    async fn get_asset_balance(&self, _: String, _: String) -> Result<Amount> { Ok(Amount(0)) }
+    async fn get_token(&self, _: String) -> Result<Option<exec::TokenInfo>> { Ok(None) }
+    async fn get_account(&self, _: String) -> Result<Option<exec::AccountState>> { Ok(None) }
+    async fn get_nonce(&self, addr: String) -> Result<u64> { Ok(self.nonces.get(&addr).copied().unwrap_or(0)) }
+    async fn get_da_shard(&self, _: [u8; 32], _: u32) -> Result<Option<Shard>> { Ok(None) }
+    async fn get_da_availability(&self, _: u64) -> Result<Option<Availability>> { Ok(None) }
+    async fn get_da_commit(&self, _: u64) -> Result<Option<DaCommit>> { Ok(None) }
+    async fn get_state_root(&self) -> Result<StateRoot> { Ok(StateRoot { height: 0, root: [0; 32] }) }
+    async fn get_account_proof(&self, _: String) -> Result<Option<AccountProof>> { Ok(None) }
+    async fn get_events(&self, _: String, _: exec::EventCursor, _: usize) -> Result<exec::EventPage> { Ok(exec::EventPage { events: Vec::new(), next: None }) }
+    async fn get_locks(&self, _: String) -> Result<Vec<exec::PendingLock>> { Ok(Vec::new()) }
+    async fn get_tx(&self, _: TxId) -> Result<Option<TxState>> { Ok(None) }
+}
+
+async fn call(api: Fake, req: Request<Body>) -> (StatusCode, Vec<u8>) {
+    let resp = rpc::router(api).oneshot(req).await.unwrap();
+    let status = resp.status();
+    (status, axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap().to_vec())
+}
+
+async fn get(api: Fake, uri: &str) -> (StatusCode, Vec<u8>) { call(api, Request::get(uri).body(Body::empty()).unwrap()).await }
+
+async fn post(api: Fake, uri: &str, body: &impl serde::Serialize) -> StatusCode {
+    let req = Request::post(uri).header("content-type", "application/json").body(Body::from(serde_json::to_vec(body).unwrap())).unwrap();
+    call(api, req).await.0
+}
+
+#[tokio::test]
+async fn nonce_route_reports_the_next_nonce() {
+    let api = Fake { nonces: Arc::new(HashMap::from([("alice".to_string(), 7)])), ..Fake::default() };
+    assert_eq!(get(api.clone(), "/nonce/alice").await, (StatusCode::OK, b"7".to_vec()));
+    assert_eq!(get(api, "/nonce/nobody").await, (StatusCode::OK, b"0".to_vec()));
+}
+
+/// Every account is fresh and rich.
+struct Open;
+
+impl StateView for Open {
+    fn nonce(&self, _: &str) -> u64 { 0 }
+    fn balance(&self, _: &str) -> Amount { Amount::MAX }
+    fn last_height(&self) -> u64 { 0 }
+    fn auth(&self, _: &str) -> Option<AuthPolicy> { None }
+}
+
+fn signed(seed: &str, amount: u64) -> SignedTx {
+    let (sk, pk) = crypto::keypair_from_seed(seed.as_bytes());
+    let t = Transfer { from: crypto::address(&pk), to: "bob".into(), amount: Amount(amount), fee: Amount(0), nonce: 0, kind: TxKind::Transfer, asset: None, token_op: None, payload: None, expiry: None };
+    SignedTx::sign(t, &sk, DEFAULT_CHAIN_ID)
+}
+
+#[tokio::test]
+async fn pool_verdicts_map_to_status_codes() {
+    let state = Arc::new(Open);
+    let config = MempoolConfig { flush_interval: Duration::from_secs(3600), limits: PoolLimits { max_txs: 1, ..PoolLimits::default() }, ..MempoolConfig::default() };
+    let mempool = spawn_mempool(config.clone(), Admission::new(DEFAULT_CHAIN_ID, state.clone()), config.pool(state), mpsc::channel(1).0, None);
+    let api = Fake { mempool: Some(mempool), ..Fake::default() };
+
+    assert_eq!(post(api.clone(), "/transfer", &signed("alice", 1)).await, StatusCode::OK);
+    // The pool holds one tx, and another at the same fee can't push it out.
+    assert_eq!(post(api.clone(), "/transfer", &signed("carol", 1)).await, StatusCode::TOO_MANY_REQUESTS);
+    // Same nonce without a fee bump.
+    assert_eq!(post(api.clone(), "/transfer", &signed("alice", 2)).await, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(post(api, "/transfer", &signed("alice", 1)).await, StatusCode::BAD_REQUEST);
+}
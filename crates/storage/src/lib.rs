@@ -1,11 +1,35 @@
 
-use parking_lot::RwLock;
-use std::collections::HashMap;
+use parking_lot::{Mutex, RwLock};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "rocksdb")]
+mod rocks;
+#[cfg(feature = "rocksdb")]
+pub use rocks::RocksKv;
+
+/// Puts (`Some`) and deletes (`None`) applied all-or-nothing by [`Kv::write_batch`].
+#[derive(Debug, Clone, Default)]
+pub struct WriteBatch { pub ops: Vec<(Vec<u8>, Option<Vec<u8>>)> }
+impl WriteBatch {
+    pub fn new() -> Self { Self::default() }
+    pub fn put(&mut self, key: &[u8], value: Vec<u8>) { self.ops.push((key.to_vec(), Some(value))); }
+    pub fn delete(&mut self, key: &[u8]) { self.ops.push((key.to_vec(), None)); }
+    pub fn is_empty(&self) -> bool { self.ops.is_empty() }
+}
 
 pub trait Kv: Send + Sync + 'static {
     fn get(&self, key: &[u8]) -> Option<Vec<u8>>;
     fn put(&self, key: &[u8], value: Vec<u8>);
     fn delete(&self, key: &[u8]);
+    /// Applies every op or none of them, even across a crash.
+    fn write_batch(&self, batch: WriteBatch) -> anyhow::Result<()>;
+    /// All entries whose key starts with `prefix`, in key order.
+    fn scan_prefix(&self, prefix: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)>;
+    /// Up to `limit` entries whose key starts with `prefix` and is at least `start`, in key order.
+    fn scan_from(&self, prefix: &[u8], start: &[u8], limit: usize) -> Vec<(Vec<u8>, Vec<u8>)>;
 }
 
 #[derive(Default)]
@@ -15,4 +39,149 @@ impl Kv for InMemoryKv {
     fn get(&self, key: &[u8]) -> Option<Vec<u8>> { self.inner.read().get(key).cloned() }
     fn put(&self, key: &[u8], value: Vec<u8>) { self.inner.write().insert(key.to_vec(), value); }
     fn delete(&self, key: &[u8]) { self.inner.write().remove(key); }
+    fn write_batch(&self, batch: WriteBatch) -> anyhow::Result<()> {
+        let mut w = self.inner.write();
+        for (k, v) in batch.ops { match v { Some(v) => { w.insert(k, v); } None => { w.remove(&k); } } }
+        Ok(())
+    }
+    fn scan_prefix(&self, prefix: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let mut out: Vec<_> = self.inner.read().iter().filter(|(k, _)| k.starts_with(prefix)).map(|(k, v)| (k.clone(), v.clone())).collect();
+        out.sort();
+        out
+    }
+    fn scan_from(&self, prefix: &[u8], start: &[u8], limit: usize) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let mut out: Vec<_> = self.inner.read().iter().filter(|(k, _)| k.starts_with(prefix) && k.as_slice() >= start).map(|(k, v)| (k.clone(), v.clone())).collect();
+        out.sort();
+        out.truncate(limit);
+        out
+    }
+}
+
+/// Append-only log of write batches, replayed into memory on open.
+///
+/// Each record is `len | blake3(payload) | payload` and is fsynced before the batch becomes
+/// visible, so a crash loses at most the batch being written. A torn or corrupt tail is cut off
+/// on the next open. The log is compacted to a single snapshot record on open, and again
+/// whenever it grows past its compaction threshold.
+///
+/// Every live entry is held in memory, so this suits state that fits in RAM; larger stores
+/// should use `RocksKv` (the `rocksdb` feature).
+pub struct FileKv { path: PathBuf, data: RwLock<BTreeMap<Vec<u8>, Vec<u8>>>, log: Mutex<Log> }
+
+/// Default for [`FileKv::with_compact_threshold`].
+pub const COMPACT_MIN_BYTES: u64 = 64 << 20;
+
+struct Log {
+    file: File,
+    /// End of the last record known to be whole; a failed append is cut back to here.
+    len: u64,
+    /// Size of the last snapshot; the log is compacted once it doubles and passes `min_compact`.
+    snapshot: u64,
+    min_compact: u64,
+    /// Set if a failed append couldn't be cut off, after which nothing more may be appended.
+    broken: bool,
+}
+
+impl FileKv {
+    pub fn open<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(dir) = path.parent() { std::fs::create_dir_all(dir)?; }
+        let mut data = BTreeMap::new();
+        if let Ok(mut f) = File::open(&path) {
+            let mut buf = Vec::new();
+            f.read_to_end(&mut buf)?;
+            let mut rest = buf.as_slice();
+            while let Some((batch, tail)) = decode_record(rest) {
+                for (k, v) in batch.ops { match v { Some(v) => { data.insert(k, v); } None => { data.remove(&k); } } }
+                rest = tail;
+            }
+        }
+        // Rewriting the live entries also drops any torn tail.
+        let snapshot = write_snapshot(&path, &data)?;
+        let file = OpenOptions::new().append(true).open(&path)?;
+        let log = Log { file, len: snapshot, snapshot, min_compact: COMPACT_MIN_BYTES, broken: false };
+        Ok(Self { path, data: RwLock::new(data), log: Mutex::new(log) })
+    }
+    /// Compacts the log only once it's at least `bytes` long (and twice the last snapshot).
+    pub fn with_compact_threshold(self, bytes: u64) -> Self { self.log.lock().min_compact = bytes; self }
+    pub fn path(&self) -> &Path { &self.path }
+
+    fn compact(&self, log: &mut Log) -> anyhow::Result<()> {
+        let snapshot = write_snapshot(&self.path, &self.data.read())?;
+        log.file = OpenOptions::new().append(true).open(&self.path)?;
+        log.len = snapshot;
+        log.snapshot = snapshot;
+        Ok(())
+    }
+}
+
+/// Replaces the log at `path` with one record holding `data`, returning its size.
+fn write_snapshot(path: &Path, data: &BTreeMap<Vec<u8>, Vec<u8>>) -> anyhow::Result<u64> {
+    let tmp = path.with_extension("compact");
+    let rec = encode_record(&WriteBatch { ops: data.iter().map(|(k, v)| (k.clone(), Some(v.clone()))).collect() })?;
+    {
+        let mut f = File::create(&tmp)?;
+        f.write_all(&rec)?;
+        f.sync_all()?;
+    }
+    std::fs::rename(&tmp, path)?;
+    // The rename itself is only durable once the directory is.
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) { File::open(dir)?.sync_all()?; }
+    Ok(rec.len() as u64)
+}
+
+fn encode_record(batch: &WriteBatch) -> anyhow::Result<Vec<u8>> {
+    let payload = bincode::serialize(&batch.ops)?;
+    let mut rec = Vec::with_capacity(4 + 32 + payload.len());
+    rec.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    rec.extend_from_slice(blake3::hash(&payload).as_bytes());
+    rec.extend_from_slice(&payload);
+    Ok(rec)
+}
+
+fn decode_record(buf: &[u8]) -> Option<(WriteBatch, &[u8])> {
+    let len = u32::from_le_bytes(buf.get(..4)?.try_into().ok()?) as usize;
+    let sum = buf.get(4..36)?;
+    let payload = buf.get(36..36 + len)?;
+    if blake3::hash(payload).as_bytes() != sum { return None; }
+    let ops = bincode::deserialize(payload).ok()?;
+    Some((WriteBatch { ops }, &buf[36 + len..]))
+}
+
+impl Kv for FileKv {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> { self.data.read().get(key).cloned() }
+    fn put(&self, key: &[u8], value: Vec<u8>) {
+        let mut b = WriteBatch::new(); b.put(key, value);
+        self.write_batch(b).expect("FileKv put");
+    }
+    fn delete(&self, key: &[u8]) {
+        let mut b = WriteBatch::new(); b.delete(key);
+        self.write_batch(b).expect("FileKv delete");
+    }
+    fn write_batch(&self, batch: WriteBatch) -> anyhow::Result<()> {
+        let rec = encode_record(&batch)?;
+        let mut log = self.log.lock();
+        if log.broken { anyhow::bail!("{} has a partial record that couldn't be cut off", self.path.display()); }
+        if let Err(e) = log.file.write_all(&rec).and_then(|()| log.file.sync_data()) {
+            // Later records must not land behind a partial one, or the next open stops there.
+            let len = log.len;
+            if log.file.set_len(len).and_then(|()| log.file.sync_data()).is_err() { log.broken = true; }
+            return Err(e.into());
+        }
+        log.len += rec.len() as u64;
+        let mut w = self.data.write();
+        for (k, v) in batch.ops { match v { Some(v) => { w.insert(k, v); } None => { w.remove(&k); } } }
+        drop(w);
+        if log.len >= log.min_compact.max(log.snapshot.saturating_mul(2)) {
+            // The batch is already durable; a failed compaction leaves the log as it was.
+            if let Err(e) = self.compact(&mut log) { tracing::warn!(error = %e, path = %self.path.display(), "log compaction failed"); }
+        }
+        Ok(())
+    }
+    fn scan_prefix(&self, prefix: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)> {
+        self.data.read().range(prefix.to_vec()..).take_while(|(k, _)| k.starts_with(prefix)).map(|(k, v)| (k.clone(), v.clone())).collect()
+    }
+    fn scan_from(&self, prefix: &[u8], start: &[u8], limit: usize) -> Vec<(Vec<u8>, Vec<u8>)> {
+        self.data.read().range(prefix.max(start).to_vec()..).take_while(|(k, _)| k.starts_with(prefix)).take(limit).map(|(k, v)| (k.clone(), v.clone())).collect()
+    }
 }
@@ -0,0 +1,42 @@
+
+use super::{Kv, WriteBatch};
+use std::path::Path;
+
+pub struct RocksKv { db: rocksdb::DB }
+
+impl RocksKv {
+    pub fn open<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let mut opts = rocksdb::Options::default();
+        opts.create_if_missing(true);
+        Ok(Self { db: rocksdb::DB::open(&opts, path)? })
+    }
+}
+
+impl Kv for RocksKv {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> { self.db.get(key).ok().flatten() }
+    fn put(&self, key: &[u8], value: Vec<u8>) { self.db.put(key, value).expect("rocksdb put"); }
+    fn delete(&self, key: &[u8]) { self.db.delete(key).expect("rocksdb delete"); }
+    fn write_batch(&self, batch: WriteBatch) -> anyhow::Result<()> {
+        let mut wb = rocksdb::WriteBatch::default();
+        for (k, v) in batch.ops { match v { Some(v) => wb.put(k, v), None => wb.delete(k) } }
+        let mut wo = rocksdb::WriteOptions::default();
+        wo.set_sync(true);
+        self.db.write_opt(wb, &wo)?;
+        Ok(())
+    }
+    fn scan_prefix(&self, prefix: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let mode = rocksdb::IteratorMode::From(prefix, rocksdb::Direction::Forward);
+        self.db.iterator(mode).map_while(Result::ok)
+            .take_while(|(k, _)| k.starts_with(prefix))
+            .map(|(k, v)| (k.to_vec(), v.to_vec()))
+            .collect()
+    }
+    fn scan_from(&self, prefix: &[u8], start: &[u8], limit: usize) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let mode = rocksdb::IteratorMode::From(prefix.max(start), rocksdb::Direction::Forward);
+        self.db.iterator(mode).map_while(Result::ok)
+            .take_while(|(k, _)| k.starts_with(prefix))
+            .take(limit)
+            .map(|(k, v)| (k.to_vec(), v.to_vec()))
+            .collect()
+    }
+}
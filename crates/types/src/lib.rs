@@ -1,23 +1,294 @@
 
 use serde::{Serialize, Deserialize};
+use std::fmt;
 use std::time::{SystemTime, UNIX_EPOCH};
+use crypto::{PubKey, SecretKey, Sig};
 
 pub type TxId = [u8; 32];
 
+pub const DEFAULT_CHAIN_ID: &str = "v-local";
+
+/// A quantity of the native coin or of a token. Arithmetic on it is checked and never wraps or
+/// saturates, so every executor rejects an overflow at the same point instead of losing value.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Amount(pub u64);
+
+impl Amount {
+    pub const ZERO: Amount = Amount(0);
+    pub const MAX: Amount = Amount(u64::MAX);
+    pub fn checked_add(self, rhs: Amount) -> Option<Amount> { self.0.checked_add(rhs.0).map(Amount) }
+    pub fn checked_sub(self, rhs: Amount) -> Option<Amount> { self.0.checked_sub(rhs.0).map(Amount) }
+    /// Sum of `amounts`, or `None` if it overflows.
+    pub fn checked_sum(amounts: impl IntoIterator<Item = Amount>) -> Option<Amount> { amounts.into_iter().try_fold(Amount::ZERO, Amount::checked_add) }
+    pub fn to_le_bytes(self) -> [u8; 8] { self.0.to_le_bytes() }
+}
+
+impl From<u64> for Amount { fn from(v: u64) -> Self { Amount(v) } }
+impl From<Amount> for u64 { fn from(a: Amount) -> Self { a.0 } }
+impl PartialEq<u64> for Amount { fn eq(&self, other: &u64) -> bool { self.0 == *other } }
+impl fmt::Display for Amount { fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { self.0.fmt(f) } }
+
+/// Why a transaction was rejected. Clients should match on [`Rejection::code`] or the `reason`
+/// tag; the message may change.
+///
+/// On the wire a rejection is `{"code", "reason", "detail", "message"}`. Codes are never reused or
+/// renumbered; [`Rejection::VERSION`] is bumped when a variant's `detail` changes shape.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(remote = "Self", tag = "reason", content = "detail", rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum Rejection {
+    BadSignature(String),
+    BadNonce { account: String, expected: u64, got: u64 },
+    FeeTooLow { need: Amount, got: Amount },
+    /// `account` holds too little of `asset`, or of the native coin when `None`.
+    InsufficientFunds { account: String, asset: Option<AssetId> },
+    /// Crediting `account` would take its `asset` balance past [`Amount::MAX`].
+    Overflow { account: String, asset: Option<AssetId> },
+    /// Included after the point it was valid until.
+    Expired { expiry: Unlock },
+    Unauthorized(String),
+    InvalidBatch(String),
+    AccountExists(String),
+    InvalidPolicy(String),
+    InvalidLock(String),
+    InvalidTokenOp(String),
+    ContractFailed(String),
+    /// The payload is over the `max` bytes a node admits.
+    TooLarge { bytes: usize, max: usize },
+}
+
+impl Rejection {
+    pub const VERSION: u16 = 1;
+
+    /// Stable numeric code of the reason.
+    pub fn code(&self) -> u16 {
+        match self {
+            Rejection::BadSignature(_) => 1,
+            Rejection::BadNonce { .. } => 2,
+            Rejection::FeeTooLow { .. } => 3,
+            Rejection::InsufficientFunds { .. } => 4,
+            Rejection::Overflow { .. } => 5,
+            Rejection::Expired { .. } => 6,
+            Rejection::Unauthorized(_) => 7,
+            Rejection::InvalidBatch(_) => 8,
+            Rejection::AccountExists(_) => 9,
+            Rejection::InvalidPolicy(_) => 10,
+            Rejection::InvalidLock(_) => 11,
+            Rejection::InvalidTokenOp(_) => 12,
+            Rejection::ContractFailed(_) => 13,
+            Rejection::TooLarge { .. } => 14,
+        }
+    }
+
+    /// Stable name of the reason, as carried by [`Event::Rejected`] and the `reason` tag.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Rejection::BadSignature(_) => "bad_signature",
+            Rejection::BadNonce { .. } => "bad_nonce",
+            Rejection::FeeTooLow { .. } => "fee_too_low",
+            Rejection::InsufficientFunds { .. } => "insufficient_funds",
+            Rejection::Overflow { .. } => "overflow",
+            Rejection::Expired { .. } => "expired",
+            Rejection::Unauthorized(_) => "unauthorized",
+            Rejection::InvalidBatch(_) => "invalid_batch",
+            Rejection::AccountExists(_) => "account_exists",
+            Rejection::InvalidPolicy(_) => "invalid_policy",
+            Rejection::InvalidLock(_) => "invalid_lock",
+            Rejection::InvalidTokenOp(_) => "invalid_token_op",
+            Rejection::ContractFailed(_) => "contract_failed",
+            Rejection::TooLarge { .. } => "too_large",
+        }
+    }
+}
+
+impl Serialize for Rejection {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        struct Tagged<'a>(&'a Rejection);
+        impl Serialize for Tagged<'_> {
+            fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> { Rejection::serialize(self.0, s) }
+        }
+        #[derive(Serialize)]
+        struct Wire<'a> { code: u16, #[serde(flatten)] tagged: Tagged<'a>, message: String }
+        Wire { code: self.code(), tagged: Tagged(self), message: self.to_string() }.serialize(s)
+    }
+}
+
+impl<'de> Deserialize<'de> for Rejection {
+    /// `code` and `message` are derived from the variant, so only `reason` and `detail` are read.
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> { Rejection::deserialize(d) }
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rejection::BadSignature(d) => write!(f, "bad signature: {d}"),
+            Rejection::BadNonce { account, expected, got } => write!(f, "bad nonce: {account} expected {expected}, got {got}"),
+            Rejection::FeeTooLow { need, got } => write!(f, "fee too low: need {need}, got {got}"),
+            Rejection::InsufficientFunds { account, asset: None } => write!(f, "insufficient funds: {account}"),
+            Rejection::InsufficientFunds { account, asset: Some(a) } => write!(f, "insufficient {a} balance: {account}"),
+            Rejection::Overflow { account, asset: None } => write!(f, "amount overflows the balance of {account}"),
+            Rejection::Overflow { account, asset: Some(a) } => write!(f, "amount overflows the {a} balance of {account}"),
+            Rejection::Expired { expiry: Unlock::Height(h) } => write!(f, "expired at height {h}"),
+            Rejection::Expired { expiry: Unlock::Time(t) } => write!(f, "expired at time {t}"),
+            Rejection::AccountExists(a) => write!(f, "account {a} already exists"),
+            Rejection::InvalidPolicy(d) => write!(f, "invalid auth policy: {d}"),
+            Rejection::TooLarge { bytes, max } => write!(f, "payload of {bytes} bytes is over the {max} byte limit"),
+            Rejection::Unauthorized(d) | Rejection::InvalidBatch(d) | Rejection::InvalidLock(d) | Rejection::InvalidTokenOp(d) | Rejection::ContractFailed(d) => f.write_str(d),
+        }
+    }
+}
+
+impl std::error::Error for Rejection {}
+
+/// Id of an issued token, see [`asset_id`].
+pub type AssetId = String;
+
+/// Names the native coin wherever an asset id is expected, e.g. in RPC paths.
+pub const NATIVE_ASSET: &str = "native";
+
+/// Id of the token created by `issuer`'s transfer with `nonce`.
+pub fn asset_id(issuer: &str, nonce: u64) -> AssetId {
+    let mut h = blake3::Hasher::new();
+    h.update(b"asset"); h.update(&(issuer.len() as u32).to_le_bytes()); h.update(issuer.as_bytes()); h.update(&nonce.to_le_bytes());
+    hex::encode(&h.finalize().as_bytes()[..20])
+}
+
+/// Metadata fixed when a token is created.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenMeta { pub symbol: String, pub decimals: u8, pub max_supply: Amount }
+
+/// Token issuance performed by a transfer instead of an ordinary payment.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TokenOp {
+    /// Creates token [`asset_id`]`(from, nonce)` issued by `from`, minting `amount` of it to `to`.
+    Create(TokenMeta),
+    /// The issuer mints `amount` of `asset` to `to`.
+    Mint,
+    /// The issuer burns `amount` of `asset` from its own balance; `to` is unused.
+    Burn,
+}
+
+/// One recipient of a [`TxKind::BatchTransfer`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Payment { pub to: String, pub amount: Amount }
+
+/// Keys allowed to sign for an account: any `threshold` distinct keys of `keys` together.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuthPolicy { pub keys: Vec<PubKey>, pub threshold: u32 }
+
+/// Most keys an [`AuthPolicy`] may list, and so most signatures a transaction may carry.
+pub const MAX_POLICY_KEYS: usize = 16;
+
+impl AuthPolicy {
+    pub fn single(key: PubKey) -> Self { Self { keys: vec![key], threshold: 1 } }
+
+    pub fn validate(&self) -> Result<(), Rejection> {
+        if self.keys.is_empty() || self.keys.len() > MAX_POLICY_KEYS { return Err(Rejection::InvalidPolicy(format!("{} keys, need 1 to {}", self.keys.len(), MAX_POLICY_KEYS))); }
+        if self.threshold == 0 || self.threshold as usize > self.keys.len() { return Err(Rejection::InvalidPolicy(format!("threshold {} of {} keys", self.threshold, self.keys.len()))); }
+        if self.keys.iter().enumerate().any(|(i, k)| self.keys[..i].contains(k)) { return Err(Rejection::InvalidPolicy("duplicate key".to_string())); }
+        Ok(())
+    }
+}
+
+/// When a lock matures: once the chain reaches a block height, or a block time in unix ms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Unlock { Height(u64), Time(u64) }
+
+impl Unlock {
+    /// Whether a block at `height` and `time_ms` is at or past this point.
+    pub fn reached(&self, height: u64, time_ms: u64) -> bool {
+        match *self { Unlock::Height(h) => height >= h, Unlock::Time(t) => time_ms >= t }
+    }
+
+    /// Whether a block at `height` and `time_ms` is past this point.
+    pub fn passed(&self, height: u64, time_ms: u64) -> bool {
+        match *self { Unlock::Height(h) => height > h, Unlock::Time(t) => time_ms > t }
+    }
+}
+
+/// Id of the lock created by `from`'s transaction with `nonce`.
+pub fn lock_id(from: &str, nonce: u64) -> String {
+    let mut h = blake3::Hasher::new();
+    h.update(b"lock"); h.update(&(from.len() as u32).to_le_bytes()); h.update(from.as_bytes()); h.update(&nonce.to_le_bytes());
+    hex::encode(&h.finalize().as_bytes()[..20])
+}
+
+/// What a transaction does. Every kind is sent by `from` and pays `fee` with its `nonce`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TxKind {
+    /// Pays `amount` of `asset` to `to`, or performs `token_op`.
+    #[default]
+    Transfer,
+    /// Pays every recipient in the native coin or `asset`; `to` and `amount` are unused.
+    BatchTransfer(Vec<Payment>),
+    /// Opens account [`account_address`]`(from, nonce)`, controlled by this key and funded with
+    /// `amount` of the native coin; `to` is unused. Anything already sent to the address is kept.
+    CreateAccount(PubKey),
+    /// Makes this key the only one that can sign for `from` from now on.
+    SetAccountKey(PubKey),
+    /// Replaces the keys that can sign for `from`, e.g. with an M-of-N multisig policy. Like
+    /// every transaction from `from`, it must satisfy the policy it replaces.
+    SetAuthPolicy(AuthPolicy),
+    /// Issues `amount` new native coins to `to`. Only the chain's governance account may send it.
+    Mint,
+    /// Locks `amount` of `asset` in lock [`lock_id`]`(from, nonce)`, paid to `to` once `Unlock`
+    /// is reached.
+    TimeLock(Unlock),
+    /// Locks `amount` of `asset` in lock [`lock_id`]`(from, nonce)` for `to`, who gets it by
+    /// revealing the blake3 preimage of `hash` with [`TxKind::Claim`] before `expiry`. At
+    /// `expiry` the lock is refunded to `from`.
+    Htlc { #[serde(with = "hex::serde")] hash: [u8; 32], expiry: Unlock },
+    /// Releases hash lock `lock` to its recipient. Anyone holding the preimage may send it;
+    /// `to` and `amount` are unused.
+    Claim { lock: String, #[serde(with = "hex::serde")] preimage: Vec<u8> },
+}
+
+/// Address of the account opened by `creator`'s [`TxKind::CreateAccount`] with `nonce`.
+pub fn account_address(creator: &str, nonce: u64) -> String {
+    let mut h = blake3::Hasher::new();
+    h.update(b"account"); h.update(&(creator.len() as u32).to_le_bytes()); h.update(creator.as_bytes()); h.update(&nonce.to_le_bytes());
+    hex::encode(&h.finalize().as_bytes()[..20])
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Transfer {
     pub from: String,
     pub to: String,
-    pub amount: u64,
+    pub amount: Amount,
     pub nonce: u64,
+    /// Paid by `from` on top of `amount` when the transfer commits.
+    #[serde(default)]
+    pub fee: Amount,
+    #[serde(default)]
+    pub kind: TxKind,
+    /// Token moved by `amount`; `None` is the native coin. Fees are always paid in the native coin.
+    #[serde(default)]
+    pub asset: Option<AssetId>,
+    #[serde(default)]
+    pub token_op: Option<TokenOp>,
     #[serde(default)]
     pub payload: Option<Vec<u8>>,
+    /// Last block height, or latest block time in unix ms, the transfer may be included at.
+    #[serde(default)]
+    pub expiry: Option<Unlock>,
+}
+
+/// A transaction signed by `pubkey` and any `cosigs`. Which keys may sign for `transfer.from`
+/// is up to the executor, since accounts can rotate their key or require several.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedTx {
+    pub transfer: Transfer,
+    pub pubkey: PubKey,
+    pub sig: Sig,
+    #[serde(default)]
+    pub cosigs: Vec<(PubKey, Sig)>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Tx {
     pub id: TxId,
-    pub transfer: Transfer,
+    pub signed: SignedTx,
     pub submitted_unix_ms: u128,
 }
 
@@ -25,12 +296,18 @@ pub struct Tx {
 pub struct Batch {
     pub id: u64,
     pub txs: Vec<Tx>,
+    /// Unix ms on the proposer's clock when the batch was cut; time locks mature against it.
+    #[serde(default)]
+    pub time_ms: u64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Outcome of a transaction: `{"status": "committed"}`, or `{"status": "rejected", ...}` with the
+/// fields of the [`Rejection`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
 pub enum Status {
     Committed,
-    Rejected(String),
+    Rejected(Rejection),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -39,24 +316,229 @@ pub struct Receipt {
     pub status: Status,
     pub block_height: u64,
     pub latency_ms: u128,
+    #[serde(default)]
+    pub fee_paid: Amount,
+    /// Data returned by a contract call.
+    #[serde(default)]
+    pub output: Option<Vec<u8>>,
+    /// Messages logged by a contract call.
+    #[serde(default)]
+    pub logs: Vec<String>,
+    /// Set once the transaction commits.
+    #[serde(default)]
+    pub result: Option<TxResult>,
+    #[serde(default)]
+    pub events: Vec<Event>,
+}
+
+/// Where a transaction is between submission and its receipt, as served by `GET /tx/{id}`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum TxState {
+    /// Queued in the mempool.
+    Pending,
+    /// Taken into mempool batch `batch` and handed to consensus.
+    Batched { batch: u64 },
+    /// Its batch was ordered by consensus at `height` and awaits execution.
+    Disseminated { height: u64 },
+    Committed { receipt: Receipt },
+    Rejected { receipt: Receipt },
+    /// Dropped from the mempool for being past its `expiry`.
+    Expired,
+    /// Pushed out of a full mempool by better-paying transactions, or replaced by fee.
+    Evicted,
+}
+
+/// Something a transaction did, for indexers and clients to follow. `asset` is `None` for the
+/// native coin.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Event {
+    Transferred { from: String, to: String, amount: Amount, asset: Option<AssetId> },
+    Minted { to: String, amount: Amount, asset: Option<AssetId> },
+    Burned { from: String, amount: Amount, asset: AssetId },
+    /// `reason_code` is the [`Rejection::reason`] of the receipt's status.
+    Rejected { from: String, reason_code: String },
+    /// Logged by a contract call.
+    Log { contract: String, message: String },
+    /// Funds moved into lock `id`, for `to`.
+    Locked { id: String, from: String, to: String, amount: Amount, asset: Option<AssetId> },
+    /// Lock `id` paid out to `to` by a claim.
+    Released { id: String, to: String, amount: Amount, asset: Option<AssetId> },
+}
+
+impl Event {
+    /// Accounts the event concerns, each once.
+    pub fn accounts(&self) -> Vec<&str> {
+        match self {
+            Event::Transferred { from, to, .. } | Event::Locked { from, to, .. } if from != to => vec![from, to],
+            Event::Locked { from, .. } => vec![from],
+            Event::Transferred { from, .. } | Event::Burned { from, .. } | Event::Rejected { from, .. } => vec![from],
+            Event::Minted { to, .. } | Event::Released { to, .. } => vec![to],
+            Event::Log { contract, .. } => vec![contract],
+        }
+    }
+}
+
+/// What a committed transaction did, by kind.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TxResult {
+    Transfer,
+    BatchTransfer { recipients: u32, total: Amount },
+    CreateAccount { addr: String },
+    SetAccountKey,
+    SetAuthPolicy,
+    /// `balance` is the recipient's native balance after the mint.
+    Mint { balance: Amount },
+    Locked { id: String },
+    Claimed { id: String, to: String, amount: Amount, asset: Option<AssetId> },
 }
 
 impl Tx {
-    pub fn new(transfer: Transfer) -> Self {
-        let id = make_tx_id(&transfer);
+    pub fn new(signed: SignedTx) -> Self {
+        let id = make_tx_id(&signed.transfer);
         let submitted_unix_ms = now_ms();
-        Self { id, transfer, submitted_unix_ms }
+        Self { id, signed, submitted_unix_ms }
+    }
+}
+
+impl SignedTx {
+    pub fn sign(transfer: Transfer, sk: &SecretKey, chain_id: &str) -> Self {
+        let sig = crypto::sign(sk, &signing_bytes(chain_id, &transfer));
+        Self { transfer, pubkey: crypto::public_key(sk), sig, cosigs: Vec::new() }
+    }
+
+    /// Size of the JSON encoding clients submit and peers gossip.
+    pub fn encoded_len(&self) -> usize { serde_json::to_vec(self).map_or(0, |v| v.len()) }
+
+    /// Adds `sk`'s signature, for accounts that need more than one.
+    pub fn cosign(mut self, sk: &SecretKey, chain_id: &str) -> Self {
+        let sig = crypto::sign(sk, &signing_bytes(chain_id, &self.transfer));
+        self.cosigs.push((crypto::public_key(sk), sig));
+        self
+    }
+
+    /// Checks that every listed key signed this transaction for `chain_id`.
+    pub fn verify(&self, chain_id: &str) -> Result<(), Rejection> {
+        if self.cosigs.len() >= MAX_POLICY_KEYS { return Err(Rejection::BadSignature(format!("{} cosignatures, max {}", self.cosigs.len(), MAX_POLICY_KEYS - 1))); }
+        let msg = signing_bytes(chain_id, &self.transfer);
+        if !crypto::verify(&self.pubkey, &msg, &self.sig) || !self.cosigs.iter().all(|(pk, sig)| crypto::verify(pk, &msg, sig)) {
+            return Err(Rejection::BadSignature(self.transfer.from.clone()));
+        }
+        Ok(())
+    }
+
+    /// Every key that signed, the primary signer first.
+    pub fn signers(&self) -> impl Iterator<Item = &PubKey> { std::iter::once(&self.pubkey).chain(self.cosigs.iter().map(|(pk, _)| pk)) }
+
+    /// Whether `pubkey` owns `transfer.from` by address, which holds until the account sets a key.
+    pub fn signed_by_owner(&self) -> bool { crypto::address(&self.pubkey) == self.transfer.from }
+
+    /// Checks that the signers are enough for an account with `auth`. An account without a
+    /// policy belongs to the key its address was derived from.
+    pub fn authorize(&self, auth: Option<&AuthPolicy>) -> Result<(), Rejection> {
+        let Some(p) = auth else {
+            return if self.signed_by_owner() { Ok(()) } else { Err(Rejection::BadSignature(format!("key {} does not own {}", self.pubkey.hex(), self.transfer.from))) };
+        };
+        let signed = p.keys.iter().filter(|k| self.signers().any(|s| s == *k)).count();
+        if signed < p.threshold as usize { return Err(Rejection::BadSignature(format!("{} of {} required signatures for {}", signed, p.threshold, self.transfer.from))); }
+        Ok(())
+    }
+}
+
+/// Canonical, length-prefixed encoding of a transfer that senders sign.
+pub fn signing_bytes(chain_id: &str, t: &Transfer) -> Vec<u8> {
+    let mut v = Vec::new();
+    v.extend_from_slice(b"V_TRANSFER");
+    v.extend_from_slice(&(chain_id.len() as u32).to_le_bytes());
+    v.extend_from_slice(chain_id.as_bytes());
+    v.extend_from_slice(&transfer_bytes(t));
+    v
+}
+
+/// Every field of `t`, each variable-length one prefixed with its length.
+fn transfer_bytes(t: &Transfer) -> Vec<u8> {
+    let mut v = Vec::new();
+    for f in [t.from.as_bytes(), t.to.as_bytes()] {
+        v.extend_from_slice(&(f.len() as u32).to_le_bytes());
+        v.extend_from_slice(f);
+    }
+    v.extend_from_slice(&t.amount.to_le_bytes());
+    v.extend_from_slice(&t.nonce.to_le_bytes());
+    v.extend_from_slice(&t.fee.to_le_bytes());
+    let kind = kind_bytes(&t.kind);
+    v.extend_from_slice(&(kind.len() as u32).to_le_bytes()); v.extend_from_slice(&kind);
+    match &t.asset {
+        Some(a) => { v.push(1); v.extend_from_slice(&(a.len() as u32).to_le_bytes()); v.extend_from_slice(a.as_bytes()); }
+        None => v.push(0),
+    }
+    match &t.token_op {
+        Some(op) => { let op = token_op_bytes(op); v.push(1); v.extend_from_slice(&(op.len() as u32).to_le_bytes()); v.extend_from_slice(&op); }
+        None => v.push(0),
+    }
+    match &t.payload {
+        Some(p) => { v.push(1); v.extend_from_slice(&(p.len() as u32).to_le_bytes()); v.extend_from_slice(p); }
+        None => v.push(0),
+    }
+    match &t.expiry {
+        Some(e) => { v.push(1); v.extend_from_slice(&unlock_bytes(e)); }
+        None => v.push(0),
+    }
+    v
+}
+
+fn kind_bytes(kind: &TxKind) -> Vec<u8> {
+    match kind {
+        TxKind::Transfer => vec![0],
+        TxKind::BatchTransfer(payments) => {
+            let mut v = vec![1];
+            v.extend_from_slice(&(payments.len() as u32).to_le_bytes());
+            for p in payments { v.extend_from_slice(&(p.to.len() as u32).to_le_bytes()); v.extend_from_slice(p.to.as_bytes()); v.extend_from_slice(&p.amount.to_le_bytes()); }
+            v
+        }
+        TxKind::CreateAccount(key) => [&[2], &key.0[..]].concat(),
+        TxKind::SetAccountKey(key) => [&[3], &key.0[..]].concat(),
+        TxKind::Mint => vec![4],
+        TxKind::SetAuthPolicy(p) => {
+            let mut v = vec![5];
+            v.extend_from_slice(&p.threshold.to_le_bytes());
+            v.extend_from_slice(&(p.keys.len() as u32).to_le_bytes());
+            for k in &p.keys { v.extend_from_slice(&k.0); }
+            v
+        }
+        TxKind::TimeLock(u) => [&[6][..], &unlock_bytes(u)].concat(),
+        TxKind::Htlc { hash, expiry } => [&[7][..], hash, &unlock_bytes(expiry)].concat(),
+        TxKind::Claim { lock, preimage } => {
+            let mut v = vec![8];
+            for f in [lock.as_bytes(), preimage] { v.extend_from_slice(&(f.len() as u32).to_le_bytes()); v.extend_from_slice(f); }
+            v
+        }
+    }
+}
+
+fn unlock_bytes(u: &Unlock) -> [u8; 9] {
+    let (tag, at) = match *u { Unlock::Height(h) => (0, h), Unlock::Time(t) => (1, t) };
+    let mut b = [tag; 9];
+    b[1..].copy_from_slice(&at.to_le_bytes());
+    b
+}
+
+fn token_op_bytes(op: &TokenOp) -> Vec<u8> {
+    match op {
+        TokenOp::Create(m) => {
+            let mut v = vec![0];
+            v.extend_from_slice(&(m.symbol.len() as u32).to_le_bytes()); v.extend_from_slice(m.symbol.as_bytes());
+            v.push(m.decimals);
+            v.extend_from_slice(&m.max_supply.to_le_bytes());
+            v
+        }
+        TokenOp::Mint => vec![1],
+        TokenOp::Burn => vec![2],
     }
 }
 
 pub fn make_tx_id(t: &Transfer) -> TxId {
-    let mut hasher = blake3::Hasher::new();
-    hasher.update(t.from.as_bytes());
-    hasher.update(t.to.as_bytes());
-    hasher.update(&t.amount.to_le_bytes());
-    hasher.update(&t.nonce.to_le_bytes());
-    if let Some(p) = &t.payload { hasher.update(p); }
-    *hasher.finalize().as_bytes()
+    // Length-prefixed like the signed bytes, so moving bytes between fields changes the id.
+    *blake3::hash(&transfer_bytes(t)).as_bytes()
 }
 
 pub fn now_ms() -> u128 {
@@ -65,6 +547,7 @@ pub fn now_ms() -> u128 {
 
 #[async_trait::async_trait]
 pub trait SubmitApi: Send + Sync {
-    async fn submit_transfer(&self, t: Transfer) -> anyhow::Result<Receipt>;
-    async fn get_balance(&self, addr: String) -> anyhow::Result<u64>;
+    async fn submit_transfer(&self, t: SignedTx) -> anyhow::Result<Receipt>;
+    async fn get_balance(&self, addr: String) -> anyhow::Result<Amount>;
+    async fn get_nonce(&self, addr: String) -> anyhow::Result<u64>;
 }
@@ -0,0 +1,55 @@
+use types::{Amount, AuthPolicy, Rejection, SignedTx, Transfer, TxKind, DEFAULT_CHAIN_ID};
+
+fn alice() -> (crypto::SecretKey, String) {
+    let (sk, pk) = crypto::keypair_from_seed(b"alice");
+    (sk, crypto::address(&pk))
+}
+
+fn transfer(from: &str) -> Transfer {
+    Transfer { from: from.to_string(), to: "bob".into(), amount: Amount(10), fee: Amount(1), nonce: 0, kind: TxKind::Transfer, asset: None, token_op: None, payload: None, expiry: None }
+}
+
+#[test]
+fn tampered_transfers_fail_verification() {
+    let (sk, addr) = alice();
+    let signed = SignedTx::sign(transfer(&addr), &sk, DEFAULT_CHAIN_ID);
+    assert_eq!(signed.verify(DEFAULT_CHAIN_ID), Ok(()));
+
+    let mut edited = signed.clone();
+    edited.transfer.amount = Amount(11);
+    assert_eq!(edited.verify(DEFAULT_CHAIN_ID), Err(Rejection::BadSignature(addr.clone())));
+
+    let mut flipped = signed.clone();
+    flipped.sig.0[0] ^= 1;
+    assert_eq!(flipped.verify(DEFAULT_CHAIN_ID), Err(Rejection::BadSignature(addr.clone())));
+
+    let (other, _) = crypto::keypair_from_seed(b"mallory");
+    let bad_cosig = signed.clone().cosign(&other, "other-chain");
+    assert_eq!(bad_cosig.verify(DEFAULT_CHAIN_ID), Err(Rejection::BadSignature(addr)));
+}
+
+#[test]
+fn signatures_are_bound_to_the_chain() {
+    let (sk, addr) = alice();
+    let signed = SignedTx::sign(transfer(&addr), &sk, "other-chain");
+    assert_eq!(signed.verify("other-chain"), Ok(()));
+    assert_eq!(signed.verify(DEFAULT_CHAIN_ID), Err(Rejection::BadSignature(addr)));
+}
+
+#[test]
+fn only_the_owning_key_authorizes() {
+    let (sk, addr) = alice();
+    let (mallory, mallory_pk) = crypto::keypair_from_seed(b"mallory");
+    assert_eq!(SignedTx::sign(transfer(&addr), &sk, DEFAULT_CHAIN_ID).authorize(None), Ok(()));
+
+    // Validly signed, but by a key that doesn't own `from`.
+    let stolen = SignedTx::sign(transfer(&addr), &mallory, DEFAULT_CHAIN_ID);
+    assert_eq!(stolen.verify(DEFAULT_CHAIN_ID), Ok(()));
+    assert!(!stolen.signed_by_owner());
+    assert_eq!(stolen.authorize(None), Err(Rejection::BadSignature(format!("key {} does not own {addr}", mallory_pk.hex()))));
+
+    // Once the account names its keys, the address key alone no longer counts.
+    let policy = AuthPolicy { keys: vec![mallory_pk], threshold: 1 };
+    assert_eq!(stolen.authorize(Some(&policy)), Ok(()));
+    assert!(SignedTx::sign(transfer(&addr), &sk, DEFAULT_CHAIN_ID).authorize(Some(&policy)).is_err());
+}
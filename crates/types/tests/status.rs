@@ -0,0 +1,46 @@
+use serde_json::json;
+use types::{Amount, Rejection, Status, Unlock};
+
+#[test]
+fn statuses_have_one_json_shape() {
+    assert_eq!(serde_json::to_value(Status::Committed).unwrap(), json!({ "status": "committed" }));
+
+    let nonce = Status::Rejected(Rejection::BadNonce { account: "alice".into(), expected: 2, got: 1 });
+    assert_eq!(serde_json::to_value(&nonce).unwrap(), json!({
+        "status": "rejected", "code": 2, "reason": "bad_nonce",
+        "detail": { "account": "alice", "expected": 2, "got": 1 },
+        "message": "bad nonce: alice expected 2, got 1",
+    }));
+    let signature = Status::Rejected(Rejection::BadSignature("forged".into()));
+    assert_eq!(serde_json::to_value(&signature).unwrap(), json!({
+        "status": "rejected", "code": 1, "reason": "bad_signature", "detail": "forged", "message": "bad signature: forged",
+    }));
+}
+
+#[test]
+fn rejections_round_trip() {
+    let all = [
+        Rejection::BadSignature("forged".into()),
+        Rejection::BadNonce { account: "a".into(), expected: 1, got: 0 },
+        Rejection::FeeTooLow { need: Amount(10), got: Amount(9) },
+        Rejection::InsufficientFunds { account: "a".into(), asset: Some("tok".into()) },
+        Rejection::Overflow { account: "a".into(), asset: None },
+        Rejection::Expired { expiry: Unlock::Height(7) },
+        Rejection::Unauthorized("no".into()),
+        Rejection::InvalidBatch("empty".into()),
+        Rejection::AccountExists("a".into()),
+        Rejection::InvalidPolicy("duplicate key".into()),
+        Rejection::InvalidLock("unknown lock x".into()),
+        Rejection::InvalidTokenOp("bad".into()),
+        Rejection::ContractFailed("trap".into()),
+        Rejection::TooLarge { bytes: 10, max: 8 },
+    ];
+    for (i, r) in all.into_iter().enumerate() {
+        assert_eq!(r.code() as usize, i + 1, "codes are stable");
+        let status = Status::Rejected(r);
+        assert_eq!(serde_json::from_str::<Status>(&serde_json::to_string(&status).unwrap()).unwrap(), status);
+    }
+    // `code` and `message` are informational and may be left out by clients that build statuses.
+    let r: Rejection = serde_json::from_value(json!({ "reason": "overflow", "detail": { "account": "a", "asset": null } })).unwrap();
+    assert_eq!(r, Rejection::Overflow { account: "a".into(), asset: None });
+}
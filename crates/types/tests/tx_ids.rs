@@ -0,0 +1,14 @@
+use types::{make_tx_id, Amount, Transfer, TxKind};
+
+fn transfer(from: &str, to: &str) -> Transfer {
+    Transfer { from: from.into(), to: to.into(), amount: Amount(10), fee: Amount(1), nonce: 0, kind: TxKind::Transfer, asset: None, token_op: None, payload: None, expiry: None }
+}
+
+#[test]
+fn ids_keep_fields_apart() {
+    assert_eq!(make_tx_id(&transfer("ab", "c")), make_tx_id(&transfer("ab", "c")));
+    assert_ne!(make_tx_id(&transfer("ab", "c")), make_tx_id(&transfer("a", "bc")));
+    let asset = Transfer { asset: Some("x".into()), ..transfer("a", "b") };
+    let payload = Transfer { payload: Some(b"x".to_vec()), ..transfer("a", "b") };
+    assert_ne!(make_tx_id(&asset), make_tx_id(&payload));
+}
@@ -1,5 +1,3 @@
-use std::fs;
-
 fn read(path: &str) -> Option<String> { std::fs::read_to_string(path).ok() }
 fn write(path: &str, s: &str) { let _ = std::fs::write(path, s); }
 
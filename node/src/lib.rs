@@ -1,13 +1,16 @@
+pub use lifecycle::{TxIndex, MAX_TRACKED_TXS};
 pub use rpc_adapter::NodeApiAdapter;
+mod lifecycle;
 mod rpc_adapter;
 
 use parking_lot::Mutex;
 use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::{mpsc, oneshot};
-use types::{SubmitApi, Transfer, Receipt, Tx, TxId};
+use tokio::sync::{broadcast, mpsc, oneshot};
+use types::{Batch, SubmitApi, SignedTx, Receipt, Tx, TxId};
 use mempool::MempoolHandle;
 use exec::Executor;
+use da::sampling::SamplingClient;
 
 #[derive(Clone)]
 pub struct Node {
@@ -16,19 +19,59 @@ pub struct Node {
     tx_timeout_ms: u64,
     executor: Arc<dyn Executor>,
     p2p_publish: Option<mpsc::Sender<Vec<u8>>>,
+    da_store: da::ShardStore,
+    da_sampler: Option<Arc<SamplingClient>>,
+    state_roots: consensus::StateRoots,
+    events: exec::EventIndex,
+    txs: Arc<TxIndex>,
 }
 
 impl Node {
-    pub fn new(mempool: MempoolHandle, executor: Arc<dyn Executor>, p2p_publish: Option<mpsc::Sender<Vec<u8>>>) -> Arc<Self> {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        mempool: MempoolHandle,
+        executor: Arc<dyn Executor>,
+        p2p_publish: Option<mpsc::Sender<Vec<u8>>>,
+        da_store: da::ShardStore,
+        da_sampler: Option<Arc<SamplingClient>>,
+        state_roots: consensus::StateRoots,
+        events: exec::EventIndex,
+        txs: Arc<TxIndex>,
+    ) -> Arc<Self> {
         Arc::new(Self {
             mempool,
             waiters: Arc::new(Mutex::new(HashMap::new())),
             tx_timeout_ms: 5_000,
             executor,
             p2p_publish,
+            da_store,
+            da_sampler,
+            state_roots,
+            events,
+            txs,
         })
     }
 
+    pub fn tx_index(&self) -> Arc<TxIndex> { self.txs.clone() }
+
+    /// Re-queues the transactions journaled before a restart and marks them pending again.
+    pub async fn restore_mempool(&self) -> usize {
+        let ids = self.mempool.restore().await;
+        for id in &ids { self.txs.pending(*id); }
+        ids.len()
+    }
+
+    /// Marks the txs of every batch the mempool makes as batched, then hands the batch to consensus.
+    pub fn spawn_batch_tracker(&self, mut batches: mpsc::Receiver<Batch>, to_consensus: mpsc::Sender<Batch>) {
+        let txs = self.txs.clone();
+        tokio::spawn(async move {
+            while let Some(b) = batches.recv().await {
+                txs.batched(&b);
+                if to_consensus.send(b).await.is_err() { break; }
+            }
+        });
+    }
+
     pub fn spawn_commit_listener(self: &Arc<Self>, mut committed_rx: mpsc::Receiver<Receipt>) {
         let me = self.clone();
         tokio::spawn(async move {
@@ -46,21 +89,107 @@ impl Node {
         rx
     }
 
+    /// Pools `tx`, failing with a [`mempool::MempoolError`] if it's invalid, the mempool is full
+    /// or the pool turns it away.
     pub async fn enqueue_tx(&self, tx: Tx) -> anyhow::Result<()> {
-        self.mempool.enqueue(tx).await
+        let id = tx.id;
+        self.mempool.try_enqueue(tx).await?;
+        self.txs.pending(id);
+        Ok(())
     }
 
     pub fn executor(&self) -> Arc<dyn Executor> { self.executor.clone() }
+
+    /// Indexes the events of a block's receipts; a failure only costs `GET /events` that block.
+    pub fn index_events(&self, receipts: &[Receipt]) {
+        if let Err(e) = self.events.record(receipts) { tracing::warn!(error = %e, "event index: write failed"); }
+    }
+
+    /// Samples every batch recorded as committed from DA peers, if a sampler is configured.
+    pub fn spawn_da_sampler(&self) {
+        let Some(sampler) = self.da_sampler.clone() else { return };
+        let mut commits = self.da_store.subscribe();
+        tokio::spawn(async move {
+            loop {
+                let committed = match commits.recv().await {
+                    Ok(c) => c,
+                    Err(broadcast::error::RecvError::Lagged(n)) => { tracing::warn!(skipped = n, "da sampling: fell behind commits"); continue; }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                let sampler = sampler.clone();
+                tokio::spawn(async move {
+                    let a = sampler.sample_and_record(&committed).await;
+                    if !a.failed.is_empty() {
+                        tracing::warn!(height = committed.height, failed = ?a.failed, confidence = a.confidence, "da sampling: shards unavailable");
+                    }
+                });
+            }
+        });
+    }
+
+    /// Pulls committed roots from `peers` in height order, for a node outside the validator set that gets
+    /// no consensus traffic. A root is recorded only if a quorum of `validators` certified it.
+    pub fn spawn_commit_follower(&self, peers: Vec<String>, validators: consensus::Validators, poll: std::time::Duration) {
+        let (store, roots) = (self.da_store.clone(), self.state_roots.clone());
+        tokio::spawn(async move {
+            let http = reqwest::Client::new();
+            let mut next = match store.latest_commit() {
+                Some(c) => c.height + 1,
+                None => latest_height(&http, &peers).await.unwrap_or(1),
+            };
+            loop {
+                let mut advanced = false;
+                for peer in &peers {
+                    let Some(c) = fetch_commit(&http, peer, next).await else { continue };
+                    if c.commit.height != next || c.qc.height != next || c.qc.root != c.commit.root || !c.qc.verify(&validators) {
+                        tracing::warn!(peer, height = next, "da follower: commit not certified by validators");
+                        continue;
+                    }
+                    store.record_commit(next, c.commit.root, c.commit.k, c.commit.m);
+                    roots.record_commit(next, c.qc);
+                    next += 1;
+                    advanced = true;
+                    break;
+                }
+                if !advanced { tokio::time::sleep(poll).await; }
+            }
+        });
+    }
+}
+
+async fn fetch_commit(http: &reqwest::Client, peer: &str, height: u64) -> Option<rpc::DaCommit> {
+    let url = format!("{}/da/commit/{height}", peer.trim_end_matches('/'));
+    http.get(url).send().await.ok()?.error_for_status().ok()?.json().await.ok()
+}
+
+/// Newest height any of `peers` reports, so a fresh follower starts at the tip instead of at genesis.
+async fn latest_height(http: &reqwest::Client, peers: &[String]) -> Option<u64> {
+    for peer in peers {
+        let url = format!("{}/state/root", peer.trim_end_matches('/'));
+        let Some(resp) = http.get(url).send().await.ok().and_then(|r| r.error_for_status().ok()) else { continue };
+        if let Ok(r) = resp.json::<rpc::StateRoot>().await { return Some(r.height.max(1)); }
+    }
+    None
+}
+
+/// Committed executor state, as the mempool reads it.
+pub struct ExecutorState(pub Arc<dyn Executor>);
+
+impl mempool::StateView for ExecutorState {
+    fn nonce(&self, addr: &str) -> u64 { self.0.nonce(addr) }
+    fn balance(&self, addr: &str) -> types::Amount { self.0.balance(addr) }
+    fn last_height(&self) -> u64 { self.0.last_height() }
+    fn auth(&self, addr: &str) -> Option<types::AuthPolicy> { self.0.account(addr).and_then(|a| a.auth) }
 }
 
 #[async_trait::async_trait]
 impl SubmitApi for Node {
-    async fn submit_transfer(&self, t: Transfer) -> anyhow::Result<Receipt> {
+    async fn submit_transfer(&self, t: SignedTx) -> anyhow::Result<Receipt> {
         let tx = Tx::new(t.clone());
-        if let Some(p2p) = &self.p2p_publish { let _ = p2p.send(serde_json::to_vec(&t)?).await; }
         let id = tx.id;
         let rx = self.register_waiter(id);
-        self.enqueue_tx(tx).await?;
+        if let Err(e) = self.enqueue_tx(tx).await { self.waiters.lock().remove(&id); return Err(e); }
+        if let Some(p2p) = &self.p2p_publish { let _ = p2p.send(serde_json::to_vec(&t)?).await; }
         match tokio::time::timeout(std::time::Duration::from_millis(self.tx_timeout_ms), rx).await {
             Ok(Ok(r)) => Ok(r),
             Ok(Err(_canceled)) => Err(anyhow::anyhow!("commit channel canceled")),
@@ -68,9 +197,13 @@ impl SubmitApi for Node {
         }
     }
 
-    async fn get_balance(&self, addr: String) -> anyhow::Result<u64> {
+    async fn get_balance(&self, addr: String) -> anyhow::Result<types::Amount> {
         Ok(self.executor().balance(&addr))
     }
+
+    async fn get_nonce(&self, addr: String) -> anyhow::Result<u64> {
+        Ok(self.executor().nonce(&addr))
+    }
 }
 
 
@@ -80,20 +213,73 @@ impl SubmitApi for Node {
 
 #[async_trait::async_trait]
 impl rpc::NodeApi for Node {
-    async fn submit_transfer(&self, t: rpc::TransferReq) -> anyhow::Result<types::Receipt> {
-        let tx = types::Transfer {
-            from: t.from,
-            to: t.to,
-            amount: t.amount,
-            nonce: 0,
-            payload: None,
-        };
-        <Self as crate::SubmitApi>::submit_transfer(self, tx).await
-    }
-
-    async fn get_balance(&self, addr: String) -> anyhow::Result<u64> {
+    async fn submit_transfer(&self, t: SignedTx) -> anyhow::Result<types::Receipt> {
+        <Self as crate::SubmitApi>::submit_transfer(self, t).await
+    }
+
+    async fn simulate(&self, txs: Vec<SignedTx>) -> anyhow::Result<Vec<types::Receipt>> {
+        let ex = self.executor();
+        let txs: Vec<types::Tx> = txs.into_iter().map(types::Tx::new).collect();
+        Ok(tokio::task::spawn_blocking(move || ex.simulate(&txs)).await?)
+    }
+
+    async fn get_balance(&self, addr: String) -> anyhow::Result<types::Amount> {
         <Self as crate::SubmitApi>::get_balance(self, addr).await
     }
+
+    async fn get_nonce(&self, addr: String) -> anyhow::Result<u64> {
+        <Self as crate::SubmitApi>::get_nonce(self, addr).await
+    }
+
+    async fn get_da_shard(&self, root: [u8; 32], index: u32) -> anyhow::Result<Option<da::Shard>> {
+        Ok(self.da_store.get(&root, index))
+    }
+
+    async fn get_da_availability(&self, height: u64) -> anyhow::Result<Option<da::sampling::Availability>> {
+        Ok(self.da_sampler.as_ref().and_then(|s| s.get(height)))
+    }
+
+    async fn get_da_commit(&self, height: u64) -> anyhow::Result<Option<rpc::DaCommit>> {
+        let (Some(commit), Some(qc)) = (self.da_store.commit_at(height), self.state_roots.commit_qc(height)) else { return Ok(None) };
+        Ok(Some(rpc::DaCommit { commit, qc }))
+    }
+
+    async fn get_state_root(&self) -> anyhow::Result<rpc::StateRoot> {
+        let ex = self.executor();
+        Ok(rpc::StateRoot { height: ex.last_height(), root: ex.state_root() })
+    }
+
+    async fn get_asset_balance(&self, addr: String, asset: String) -> anyhow::Result<types::Amount> {
+        Ok(self.executor().asset_balance(&addr, &asset))
+    }
+
+    async fn get_account(&self, addr: String) -> anyhow::Result<Option<exec::AccountState>> {
+        Ok(self.executor().account(&addr))
+    }
+
+    async fn get_token(&self, asset: String) -> anyhow::Result<Option<exec::TokenInfo>> {
+        Ok(self.executor().token(&asset))
+    }
+
+    async fn get_account_proof(&self, addr: String) -> anyhow::Result<Option<rpc::AccountProof>> {
+        let Some(qc) = self.state_roots.certified() else { return Ok(None) };
+        let Some(c) = qc.state else { return Ok(None) };
+        let Some(state) = self.executor().prove(&addr, c.height) else { return Ok(None) };
+        if state.root != c.root { anyhow::bail!("local state root at height {} disagrees with the certified root", c.height); }
+        Ok(Some(rpc::AccountProof { state, qc }))
+    }
+
+    async fn get_events(&self, account: String, from: exec::EventCursor, limit: usize) -> anyhow::Result<exec::EventPage> {
+        self.events.query(&account, from, limit)
+    }
+
+    async fn get_locks(&self, addr: String) -> anyhow::Result<Vec<exec::PendingLock>> {
+        Ok(self.executor().locks(&addr))
+    }
+
+    async fn get_tx(&self, id: TxId) -> anyhow::Result<Option<types::TxState>> {
+        Ok(self.txs.get(&id))
+    }
 }
 
 
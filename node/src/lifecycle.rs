@@ -0,0 +1,55 @@
+//! Where transactions this node has seen are, from submission to receipt, for `GET /tx/{id}`.
+
+use parking_lot::Mutex;
+use std::collections::{HashMap, VecDeque};
+use types::{Batch, Receipt, Status, Tx, TxId, TxState};
+
+/// Most transactions remembered; the first seen are forgotten first.
+pub const MAX_TRACKED_TXS: usize = 100_000;
+
+#[derive(Default)]
+pub struct TxIndex { inner: Mutex<Tracked> }
+
+#[derive(Default)]
+struct Tracked { states: HashMap<TxId, TxState>, order: VecDeque<TxId> }
+
+impl TxIndex {
+    pub fn get(&self, id: &TxId) -> Option<TxState> { self.inner.lock().states.get(id).cloned() }
+
+    /// Marks `id` queued, unless its batch was already taken, or committed, while it was being submitted.
+    pub fn pending(&self, id: TxId) {
+        let mut t = self.inner.lock();
+        let moved_on = matches!(t.states.get(&id), Some(TxState::Batched { .. } | TxState::Disseminated { .. } | TxState::Committed { .. }));
+        if !moved_on { t.insert(id, TxState::Pending); }
+    }
+
+    pub fn batched(&self, batch: &Batch) {
+        for tx in &batch.txs { self.set(tx.id, TxState::Batched { batch: batch.id }); }
+    }
+
+    pub fn disseminated(&self, batch: &Batch, height: u64) {
+        for tx in &batch.txs { self.set(tx.id, TxState::Disseminated { height }); }
+    }
+
+    pub fn executed(&self, receipts: &[Receipt]) {
+        for r in receipts {
+            let state = match r.status { Status::Committed => TxState::Committed { receipt: r.clone() }, Status::Rejected(_) => TxState::Rejected { receipt: r.clone() } };
+            self.set(r.tx_id, state);
+        }
+    }
+
+    fn set(&self, id: TxId, state: TxState) { self.inner.lock().insert(id, state); }
+}
+
+impl Tracked {
+    fn insert(&mut self, id: TxId, state: TxState) {
+        if self.states.insert(id, state).is_some() { return; }
+        self.order.push_back(id);
+        if self.order.len() > MAX_TRACKED_TXS && let Some(old) = self.order.pop_front() { self.states.remove(&old); }
+    }
+}
+
+impl mempool::PoolObserver for TxIndex {
+    fn evicted(&self, tx: &Tx) { self.set(tx.id, TxState::Evicted); }
+    fn expired(&self, tx: &Tx) { self.set(tx.id, TxState::Expired); }
+}
@@ -1,6 +1,7 @@
 pub use node::Node;
 
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::mpsc;
 use telemetry::init as telemetry_init;
 use tracing::{info, warn};
@@ -20,6 +21,9 @@ struct EnvConfig {
     rpc_addr: String, quic_addr: Option<String>, p2p_listen: Option<String>, p2p_bootstrap: Vec<Multiaddr>,
     node_id: u32, validators: String, validators_keys: String, node_sk: Option<String>,
     db_path: String, use_yaml: Option<String>,
+    da_peers: Vec<String>, da_samples: u32, chain_id: Option<String>,
+    genesis_file: Option<String>, genesis_hash: Option<String>,
+    mempool: mempool::MempoolConfig,
 }
 
 #[tokio::main]
@@ -33,6 +37,9 @@ async fn main() -> anyhow::Result<()> {
         cfg.rpc_addr = y.rpc_addr; cfg.quic_addr = Some(y.quic_addr); cfg.p2p_listen = Some(y.p2p_listen);
         cfg.node_id = y.node_id; cfg.validators = y.validators; cfg.db_path = y.db_path;
         cfg.validators_keys = y.validators_keys; cfg.node_sk = y.node_sk;
+        cfg.da_peers = y.da_peers; cfg.da_samples = y.da_samples; cfg.chain_id = y.chain_id;
+        cfg.genesis_file = y.genesis_file; cfg.genesis_hash = y.genesis_hash;
+        apply_mempool_yaml(&mut cfg.mempool, &y.mempool);
         tokio::spawn(watch_and_log(path.clone()));
     }
 
@@ -54,25 +61,108 @@ fn read_env_cfg() -> EnvConfig {
         node_sk: std::env::var("NODE_SK").ok(),
         db_path: std::env::var("DB_PATH").unwrap_or_else(|_| "db".to_string()),
         use_yaml: std::env::var("CONFIG_YAML").ok(),
+        da_peers: std::env::var("DA_PEERS").unwrap_or_default().split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect(),
+        da_samples: std::env::var("DA_SAMPLES").ok().and_then(|s| s.parse().ok()).unwrap_or(8),
+        chain_id: std::env::var("CHAIN_ID").ok(),
+        genesis_file: std::env::var("GENESIS_FILE").ok(),
+        genesis_hash: std::env::var("GENESIS_HASH").ok(),
+        mempool: read_mempool_cfg(),
     }
 }
 
+fn read_mempool_cfg() -> mempool::MempoolConfig {
+    fn var<T: std::str::FromStr>(name: &str, default: T) -> T { std::env::var(name).ok().and_then(|s| s.parse().ok()).unwrap_or(default) }
+    let d = mempool::MempoolConfig::default();
+    mempool::MempoolConfig {
+        flush_interval: Duration::from_millis(var("MEMPOOL_FLUSH_MS", d.flush_interval.as_millis() as u64)),
+        max_batch_txs: var("MEMPOOL_MAX_BATCH_TXS", d.max_batch_txs),
+        max_batch_bytes: var("MEMPOOL_MAX_BATCH_BYTES", d.max_batch_bytes),
+        channel_capacity: var("MEMPOOL_CHANNEL_CAPACITY", d.channel_capacity),
+        dedup_window: Duration::from_secs(var("MEMPOOL_DEDUP_SECS", d.dedup_window.as_secs())),
+        limits: mempool::PoolLimits { max_txs: var("MEMPOOL_MAX_TXS", d.limits.max_txs), max_bytes: var("MEMPOOL_MAX_BYTES", d.limits.max_bytes) },
+    }
+}
+
+/// Overrides the environment's mempool settings with those the YAML config sets.
+fn apply_mempool_yaml(m: &mut mempool::MempoolConfig, y: &configd::MempoolSettings) {
+    if let Some(ms) = y.flush_ms { m.flush_interval = Duration::from_millis(ms); }
+    if let Some(n) = y.max_batch_txs { m.max_batch_txs = n; }
+    if let Some(n) = y.max_batch_bytes { m.max_batch_bytes = n; }
+    if let Some(n) = y.channel_capacity { m.channel_capacity = n; }
+    if let Some(secs) = y.dedup_secs { m.dedup_window = Duration::from_secs(secs); }
+    if let Some(n) = y.max_txs { m.limits.max_txs = n; }
+    if let Some(n) = y.max_bytes { m.limits.max_bytes = n; }
+}
+
+/// Resolves on ctrl-c, or SIGTERM on unix.
+async fn shutdown_signal() {
+    let ctrl_c = async { if let Err(e) = tokio::signal::ctrl_c().await { warn!(error = %e, "ctrl-c handler failed"); std::future::pending::<()>().await } };
+    #[cfg(unix)]
+    let term = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut s) => { s.recv().await; }
+            Err(e) => { warn!(error = %e, "SIGTERM handler failed"); std::future::pending::<()>().await }
+        }
+    };
+    #[cfg(not(unix))]
+    let term = std::future::pending::<()>();
+    tokio::select! { _ = ctrl_c => {}, _ = term => {} }
+    info!("shutdown requested");
+}
+
+#[cfg(feature = "rocksdb")]
+fn open_state_kv(db_path: &str) -> anyhow::Result<Arc<dyn storage::Kv>> { Ok(Arc::new(storage::RocksKv::open(db_path)?)) }
+#[cfg(not(feature = "rocksdb"))]
+fn open_state_kv(db_path: &str) -> anyhow::Result<Arc<dyn storage::Kv>> { Ok(Arc::new(storage::FileKv::open(std::path::Path::new(db_path).join("state.log"))?)) }
+
+/// Contracts run serially, so they replace Block-STM rather than sitting on top of it.
+#[cfg(feature = "wasm")]
+fn block_executor(simple: Arc<exec::SimpleExecutor>) -> Arc<dyn exec::Executor> { Arc::new(exec::WasmExecutor::new(simple)) }
+#[cfg(not(feature = "wasm"))]
+fn block_executor(simple: Arc<exec::SimpleExecutor>) -> Arc<dyn exec::Executor> { Arc::new(exec::BlockStmExecutor::new(simple)) }
+
+/// Loads the genesis file (or the dev genesis) and checks it against `CHAIN_ID` and `GENESIS_HASH`.
+async fn load_checked_genesis(cfg: &EnvConfig) -> anyhow::Result<(configd::Genesis, [u8; 32])> {
+    let genesis = match &cfg.genesis_file {
+        Some(path) => configd::load_genesis(path).await?,
+        None => configd::Genesis { chain_id: cfg.chain_id.clone().unwrap_or_else(|| types::DEFAULT_CHAIN_ID.to_string()), ..configd::Genesis::dev() },
+    };
+    if let Some(id) = &cfg.chain_id && id != &genesis.chain_id {
+        anyhow::bail!("CHAIN_ID {} does not match genesis chain_id {}", id, genesis.chain_id);
+    }
+    let hash = genesis.hash();
+    if let Some(want) = &cfg.genesis_hash && !want.eq_ignore_ascii_case(&hex::encode(hash)) {
+        anyhow::bail!("genesis hash {} does not match GENESIS_HASH {}", hex::encode(hash), want);
+    }
+    info!(chain_id = %genesis.chain_id, hash = %hex::encode(hash), "genesis loaded");
+    Ok((genesis, hash))
+}
+
 async fn run_node(cfg: EnvConfig) -> anyhow::Result<()> {
     info!(?cfg, "launching node");
+    let (genesis, genesis_hash) = load_checked_genesis(&cfg).await?;
+    let chain_id = genesis.chain_id.clone();
 
     let (to_consensus_tx, from_mempool_rx) = mpsc::channel(1024);
     let (to_exec_tx, mut from_consensus_rx) = mpsc::channel(1024);
     let (committed_tx, committed_rx) = mpsc::channel::<Receipt>(1024);
 
-    let simple = Arc::new(exec::SimpleExecutor::new());
-    let executor: Arc<dyn exec::Executor> = Arc::new(exec::BlockStmExecutor::new(simple));
+    let accounts: Vec<(String, types::Amount)> = genesis.accounts.iter().map(|a| (a.addr.clone(), a.balance)).collect();
+    let state_kv = open_state_kv(&cfg.db_path)?;
+    let simple = Arc::new(exec::SimpleExecutor::open(chain_id.clone(), state_kv.clone(), &accounts, genesis_hash)?
+        .with_fees(exec::FeeSchedule { gas_price: genesis.fees.gas_price, treasury: genesis.fees.treasury.clone() })
+        .with_governance(genesis.governance.clone()));
+    let executor = block_executor(simple);
+    let state_roots = consensus::StateRoots::new();
+    state_roots.record(executor.last_height(), executor.state_root());
+    info!(height = executor.last_height(), "executor state loaded");
 
     let (p2p_publish_opt, p2p_rx_opt) = if let Some(addr) = &cfg.p2p_listen {
         let (p2p_handle, mut p2p_in) = spawn_p2p(addr, "txs", cfg.p2p_bootstrap.clone()).await?;
         let (tx_to_mempool, rx_to_mempool) = mpsc::channel::<types::Tx>(4096);
         tokio::spawn(async move {
             while let Some(bytes) = p2p_in.recv().await {
-                if let Ok(t) = serde_json::from_slice::<types::Transfer>(&bytes) {
+                if let Ok(t) = serde_json::from_slice::<types::SignedTx>(&bytes) {
                     let tx = types::Tx::new(t);
                     if let Err(_e) = tx_to_mempool.send(tx).await { break; }
                 }
@@ -81,14 +171,26 @@ async fn run_node(cfg: EnvConfig) -> anyhow::Result<()> {
         (Some(p2p_handle.publish.clone()), Some(rx_to_mempool))
     } else { (None, None) };
 
-    let (mempool_handle, mempool_rx) = mempool::spawn_mempool(25, 128, to_consensus_tx.clone());
+    let committed_state: Arc<dyn mempool::StateView> = Arc::new(node::ExecutorState(executor.clone()));
+    let admission = mempool::Admission::new(&chain_id, committed_state.clone());
+    let journal = mempool::Journal::new(state_kv.clone());
+    let txs = Arc::new(node::TxIndex::default());
+    let pool = cfg.mempool.pool(committed_state).with_observer(txs.clone()).with_journal(journal.clone());
+    let (batches_tx, batches_rx) = mpsc::channel(1024);
+    let mempool_handle = mempool::spawn_mempool(cfg.mempool.clone(), admission, pool, batches_tx, p2p_rx_opt).with_journal(journal.clone());
 
-    let node = crate::Node::new(mempool_handle.clone(), executor.clone(), p2p_publish_opt.clone());
-    node.spawn_commit_listener(committed_rx);
+    let da_store = da::ShardStore::new();
+    let da_sampler = if cfg.da_peers.is_empty() { None } else {
+        let fetcher = Arc::new(da::sampling::HttpShardFetcher::new(cfg.da_peers.clone()));
+        Some(Arc::new(da::sampling::SamplingClient::new(fetcher, cfg.da_samples)))
+    };
 
-    tokio::spawn(async move {
-        mempool::run_mempool(mempool_rx, to_consensus_tx, 25, 128, p2p_rx_opt).await;
-    });
+    let node = crate::Node::new(mempool_handle.clone(), executor.clone(), p2p_publish_opt.clone(), da_store.clone(), da_sampler, state_roots.clone(), exec::EventIndex::new(state_kv), txs);
+    node.spawn_commit_listener(committed_rx);
+    node.spawn_batch_tracker(batches_rx, to_consensus_tx);
+    node.spawn_da_sampler();
+    let restored = node.restore_mempool().await;
+    info!(restored, "mempool journal replayed");
 
     let store_dir = std::path::PathBuf::from("consensus_store");
     let qc_store = std::sync::Arc::new(FileStore::new(&store_dir));
@@ -101,23 +203,26 @@ async fn run_node(cfg: EnvConfig) -> anyhow::Result<()> {
 
         let mut id_to_pk: std::collections::HashMap<u32, crypto::PubKey> = std::collections::HashMap::new();
         for part in cfg.validators_keys.split(',').filter(|s| !s.trim().is_empty()) {
-            if let Some((id_s, pk_hex)) = part.split_once('@') {
-                if let Ok(id) = id_s.parse::<u32>() {
-                    if let Ok(bytes) = hex::decode(pk_hex) { if bytes.len()==32 { let mut a=[0u8;32]; a.copy_from_slice(&bytes); id_to_pk.insert(id, crypto::PubKey(a)); } }
-                }
+            if let Some((id_s, pk_hex)) = part.split_once('@')
+                && let Ok(id) = id_s.parse::<u32>()
+                && let Ok(bytes) = hex::decode(pk_hex) && bytes.len()==32 {
+                let mut a=[0u8;32]; a.copy_from_slice(&bytes); id_to_pk.insert(id, crypto::PubKey(a));
             }
         }
 
         let mut nodes = Vec::new();
-        for part in cfg.validators.split(',').filter(|s| !s.trim().is_empty()) {
-            if let Some((id_s, addr_s)) = part.split_once('@') {
-                if let (Ok(id), Ok(addr)) = (id_s.parse::<u32>(), addr_s.parse::<SocketAddr>()) {
-                    let pk = id_to_pk.get(&id).cloned().unwrap_or_else(|| {
-                        let (_sk, pk) = crypto::generate();
-                        warn!("No ed25519 pubkey for id {}, using ephemeral {}", id, pk.hex()); pk
-                    });
-                    nodes.push(Validator { id, addr, pubkey: pk });
-                }
+        for v in &genesis.validators {
+            nodes.push(Validator { id: v.id, addr: v.addr.parse()?, pubkey: v.pubkey()? });
+        }
+        let env_validators = if nodes.is_empty() { cfg.validators.as_str() } else { "" };
+        for part in env_validators.split(',').filter(|s| !s.trim().is_empty()) {
+            if let Some((id_s, addr_s)) = part.split_once('@')
+                && let (Ok(id), Ok(addr)) = (id_s.parse::<u32>(), addr_s.parse::<SocketAddr>()) {
+                let pk = id_to_pk.get(&id).cloned().unwrap_or_else(|| {
+                    let (_sk, pk) = crypto::generate();
+                    warn!("No ed25519 pubkey for id {}, using ephemeral {}", id, pk.hex()); pk
+                });
+                nodes.push(Validator { id, addr, pubkey: pk });
             }
         }
         if nodes.is_empty() {
@@ -135,20 +240,34 @@ async fn run_node(cfg: EnvConfig) -> anyhow::Result<()> {
         let my_pk = { let vk = ed25519_dalek::SigningKey::from_bytes(&my_sk.0).verifying_key(); crypto::PubKey(vk.to_bytes()) };
 
         let validators = Validators { self_id: cfg.node_id, nodes };
+        if validators.get_pub(cfg.node_id).is_none() && !cfg.da_peers.is_empty() {
+            node.spawn_commit_follower(cfg.da_peers.clone(), validators.clone(), Duration::from_millis(genesis.consensus.pacemaker_ms.max(100)));
+        }
         let mut pk_map = std::collections::HashMap::new(); for v in &validators.nodes { pk_map.insert(v.id, v.pubkey.clone()); }
         let keys = consensus::KeySet { my_sk, my_pk, pks: pk_map };
 
         let to_exec_tx2 = to_exec_tx.clone();
         let qc_store_arc = qc_store.clone();
+        let da_store2 = da_store.clone();
+        let params = consensus::Params { pacemaker_ms: genesis.consensus.pacemaker_ms, da_k: genesis.da.k, da_m: genesis.da.m };
+        let state_roots2 = state_roots.clone();
         tokio::spawn(async move {
-            consensus::run_hotstuff_quic(from_mempool_rx, to_exec_tx2, 60, qhandle.outbound, qin, validators, keys, Some(qc_store_arc)).await;
+            consensus::run_hotstuff_quic(from_mempool_rx, to_exec_tx2, params, qhandle.outbound, qin, validators, keys, Some(qc_store_arc), Some(da_store2), Some(state_roots2)).await;
         });
     }
 
     let exec2 = executor.clone();
+    let node2 = node.clone();
+    let journal2 = journal.clone();
     tokio::spawn(async move {
+        let txs = node2.tx_index();
         while let Some((batch, height)) = from_consensus_rx.recv().await {
+            txs.disseminated(&batch, height);
             let receipts = exec2.apply_batch_blocking(batch, height);
+            state_roots.record(height, exec2.state_root());
+            node2.index_events(&receipts);
+            txs.executed(&receipts);
+            journal2.forget(receipts.iter().map(|r| &r.tx_id));
             EXEC_COMMITS.inc();
             for r in receipts {
                 if let Err(e) = committed_tx.send(r).await { eprintln!("commit send error: {e}"); }
@@ -156,8 +275,10 @@ async fn run_node(cfg: EnvConfig) -> anyhow::Result<()> {
         }
     });
 
-    let api: Arc<dyn types::SubmitApi> = node.clone();
-    rpc::serve(&cfg.rpc_addr, node::NodeApiAdapter(node.clone()), executor.clone()).await?;
-
-    Ok(())
+    let served = rpc::serve(&cfg.rpc_addr, node::NodeApiAdapter(node.clone()), executor.clone(), shutdown_signal()).await;
+    // What's still pooled stays journaled for the next start.
+    mempool_handle.shutdown();
+    mempool_handle.stopped().await;
+    journal.flush().await;
+    served
 }
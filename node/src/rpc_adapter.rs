@@ -1,22 +1,68 @@
-use crate::SubmitApi;
+use crate::Node;
+use std::sync::Arc;
 
 #[derive(Clone)]
-pub struct NodeApiAdapter(pub std::sync::Arc<dyn SubmitApi>);
+pub struct NodeApiAdapter(pub Arc<Node>);
 
 #[async_trait::async_trait]
 impl rpc::NodeApi for NodeApiAdapter {
-    async fn submit_transfer(&self, t: rpc::TransferReq) -> anyhow::Result<types::Receipt> {
-        let tx = types::Transfer {
-            from: t.from,
-            to: t.to,
-            amount: t.amount,
-            nonce: 0,
-            payload: None,
-        };
-        self.0.submit_transfer(tx).await
-    }
-
-    async fn get_balance(&self, addr: String) -> anyhow::Result<u64> {
-        self.0.get_balance(addr).await
+    async fn submit_transfer(&self, t: types::SignedTx) -> anyhow::Result<types::Receipt> {
+        <Node as rpc::NodeApi>::submit_transfer(&self.0, t).await
+    }
+
+    async fn simulate(&self, txs: Vec<types::SignedTx>) -> anyhow::Result<Vec<types::Receipt>> {
+        <Node as rpc::NodeApi>::simulate(&self.0, txs).await
+    }
+
+    async fn get_balance(&self, addr: String) -> anyhow::Result<types::Amount> {
+        <Node as rpc::NodeApi>::get_balance(&self.0, addr).await
+    }
+
+    async fn get_nonce(&self, addr: String) -> anyhow::Result<u64> {
+        <Node as rpc::NodeApi>::get_nonce(&self.0, addr).await
+    }
+
+    async fn get_da_shard(&self, root: [u8; 32], index: u32) -> anyhow::Result<Option<da::Shard>> {
+        <Node as rpc::NodeApi>::get_da_shard(&self.0, root, index).await
+    }
+
+    async fn get_da_availability(&self, height: u64) -> anyhow::Result<Option<da::sampling::Availability>> {
+        <Node as rpc::NodeApi>::get_da_availability(&self.0, height).await
+    }
+
+    async fn get_da_commit(&self, height: u64) -> anyhow::Result<Option<rpc::DaCommit>> {
+        <Node as rpc::NodeApi>::get_da_commit(&self.0, height).await
+    }
+
+    async fn get_state_root(&self) -> anyhow::Result<rpc::StateRoot> {
+        <Node as rpc::NodeApi>::get_state_root(&self.0).await
+    }
+
+    async fn get_asset_balance(&self, addr: String, asset: String) -> anyhow::Result<types::Amount> {
+        <Node as rpc::NodeApi>::get_asset_balance(&self.0, addr, asset).await
+    }
+
+    async fn get_token(&self, asset: String) -> anyhow::Result<Option<exec::TokenInfo>> {
+        <Node as rpc::NodeApi>::get_token(&self.0, asset).await
+    }
+
+    async fn get_account(&self, addr: String) -> anyhow::Result<Option<exec::AccountState>> {
+        <Node as rpc::NodeApi>::get_account(&self.0, addr).await
+    }
+
+    async fn get_account_proof(&self, addr: String) -> anyhow::Result<Option<rpc::AccountProof>> {
+        <Node as rpc::NodeApi>::get_account_proof(&self.0, addr).await
+    }
+
+    async fn get_events(&self, account: String, from: exec::EventCursor, limit: usize) -> anyhow::Result<exec::EventPage> {
+        <Node as rpc::NodeApi>::get_events(&self.0, account, from, limit).await
+    }
+
+    async fn get_locks(&self, addr: String) -> anyhow::Result<Vec<exec::PendingLock>> {
+        <Node as rpc::NodeApi>::get_locks(&self.0, addr).await
+    }
+
+    async fn get_tx(&self, id: types::TxId) -> anyhow::Result<Option<types::TxState>> {
+        <Node as rpc::NodeApi>::get_tx(&self.0, id).await
     }
 }
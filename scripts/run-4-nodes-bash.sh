@@ -23,6 +23,9 @@ export MSYS2_ENV_CONV_EXCL='P2P_LISTEN;P2P_BOOTSTRAP'
   P2P_LISTEN='/ip4/127.0.0.1/tcp/9000' \
   P2P_BOOTSTRAP='[]' \
   DB_PATH=./db1 \
+  NODE_ID=1 \
+  NODE_SK=0acf27d192849e8adc4b27830573f82cbf10e0391a05533f8399a119965cd0a3 \
+  GENESIS_FILE=../../scripts/genesis.local.json \
   "$NODE" ) &
 
 ( cd run/n2 && \
@@ -31,6 +34,9 @@ export MSYS2_ENV_CONV_EXCL='P2P_LISTEN;P2P_BOOTSTRAP'
   P2P_LISTEN='/ip4/127.0.0.1/tcp/9001' \
   P2P_BOOTSTRAP='["/ip4/127.0.0.1/tcp/9000"]' \
   DB_PATH=./db2 \
+  NODE_ID=2 \
+  NODE_SK=37780a445bbc5d107ce33647a4e4a78ba8f7ddfa4f1c686b6a7200654f899aa8 \
+  GENESIS_FILE=../../scripts/genesis.local.json \
   "$NODE" ) &
 
 ( cd run/n3 && \
@@ -39,6 +45,9 @@ export MSYS2_ENV_CONV_EXCL='P2P_LISTEN;P2P_BOOTSTRAP'
   P2P_LISTEN='/ip4/127.0.0.1/tcp/9002' \
   P2P_BOOTSTRAP='["/ip4/127.0.0.1/tcp/9000"]' \
   DB_PATH=./db3 \
+  NODE_ID=3 \
+  NODE_SK=dd03f52bfb6ef0434fb95d043ac0311a85991860c9729334fa28a617b3172cad \
+  GENESIS_FILE=../../scripts/genesis.local.json \
   "$NODE" ) &
 
 ( cd run/n4 && \
@@ -47,6 +56,9 @@ export MSYS2_ENV_CONV_EXCL='P2P_LISTEN;P2P_BOOTSTRAP'
   P2P_LISTEN='/ip4/127.0.0.1/tcp/9003' \
   P2P_BOOTSTRAP='["/ip4/127.0.0.1/tcp/9000"]' \
   DB_PATH=./db4 \
+  NODE_ID=4 \
+  NODE_SK=8b89a96befcc3215ad0a0f9f2229407c4eaf0d005038674711055ee4f913f890 \
+  GENESIS_FILE=../../scripts/genesis.local.json \
   "$NODE" ) &
 
 echo "Nodes started in background. To stop: bash scripts/stop-nodes-bash.sh"
@@ -12,10 +12,10 @@ mkdir run\n2 2>nul
 mkdir run\n3 2>nul
 mkdir run\n4 2>nul
 
-start "node n1" cmd /c "cd /d run\n1 && set RPC_ADDR=127.0.0.1:8367 && set QUIC_ADDR=127.0.0.1:7000 && set P2P_LISTEN=/ip4/127.0.0.1/tcp/9000 && set P2P_BOOTSTRAP=[] && set DB_PATH=./db1 && ..\..\%NODE%"
-start "node n2" cmd /c "cd /d run\n2 && set RPC_ADDR=127.0.0.1:8368 && set QUIC_ADDR=127.0.0.1:7001 && set P2P_LISTEN=/ip4/127.0.0.1/tcp/9001 && set P2P_BOOTSTRAP=[\"/ip4/127.0.0.1/tcp/9000\"] && set DB_PATH=./db2 && ..\..\%NODE%"
-start "node n3" cmd /c "cd /d run\n3 && set RPC_ADDR=127.0.0.1:8369 && set QUIC_ADDR=127.0.0.1:7002 && set P2P_LISTEN=/ip4/127.0.0.1/tcp/9002 && set P2P_BOOTSTRAP=[\"/ip4/127.0.0.1/tcp/9000\"] && set DB_PATH=./db3 && ..\..\%NODE%"
-start "node n4" cmd /c "cd /d run\n4 && set RPC_ADDR=127.0.0.1:8370 && set QUIC_ADDR=127.0.0.1:7003 && set P2P_LISTEN=/ip4/127.0.0.1/tcp/9003 && set P2P_BOOTSTRAP=[\"/ip4/127.0.0.1/tcp/9000\"] && set DB_PATH=./db4 && ..\..\%NODE%"
+start "node n1" cmd /c "cd /d run\n1 && set RPC_ADDR=127.0.0.1:8367 && set QUIC_ADDR=127.0.0.1:7000 && set P2P_LISTEN=/ip4/127.0.0.1/tcp/9000 && set P2P_BOOTSTRAP=[] && set DB_PATH=./db1 && set NODE_ID=1&& set NODE_SK=0acf27d192849e8adc4b27830573f82cbf10e0391a05533f8399a119965cd0a3&& set GENESIS_FILE=..\..\scripts\genesis.local.json&& ..\..\%NODE%"
+start "node n2" cmd /c "cd /d run\n2 && set RPC_ADDR=127.0.0.1:8368 && set QUIC_ADDR=127.0.0.1:7001 && set P2P_LISTEN=/ip4/127.0.0.1/tcp/9001 && set P2P_BOOTSTRAP=[\"/ip4/127.0.0.1/tcp/9000\"] && set DB_PATH=./db2 && set NODE_ID=2&& set NODE_SK=37780a445bbc5d107ce33647a4e4a78ba8f7ddfa4f1c686b6a7200654f899aa8&& set GENESIS_FILE=..\..\scripts\genesis.local.json&& ..\..\%NODE%"
+start "node n3" cmd /c "cd /d run\n3 && set RPC_ADDR=127.0.0.1:8369 && set QUIC_ADDR=127.0.0.1:7002 && set P2P_LISTEN=/ip4/127.0.0.1/tcp/9002 && set P2P_BOOTSTRAP=[\"/ip4/127.0.0.1/tcp/9000\"] && set DB_PATH=./db3 && set NODE_ID=3&& set NODE_SK=dd03f52bfb6ef0434fb95d043ac0311a85991860c9729334fa28a617b3172cad&& set GENESIS_FILE=..\..\scripts\genesis.local.json&& ..\..\%NODE%"
+start "node n4" cmd /c "cd /d run\n4 && set RPC_ADDR=127.0.0.1:8370 && set QUIC_ADDR=127.0.0.1:7003 && set P2P_LISTEN=/ip4/127.0.0.1/tcp/9003 && set P2P_BOOTSTRAP=[\"/ip4/127.0.0.1/tcp/9000\"] && set DB_PATH=./db4 && set NODE_ID=4&& set NODE_SK=8b89a96befcc3215ad0a0f9f2229407c4eaf0d005038674711055ee4f913f890&& set GENESIS_FILE=..\..\scripts\genesis.local.json&& ..\..\%NODE%"
 
 echo Started nodes in 4 windows. Press any key to exit this starter script...
 pause >nul
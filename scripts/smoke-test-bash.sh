@@ -1,8 +1,9 @@
 #!/usr/bin/env bash
 set -euo pipefail
 URL=${1:-http://127.0.0.1:8367}
-echo "Balance(alice) before:"
-curl -s "$URL/balance/alice" || true; echo
+ALICE=$(cargo run -q -p bench --release -- --from alice --print-address)
+echo "Balance(alice=$ALICE) before:"
+curl -s "$URL/balance/$ALICE" || true; echo
 cargo run -p bench --release -- --n 100 --concurrency 16 --url "$URL" --from alice --to bench-bob --csv smoke.csv
-echo "Balance(alice) after:"
-curl -s "$URL/balance/alice" || true; echo
+echo "Balance(alice=$ALICE) after:"
+curl -s "$URL/balance/$ALICE" || true; echo
@@ -1,7 +1,8 @@
 use clap::Parser;
-use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
 use std::time::Instant;
 use tokio::sync::{Semaphore, mpsc};
+use types::{Receipt, Status};
 use anyhow::Result;
 
 use tracing_subscriber::EnvFilter;
@@ -21,39 +22,37 @@ struct Opts {
     #[arg(long, default_value_t = 32)]
     concurrency: usize,
 
-    /// Logical sender
+    /// Dev key seed for the sender (ignored when --sk is given)
     #[arg(long, default_value = "alice")]
     from: String,
 
-    /// Logical recipient
+    /// Hex-encoded ed25519 secret key for the sender
+    #[arg(long)]
+    sk: Option<String>,
+
+    /// Recipient address
     #[arg(long, default_value = "bench-bob")]
     to: String,
 
+    /// Fee offered by each transfer
+    #[arg(long, default_value_t = 0)]
+    fee: u64,
+
+    /// Chain id the transfers are signed for
+    #[arg(long, default_value = types::DEFAULT_CHAIN_ID)]
+    chain_id: String,
+
+    /// Print the sender address and exit
+    #[arg(long)]
+    print_address: bool,
+
     /// Optional CSV file name to write per-request latencies (ms)
     #[arg(long)]
     csv: Option<String>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct TransferReq {
-    from: String,
-    to: String,
-    amount: u64,
-}
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(tag="status")]
-enum Status {
-    Committed,
-    Rejected(String),
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct Receipt {
-    tx_id: String,
-    status: Status,
-    block_height: u64,
-    latency_ms: u64,
+    /// First nonce to use for the sender (defaults to GET /nonce/{from})
+    #[arg(long)]
+    nonce: Option<u64>,
 }
 
 #[tokio::main]
@@ -64,6 +63,18 @@ async fn main() -> Result<()> {
         .init();
 
     let opt = Opts::parse();
+    let sk = match &opt.sk {
+        Some(h) => {
+            let bytes: [u8; 32] = hex::decode(h)?.try_into().map_err(|_| anyhow::anyhow!("--sk must be 32 bytes"))?;
+            crypto::SecretKey(bytes)
+        }
+        None => crypto::keypair_from_seed(opt.from.as_bytes()).0,
+    };
+    let from = crypto::address(&crypto::public_key(&sk));
+    if opt.print_address {
+        println!("{}", from);
+        return Ok(());
+    }
     let base = opt.url.trim_end_matches('/').to_string();
     let url = format!("{}/transfer", base);
 
@@ -71,39 +82,48 @@ async fn main() -> Result<()> {
         
         .build()?;
 
+    let first_nonce = match opt.nonce {
+        Some(n) => n,
+        None => client.get(format!("{}/nonce/{}", base, from)).send().await?.json::<u64>().await?,
+    };
+
     let sem = std::sync::Arc::new(Semaphore::new(opt.concurrency));
-    let (tx, mut rx) = mpsc::channel::<f64>(opt.n);
+    let (tx, mut rx) = mpsc::channel::<(f64, Option<Status>)>(opt.n);
     let start = Instant::now();
 
-    for _ in 0..opt.n {
+    for i in 0..opt.n {
         let permit = sem.clone().acquire_owned().await?;
         let client = client.clone();
         let url = url.clone();
-        let from = opt.from.clone();
-        let to = opt.to.clone();
+        let transfer = types::Transfer { from: from.clone(), to: opt.to.clone(), amount: types::Amount(1), fee: types::Amount(opt.fee), nonce: first_nonce + i as u64, kind: types::TxKind::Transfer, asset: None, token_op: None, payload: None, expiry: None };
+        let body = types::SignedTx::sign(transfer, &sk, &opt.chain_id);
         let tx = tx.clone();
 
         tokio::spawn(async move {
             let _permit = permit;
             let t0 = Instant::now();
-            let body = TransferReq { from, to, amount: 1 };
             let resp = client.post(&url).json(&body).send().await;
             let dt_ms = t0.elapsed().as_secs_f64() * 1000.0;
 
-            if let Ok(r) = resp {
-                if r.status().is_success() {
-                    // Consume body to keep pressure realistic
-                    let _ = r.bytes().await;
-                }
-            }
-            let _ = tx.send(dt_ms).await;
+            let status = match resp {
+                Ok(r) if r.status().is_success() => r.json::<Receipt>().await.ok().map(|rc| rc.status),
+                _ => None,
+            };
+            let _ = tx.send((dt_ms, status)).await;
         });
     }
     drop(tx); // close the channel when all tasks spawned
 
     let mut lats_ms = Vec::with_capacity(opt.n);
-    while let Some(v) = rx.recv().await {
+    let (mut committed, mut failed) = (0usize, 0usize);
+    let mut rejected: BTreeMap<&'static str, usize> = BTreeMap::new();
+    while let Some((v, status)) = rx.recv().await {
         lats_ms.push(v);
+        match status {
+            Some(Status::Committed) => committed += 1,
+            Some(Status::Rejected(e)) => *rejected.entry(e.reason()).or_default() += 1,
+            None => failed += 1,
+        }
     }
 
     let elapsed = start.elapsed().as_secs_f64();
@@ -120,6 +140,10 @@ async fn main() -> Result<()> {
 
     println!("n={}, concurrency={}, elapsed={:.2}s, tps={:.1}", opt.n, opt.concurrency, elapsed, tps);
     println!("p50={:.0} ms  p95={:.0} ms  p99={:.0} ms", p50, p95, p99);
+    println!("committed={}, rejected={}, failed={}", committed, rejected.values().sum::<usize>(), failed);
+    for (reason, n) in &rejected {
+        println!("  rejected {}: {}", reason, n);
+    }
 
     if let Some(csv) = opt.csv.as_ref() {
         use std::io::Write;